@@ -3,7 +3,6 @@ use self::asset_item_list::AssetItemList;
 use crate::starry_toolkit::traits::focus::Focus;
 use starry_client::base::color::Color;
 use starry_server::base::image::Image as ImageResource;
-use starry_server::core::{SCREEN_HEIGHT, SCREEN_WIDTH};
 use starry_toolkit::layout::list::{List, ListArrangeType};
 use starry_toolkit::{
     base::{panel::Panel, rect::Rect},
@@ -33,18 +32,15 @@ pub struct AssetManager {
 
 impl AssetManager {
     pub fn new() -> Self {
+        let screen = Rect::screen();
         AssetManager {
             cur_path: String::from("/"),
             asset_grid: Grid::new(),
             asset_list: List::new(),
             items: BTreeMap::new(),
-            panel: Panel::new(
-                Rect::new(0, 0, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32),
-                "Title",
-                Color::rgb(0, 0, 0),
-            ),
+            panel: Panel::new(screen, "Title", Color::rgb(0, 0, 0)),
             loading_panel: Panel::new(
-                Rect::new(SCREEN_WIDTH as i32 - 64, SCREEN_HEIGHT as i32 - 64, 64, 64),
+                Rect::new(screen.width as i32 - 64, screen.height as i32 - 64, 64, 64),
                 "Loading",
                 Color::rgb(255, 255, 255),
             ),
@@ -1,7 +1,13 @@
 use core::fmt;
 
+use serde::{
+    de::{self, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
 /// 一个像素的颜色值
 #[derive(Clone, Copy)]
+#[repr(transparent)]
 pub struct Color {
     /// ARGB
     pub data: u32,
@@ -80,3 +86,163 @@ impl fmt::Debug for Color {
         write!(f, "{:#010X}", { self.data })
     }
 }
+
+impl Color {
+    /// # 函数功能
+    /// 解析十六进制颜色字符串
+    /// 支持`#RGB`、`#RRGGBB`和`#RRGGBBAA`，`#`可省略
+    ///
+    /// ## 参数
+    /// - string: 颜色字符串
+    ///
+    /// ## 返回值
+    /// 格式正确时返回颜色
+    pub fn from_hex(string: &str) -> Option<Color> {
+        let hex = string.strip_prefix('#').unwrap_or(string);
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+
+        match hex.len() {
+            3 => {
+                let nibble = |i: usize| u8::from_str_radix(&hex[i..i + 1], 16).ok().map(|v| v * 17);
+                Some(Color::rgb(nibble(0)?, nibble(1)?, nibble(2)?))
+            }
+            6 => Some(Color::rgb(byte(0)?, byte(2)?, byte(4)?)),
+            8 => Some(Color::rgba(byte(0)?, byte(2)?, byte(4)?, byte(6)?)),
+            _ => None,
+        }
+    }
+
+    /// 转化为`#RRGGBBAA`形式的字符串
+    pub fn to_hex(&self) -> String {
+        format!(
+            "#{:02X}{:02X}{:02X}{:02X}",
+            self.r(),
+            self.g(),
+            self.b(),
+            self.a()
+        )
+    }
+}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+/// 反序列化颜色，接受十六进制字符串或`{ r, g, b, a }`表(a可省略，默认为255)
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ColorVisitor)
+    }
+}
+
+struct ColorVisitor;
+
+impl<'de> Visitor<'de> for ColorVisitor {
+    type Value = Color;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a hex color string like \"#RRGGBB\" or a table with r, g, b and optional a"
+        )
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Color, E> {
+        Color::from_hex(value).ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Color, A::Error> {
+        let (mut r, mut g, mut b, mut a) = (None, None, None, None);
+        while let Some(key) = map.next_key::<String>()? {
+            let channel = match key.as_str() {
+                "r" => &mut r,
+                "g" => &mut g,
+                "b" => &mut b,
+                "a" => &mut a,
+                _ => return Err(de::Error::unknown_field(&key, &["r", "g", "b", "a"])),
+            };
+            if channel.is_some() {
+                return Err(de::Error::custom(format!("duplicate field `{}`", key)));
+            }
+            *channel = Some(map.next_value::<u8>()?);
+        }
+
+        Ok(Color::rgba(
+            r.ok_or_else(|| de::Error::missing_field("r"))?,
+            g.ok_or_else(|| de::Error::missing_field("g"))?,
+            b.ok_or_else(|| de::Error::missing_field("b"))?,
+            a.unwrap_or(255),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::{
+        value::{Error, MapDeserializer, StrDeserializer},
+        IntoDeserializer,
+    };
+
+    use super::*;
+
+    #[test]
+    fn from_hex_accepts_all_lengths() {
+        assert_eq!(Color::from_hex("#abc").unwrap().data, 0xFFAABBCC);
+        assert_eq!(Color::from_hex("#A0B1C2").unwrap().data, 0xFFA0B1C2);
+        assert_eq!(Color::from_hex("#A0B1C27F").unwrap().data, 0x7FA0B1C2);
+        assert_eq!(Color::from_hex("a0b1c2").unwrap().data, 0xFFA0B1C2);
+    }
+
+    #[test]
+    fn from_hex_rejects_invalid_strings() {
+        for string in [
+            "", "#", "#ab", "#abcd", "#12345", "#1234567", "#gggggg", "#+1+2+3", "#ａbc",
+        ] {
+            assert!(Color::from_hex(string).is_none(), "{}", string);
+        }
+    }
+
+    #[test]
+    fn to_hex_round_trips() {
+        let color = Color::rgba(1, 0xAB, 0xFF, 0x80);
+        assert_eq!(color.to_hex(), "#01ABFF80");
+        assert_eq!(Color::from_hex(&color.to_hex()).unwrap().data, color.data);
+    }
+
+    #[test]
+    fn deserialize_string() {
+        let deserializer = StrDeserializer::<Error>::new("#102030");
+        assert_eq!(Color::deserialize(deserializer).unwrap().data, 0xFF102030);
+
+        let deserializer = StrDeserializer::<Error>::new("red");
+        assert!(Color::deserialize(deserializer).is_err());
+    }
+
+    #[test]
+    fn deserialize_table() {
+        let table = |entries: Vec<(&'static str, u8)>| {
+            Color::deserialize(MapDeserializer::<_, Error>::new(
+                entries.into_iter().map(|(k, v)| (k, v.into_deserializer())),
+            ))
+        };
+
+        assert_eq!(
+            table(vec![("r", 1), ("g", 2), ("b", 3)]).unwrap().data,
+            0xFF010203
+        );
+        assert_eq!(
+            table(vec![("b", 3), ("a", 4), ("r", 1), ("g", 2)])
+                .unwrap()
+                .data,
+            0x04010203
+        );
+        assert!(table(vec![("r", 1), ("g", 2)]).is_err());
+        assert!(table(vec![("r", 1), ("g", 2), ("b", 3), ("x", 4)]).is_err());
+        assert!(table(vec![("r", 1), ("r", 1), ("g", 2), ("b", 3)]).is_err());
+    }
+}
@@ -6,10 +6,22 @@ pub enum EventOption {
     MouseRelative(MouseRelativeEvent),
     /// 鼠标按键事件
     Button(ButtonEvent),
+    /// 鼠标位置更新事件
+    MouseUpdate(MouseUpdateEvent),
     /// 窗口位置移动事件
     WindowMove(WindowMoveEvent),
     /// 窗口大小改变事件
     WindowResize(WindowResizeEvent),
+    /// 窗口获得或失去焦点事件
+    Focus(FocusEvent),
+    /// 窗口被关闭事件
+    Quit(QuitEvent),
+    /// 滚轮滚动事件
+    Scroll(ScrollEvent),
+    /// 鼠标进入窗口事件
+    Enter(EnterEvent),
+    /// 鼠标离开窗口事件
+    Leave(LeaveEvent),
     /// 未知事件
     Unknown(Event),
     /// 空事件
@@ -23,9 +35,14 @@ pub const EVENT_BUTTON: i64 = 3;
 pub const EVENT_MOUSE_UPDATE: i64 = 4;
 pub const EVENT_WINDOW_MOVE: i64 = 5;
 pub const EVENT_WINDOW_RESIZE: i64 = 6;
+pub const EVENT_FOCUS: i64 = 7;
+pub const EVENT_QUIT: i64 = 8;
+pub const EVENT_SCROLL: i64 = 9;
+pub const EVENT_ENTER: i64 = 10;
+pub const EVENT_LEAVE: i64 = 11;
 
 /// 通用事件
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Event {
     pub code: i64,
     pub a: i64,
@@ -49,6 +66,14 @@ impl Event {
                 EventOption::MouseRelative(MouseRelativeEvent::from_event(self))
             }
             EVENT_BUTTON => EventOption::Button(ButtonEvent::from_event(self)),
+            EVENT_MOUSE_UPDATE => EventOption::MouseUpdate(MouseUpdateEvent::from_event(self)),
+            EVENT_WINDOW_MOVE => EventOption::WindowMove(WindowMoveEvent::from_event(self)),
+            EVENT_WINDOW_RESIZE => EventOption::WindowResize(WindowResizeEvent::from_event(self)),
+            EVENT_FOCUS => EventOption::Focus(FocusEvent::from_event(self)),
+            EVENT_QUIT => EventOption::Quit(QuitEvent::from_event(self)),
+            EVENT_SCROLL => EventOption::Scroll(ScrollEvent::from_event(self)),
+            EVENT_ENTER => EventOption::Enter(EnterEvent::from_event(self)),
+            EVENT_LEAVE => EventOption::Leave(LeaveEvent::from_event(self)),
             _ => EventOption::Unknown(self),
         }
     }
@@ -59,7 +84,7 @@ impl Event {
 pub struct KeyEvent {
     /// 按键字符
     pub character: char,
-    /// 按键扫描码
+    /// 按键扫描码(PS/2第一套扫描码的通码，0xE0前缀的扩展键最高位置1)
     pub scancode: u8,
     /// 是否按下
     pub pressed: bool,
@@ -123,14 +148,21 @@ pub struct ButtonEvent {
     pub right: bool,
     /// 中键是否按下
     pub middle: bool,
+    /// 后退键(第4键)是否按下
+    pub back: bool,
+    /// 前进键(第5键)是否按下
+    pub forward: bool,
 }
 
 impl ButtonEvent {
+    /// 根据PS/2鼠标数据包的首字节创建(bit0左键，bit1右键，bit2中键)
     pub fn new(byte: u8) -> Self {
         ButtonEvent {
-            left: byte & (1 << 0) == 1,
-            middle: byte & (1 << 1) == 1,
-            right: byte & (1 << 2) == 1,
+            left: byte & (1 << 0) != 0,
+            right: byte & (1 << 1) != 0,
+            middle: byte & (1 << 2) != 0,
+            back: false,
+            forward: false,
         }
     }
 
@@ -138,7 +170,11 @@ impl ButtonEvent {
     pub fn to_event(&self) -> Event {
         Event {
             code: EVENT_BUTTON,
-            a: self.left as i64 | (self.middle as i64) << 1 | (self.right as i64) << 2,
+            a: self.left as i64
+                | (self.middle as i64) << 1
+                | (self.right as i64) << 2
+                | (self.back as i64) << 3
+                | (self.forward as i64) << 4,
             b: 0,
         }
     }
@@ -146,14 +182,18 @@ impl ButtonEvent {
     /// 从Event转换为ButtonEvent
     pub fn from_event(event: Event) -> ButtonEvent {
         ButtonEvent {
-            left: event.a & (1 << 0) == 1,
-            middle: event.a & (1 << 1) == 1,
-            right: event.a & (1 << 2) == 1,
+            left: event.a & (1 << 0) != 0,
+            middle: event.a & (1 << 1) != 0,
+            right: event.a & (1 << 2) != 0,
+            back: event.a & (1 << 3) != 0,
+            forward: event.a & (1 << 4) != 0,
         }
     }
 }
 
 /// 鼠标位置更新事件
+/// 输入设备发给服务器时为屏幕坐标，服务器发给窗口时为窗口内坐标
+#[derive(Copy, Clone, Debug)]
 pub struct MouseUpdateEvent {
     /// 更新后鼠标位置x坐标
     pub x: i32,
@@ -227,3 +267,115 @@ impl WindowResizeEvent {
         }
     }
 }
+
+/// 窗口获得或失去焦点事件
+#[derive(Copy, Clone, Debug)]
+pub struct FocusEvent {
+    /// 是否获得焦点
+    pub focused: bool,
+}
+
+impl FocusEvent {
+    pub fn to_event(&self) -> Event {
+        Event {
+            code: EVENT_FOCUS,
+            a: self.focused as i64,
+            b: 0,
+        }
+    }
+
+    pub fn from_event(event: Event) -> FocusEvent {
+        FocusEvent {
+            focused: event.a != 0,
+        }
+    }
+}
+
+/// 窗口被关闭事件
+/// 服务器发送该事件后即销毁窗口并断开连接
+#[derive(Copy, Clone, Debug)]
+pub struct QuitEvent;
+
+impl QuitEvent {
+    pub fn to_event(&self) -> Event {
+        Event {
+            code: EVENT_QUIT,
+            a: 0,
+            b: 0,
+        }
+    }
+
+    pub fn from_event(_event: Event) -> QuitEvent {
+        QuitEvent
+    }
+}
+
+/// 滚轮滚动事件
+#[derive(Copy, Clone, Debug)]
+pub struct ScrollEvent {
+    /// 水平滚动量，向右为正
+    pub x: i32,
+    /// 垂直滚动量，向上为正
+    pub y: i32,
+}
+
+impl ScrollEvent {
+    pub fn to_event(&self) -> Event {
+        Event {
+            code: EVENT_SCROLL,
+            a: self.x as i64,
+            b: self.y as i64,
+        }
+    }
+
+    pub fn from_event(event: Event) -> ScrollEvent {
+        ScrollEvent {
+            x: event.a as i32,
+            y: event.b as i32,
+        }
+    }
+}
+
+/// 鼠标进入窗口事件
+#[derive(Copy, Clone, Debug)]
+pub struct EnterEvent {
+    /// 鼠标在窗口内的x坐标
+    pub x: i32,
+    /// 鼠标在窗口内的y坐标
+    pub y: i32,
+}
+
+impl EnterEvent {
+    pub fn to_event(&self) -> Event {
+        Event {
+            code: EVENT_ENTER,
+            a: self.x as i64,
+            b: self.y as i64,
+        }
+    }
+
+    pub fn from_event(event: Event) -> EnterEvent {
+        EnterEvent {
+            x: event.a as i32,
+            y: event.b as i32,
+        }
+    }
+}
+
+/// 鼠标离开窗口事件
+#[derive(Copy, Clone, Debug)]
+pub struct LeaveEvent;
+
+impl LeaveEvent {
+    pub fn to_event(&self) -> Event {
+        Event {
+            code: EVENT_LEAVE,
+            a: 0,
+            b: 0,
+        }
+    }
+
+    pub fn from_event(_event: Event) -> LeaveEvent {
+        LeaveEvent
+    }
+}
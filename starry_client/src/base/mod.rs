@@ -1,4 +1,7 @@
 pub mod color;
 pub mod event;
 pub mod graphicspath;
+pub mod protocol;
 pub mod renderer;
+pub mod screen;
+pub mod shm;
@@ -0,0 +1,995 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    mem,
+    os::unix::{
+        io::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+        net::UnixStream,
+    },
+    ptr,
+};
+
+use bitflags::bitflags;
+
+use super::{
+    event::Event,
+    screen::{Channel, PixelFormat, ScreenInfo},
+};
+
+/// 服务器监听的套接字路径
+pub const SERVER_SOCKET_PATH: &str = "/tmp/starry_server.sock";
+
+/// 数据包头部长度(类型码4字节 + 负载长度4字节)
+const HEADER_SIZE: usize = 8;
+
+/// 单个数据包负载的最大长度
+const MAX_PAYLOAD_SIZE: usize = 64 * 1024;
+
+/// 非阻塞模式下允许积压的未发送数据量，超过后认为对端已停止读取
+const MAX_PENDING_SIZE: usize = 256 * 1024;
+
+/// 单次接收时最多接收的文件描述符数量
+const MAX_RECEIVED_FDS: usize = 4;
+
+pub const REQUEST_WINDOW_NEW: u32 = 1;
+pub const REQUEST_WINDOW_DESTROY: u32 = 2;
+pub const REQUEST_WINDOW_MOVE: u32 = 3;
+pub const REQUEST_WINDOW_RESIZE: u32 = 4;
+pub const REQUEST_WINDOW_SET_TITLE: u32 = 5;
+pub const REQUEST_WINDOW_DAMAGE: u32 = 6;
+pub const REQUEST_SCREEN_INFO: u32 = 7;
+pub const REQUEST_CONFIG_RELOAD: u32 = 8;
+pub const REQUEST_WINDOW_SET_CURSOR: u32 = 9;
+pub const REQUEST_WINDOW_SET_SIZE_LIMITS: u32 = 10;
+pub const REQUEST_WINDOW_RAISE: u32 = 11;
+pub const REQUEST_WINDOW_LOWER: u32 = 12;
+pub const REQUEST_WINDOW_SET_LAYER: u32 = 13;
+
+pub const REPLY_WINDOW_CREATED: u32 = 1;
+pub const REPLY_EVENT: u32 = 2;
+pub const REPLY_SCREEN_INFO: u32 = 3;
+
+bitflags! {
+    /// 窗口属性
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct WindowFlags: u32 {
+        /// 无边界(不绘制标题栏)
+        const BORDERLESS = 0b0000_0001;
+        /// 大小可变
+        const RESIZABLE = 0b0000_0010;
+        /// (半)透明
+        const TRANSPARENT = 0b0000_0100;
+        /// 不可关闭
+        const UNCLOSABLE = 0b0000_1000;
+        /// 前景窗口
+        const FRONT = 0b0001_0000;
+        /// 背景窗口
+        const BACK = 0b0010_0000;
+    }
+}
+
+/// 鼠标位于窗口内容区域时显示的样式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowCursor {
+    /// 默认箭头
+    #[default]
+    Normal,
+    /// 文本输入(I形)
+    Text,
+    /// 移动
+    Move,
+    /// 忙碌
+    Busy,
+    /// 禁止操作
+    NotAllowed,
+}
+
+impl WindowCursor {
+    pub fn to_u32(self) -> u32 {
+        self as u32
+    }
+
+    /// 未知的值视为默认样式
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            1 => WindowCursor::Text,
+            2 => WindowCursor::Move,
+            3 => WindowCursor::Busy,
+            4 => WindowCursor::NotAllowed,
+            _ => WindowCursor::Normal,
+        }
+    }
+}
+
+/// 窗口所在的层，同一层内的窗口可以互相遮挡
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowLayer {
+    /// 位于普通窗口之下(如桌面)
+    Back,
+    /// 普通窗口
+    #[default]
+    Normal,
+    /// 总在普通窗口之上
+    Front,
+}
+
+impl WindowLayer {
+    pub fn to_u32(self) -> u32 {
+        self as u32
+    }
+
+    /// 未知的值视为普通层
+    pub fn from_u32(value: u32) -> Self {
+        match value {
+            0 => WindowLayer::Back,
+            2 => WindowLayer::Front,
+            _ => WindowLayer::Normal,
+        }
+    }
+}
+
+/// 可在连接上传输的数据包需要实现的特性
+pub trait Packet: Sized {
+    /// 编码为(类型码，负载)
+    fn encode(&self) -> (u32, Vec<u8>);
+
+    /// 根据类型码和负载解码
+    fn decode(code: u32, payload: &[u8]) -> io::Result<Self>;
+}
+
+/// 客户端发往服务器的请求
+#[derive(Clone, Debug, PartialEq)]
+pub enum Request {
+    /// 新建窗口，每个连接对应一个窗口
+    /// 窗体像素存放在客户端创建的共享内存中，其文件描述符随请求一同发送
+    WindowNew {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+        flags: WindowFlags,
+        title: String,
+    },
+    /// 销毁窗口
+    WindowDestroy,
+    /// 移动窗口
+    WindowMove { x: i32, y: i32 },
+    /// 改变窗口大小，新大小的共享内存的文件描述符随请求一同发送
+    WindowResize { width: u32, height: u32 },
+    /// 设置窗口标题
+    WindowSetTitle { title: String },
+    /// 窗口内容更新：共享内存中发生变化的矩形区域(窗口内相对位置)
+    WindowDamage {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+    /// 查询屏幕信息，无需先创建窗口
+    ScreenInfo,
+    /// 通知服务器重新加载配置文件，无需先创建窗口
+    ConfigReload,
+    /// 设置鼠标位于窗口内容区域时的样式
+    WindowSetCursor { cursor: WindowCursor },
+    /// 设置用户拖动缩放窗口时允许的最小和最大宽高
+    WindowSetSizeLimits {
+        min_width: u32,
+        min_height: u32,
+        max_width: u32,
+        max_height: u32,
+    },
+    /// 将窗口提升到所在层的最上方
+    WindowRaise,
+    /// 将窗口降低到所在层的最下方
+    WindowLower,
+    /// 将窗口移到指定的层
+    WindowSetLayer { layer: WindowLayer },
+}
+
+impl Packet for Request {
+    fn encode(&self) -> (u32, Vec<u8>) {
+        let mut writer = PacketWriter::new();
+        let code = match self {
+            Request::WindowNew {
+                x,
+                y,
+                width,
+                height,
+                flags,
+                title,
+            } => {
+                writer.i32(*x).i32(*y).u32(*width).u32(*height);
+                writer.u32(flags.bits()).str(title);
+                REQUEST_WINDOW_NEW
+            }
+            Request::WindowDestroy => REQUEST_WINDOW_DESTROY,
+            Request::WindowMove { x, y } => {
+                writer.i32(*x).i32(*y);
+                REQUEST_WINDOW_MOVE
+            }
+            Request::WindowResize { width, height } => {
+                writer.u32(*width).u32(*height);
+                REQUEST_WINDOW_RESIZE
+            }
+            Request::WindowSetTitle { title } => {
+                writer.str(title);
+                REQUEST_WINDOW_SET_TITLE
+            }
+            Request::WindowDamage {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                writer.i32(*x).i32(*y).u32(*width).u32(*height);
+                REQUEST_WINDOW_DAMAGE
+            }
+            Request::ScreenInfo => REQUEST_SCREEN_INFO,
+            Request::ConfigReload => REQUEST_CONFIG_RELOAD,
+            Request::WindowSetCursor { cursor } => {
+                writer.u32(cursor.to_u32());
+                REQUEST_WINDOW_SET_CURSOR
+            }
+            Request::WindowSetSizeLimits {
+                min_width,
+                min_height,
+                max_width,
+                max_height,
+            } => {
+                writer.u32(*min_width).u32(*min_height);
+                writer.u32(*max_width).u32(*max_height);
+                REQUEST_WINDOW_SET_SIZE_LIMITS
+            }
+            Request::WindowRaise => REQUEST_WINDOW_RAISE,
+            Request::WindowLower => REQUEST_WINDOW_LOWER,
+            Request::WindowSetLayer { layer } => {
+                writer.u32(layer.to_u32());
+                REQUEST_WINDOW_SET_LAYER
+            }
+        };
+        (code, writer.finish())
+    }
+
+    fn decode(code: u32, payload: &[u8]) -> io::Result<Self> {
+        let mut reader = PacketReader::new(payload);
+        let request = match code {
+            REQUEST_WINDOW_NEW => Request::WindowNew {
+                x: reader.i32()?,
+                y: reader.i32()?,
+                width: reader.u32()?,
+                height: reader.u32()?,
+                flags: WindowFlags::from_bits_truncate(reader.u32()?),
+                title: reader.str()?,
+            },
+            REQUEST_WINDOW_DESTROY => Request::WindowDestroy,
+            REQUEST_WINDOW_MOVE => Request::WindowMove {
+                x: reader.i32()?,
+                y: reader.i32()?,
+            },
+            REQUEST_WINDOW_RESIZE => Request::WindowResize {
+                width: reader.u32()?,
+                height: reader.u32()?,
+            },
+            REQUEST_WINDOW_SET_TITLE => Request::WindowSetTitle {
+                title: reader.str()?,
+            },
+            REQUEST_WINDOW_DAMAGE => Request::WindowDamage {
+                x: reader.i32()?,
+                y: reader.i32()?,
+                width: reader.u32()?,
+                height: reader.u32()?,
+            },
+            REQUEST_SCREEN_INFO => Request::ScreenInfo,
+            REQUEST_CONFIG_RELOAD => Request::ConfigReload,
+            REQUEST_WINDOW_SET_CURSOR => Request::WindowSetCursor {
+                cursor: WindowCursor::from_u32(reader.u32()?),
+            },
+            REQUEST_WINDOW_SET_SIZE_LIMITS => Request::WindowSetSizeLimits {
+                min_width: reader.u32()?,
+                min_height: reader.u32()?,
+                max_width: reader.u32()?,
+                max_height: reader.u32()?,
+            },
+            REQUEST_WINDOW_RAISE => Request::WindowRaise,
+            REQUEST_WINDOW_LOWER => Request::WindowLower,
+            REQUEST_WINDOW_SET_LAYER => Request::WindowSetLayer {
+                layer: WindowLayer::from_u32(reader.u32()?),
+            },
+            _ => return Err(invalid_data(format!("unknown request code {}", code))),
+        };
+        Ok(request)
+    }
+}
+
+/// 服务器发往客户端的回复
+#[derive(Clone, Debug, PartialEq)]
+pub enum Reply {
+    /// 窗口创建成功，附带服务器最终确定的窗口位置
+    WindowCreated { id: u32, x: i32, y: i32 },
+    /// 传递给窗口的事件
+    Event(Event),
+    /// 屏幕信息
+    ScreenInfo(ScreenInfo),
+}
+
+impl Packet for Reply {
+    fn encode(&self) -> (u32, Vec<u8>) {
+        let mut writer = PacketWriter::new();
+        let code = match self {
+            Reply::WindowCreated { id, x, y } => {
+                writer.u32(*id).i32(*x).i32(*y);
+                REPLY_WINDOW_CREATED
+            }
+            Reply::Event(event) => {
+                writer.i64(event.code).i64(event.a).i64(event.b);
+                REPLY_EVENT
+            }
+            Reply::ScreenInfo(info) => {
+                writer.u32(info.width).u32(info.height).u32(info.stride);
+                writer.u32(info.format.bits_per_pixel);
+                for channel in [
+                    info.format.red,
+                    info.format.green,
+                    info.format.blue,
+                    info.format.alpha,
+                ] {
+                    writer.u32(channel.offset).u32(channel.length);
+                }
+                REPLY_SCREEN_INFO
+            }
+        };
+        (code, writer.finish())
+    }
+
+    fn decode(code: u32, payload: &[u8]) -> io::Result<Self> {
+        let mut reader = PacketReader::new(payload);
+        let reply = match code {
+            REPLY_WINDOW_CREATED => Reply::WindowCreated {
+                id: reader.u32()?,
+                x: reader.i32()?,
+                y: reader.i32()?,
+            },
+            REPLY_EVENT => Reply::Event(Event {
+                code: reader.i64()?,
+                a: reader.i64()?,
+                b: reader.i64()?,
+            }),
+            REPLY_SCREEN_INFO => Reply::ScreenInfo(ScreenInfo {
+                width: reader.u32()?,
+                height: reader.u32()?,
+                stride: reader.u32()?,
+                format: PixelFormat {
+                    bits_per_pixel: reader.u32()?,
+                    red: reader.channel()?,
+                    green: reader.channel()?,
+                    blue: reader.channel()?,
+                    alpha: reader.channel()?,
+                },
+            }),
+            _ => return Err(invalid_data(format!("unknown reply code {}", code))),
+        };
+        Ok(reply)
+    }
+}
+
+/// 通知服务器重新加载配置文件
+pub fn request_config_reload() -> io::Result<()> {
+    Connection::connect()?.send(&Request::ConfigReload)
+}
+
+/// 客户端与服务器之间的一条连接
+/// 负责数据包的分帧、发送和接收
+pub struct Connection {
+    /// 套接字
+    stream: UnixStream,
+    /// 尚未组成完整数据包的接收数据
+    buffer: Vec<u8>,
+    /// 尚未写出的发送数据
+    outgoing: Vec<u8>,
+    /// 已接收但尚未被取走的文件描述符
+    fds: VecDeque<OwnedFd>,
+}
+
+impl Connection {
+    pub fn new(stream: UnixStream) -> Self {
+        Connection {
+            stream,
+            buffer: Vec::new(),
+            outgoing: Vec::new(),
+            fds: VecDeque::new(),
+        }
+    }
+
+    /// 连接到服务器
+    pub fn connect() -> io::Result<Self> {
+        Ok(Connection::new(UnixStream::connect(SERVER_SOCKET_PATH)?))
+    }
+
+    /// 返回底层套接字
+    pub fn stream(&self) -> &UnixStream {
+        &self.stream
+    }
+
+    /// 设置发送是否为非阻塞模式，接收总是非阻塞的
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> io::Result<()> {
+        self.stream.set_nonblocking(nonblocking)
+    }
+
+    /// # 函数功能
+    /// 发送一个数据包
+    /// 非阻塞模式下写不完的数据留在发送队列中，由之后的`flush`继续写出
+    ///
+    /// ## 参数
+    /// - packet: 数据包
+    ///
+    /// ## 返回值
+    /// 非阻塞模式下积压的数据超过上限时返回错误，此时数据包被丢弃
+    pub fn send<P: Packet>(&mut self, packet: &P) -> io::Result<()> {
+        if self.outgoing.len() > MAX_PENDING_SIZE {
+            return Err(Self::backlog_error());
+        }
+
+        let (code, payload) = packet.encode();
+        self.outgoing.extend_from_slice(&code.to_le_bytes());
+        self.outgoing
+            .extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        self.outgoing.extend_from_slice(&payload);
+
+        self.flush()
+    }
+
+    /// # 函数功能
+    /// 尽可能写出发送队列中的数据
+    /// 阻塞模式下写出全部数据，非阻塞模式下写到套接字缓冲区满为止
+    ///
+    /// ## 返回值
+    /// 非阻塞模式下积压的数据超过上限时返回错误
+    pub fn flush(&mut self) -> io::Result<()> {
+        let mut written = 0;
+        let result = loop {
+            if written == self.outgoing.len() {
+                break Ok(());
+            }
+            match self.stream.write(&self.outgoing[written..]) {
+                Ok(0) => break Err(io::ErrorKind::WriteZero.into()),
+                Ok(count) => written += count,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break Ok(()),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => break Err(err),
+            }
+        };
+        self.outgoing.drain(..written);
+
+        result?;
+        if self.outgoing.len() > MAX_PENDING_SIZE {
+            return Err(Self::backlog_error());
+        }
+        Ok(())
+    }
+
+    /// # 函数功能
+    /// 发送一个数据包，同时传递一个文件描述符
+    /// 文件描述符附在数据包的第一个字节上，对端接收该数据包时一并收到
+    ///
+    /// ## 参数
+    /// - packet: 数据包
+    /// - fd: 文件描述符
+    ///
+    /// ## 返回值
+    /// 发送队列中有数据无法写出时返回WouldBlock错误
+    pub fn send_with_fd<P: Packet>(&mut self, packet: &P, fd: BorrowedFd) -> io::Result<()> {
+        // 先写出之前的数据，保证文件描述符与数据包对应
+        self.flush()?;
+        if self.has_pending() {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        let (code, payload) = packet.encode();
+        let mut data = Vec::with_capacity(HEADER_SIZE + payload.len());
+        data.extend_from_slice(&code.to_le_bytes());
+        data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        data.extend_from_slice(&payload);
+
+        let written = loop {
+            match Self::send_fd(self.stream.as_raw_fd(), &data, fd.as_raw_fd()) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                result => break result?,
+            }
+        };
+        self.outgoing.extend_from_slice(&data[written..]);
+        self.flush()
+    }
+
+    /// 调用sendmsg发送数据和一个文件描述符，返回写出的字节数
+    fn send_fd(socket: RawFd, data: &[u8], fd: RawFd) -> io::Result<usize> {
+        let mut iov = libc::iovec {
+            iov_base: data.as_ptr() as *mut libc::c_void,
+            iov_len: data.len(),
+        };
+        let space = unsafe { libc::CMSG_SPACE(mem::size_of::<RawFd>() as u32) } as usize;
+        let mut control = vec![0u8; space];
+
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = space as _;
+
+        let count = unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<RawFd>() as u32) as _;
+            ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, fd);
+            libc::sendmsg(socket, &msg, libc::MSG_NOSIGNAL)
+        };
+        if count < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(count as usize)
+    }
+
+    /// 取出最早接收到的文件描述符
+    pub fn take_fd(&mut self) -> Option<OwnedFd> {
+        self.fds.pop_front()
+    }
+
+    /// 发送队列中是否还有未写出的数据
+    pub fn has_pending(&self) -> bool {
+        !self.outgoing.is_empty()
+    }
+
+    fn backlog_error() -> io::Error {
+        io::Error::new(
+            io::ErrorKind::WouldBlock,
+            "peer is not reading, send backlog exceeds limit",
+        )
+    }
+
+    /// # 函数功能
+    /// 读取当前可读的数据并解析出所有完整的数据包
+    /// 无论套接字是否为非阻塞模式都不会阻塞，没有数据时返回空数组
+    ///
+    /// ## 返回值
+    /// 解析出的数据包数组，对端关闭连接时返回UnexpectedEof错误
+    pub fn receive<P: Packet>(&mut self) -> io::Result<Vec<P>> {
+        let mut buf = [0u8; 4096];
+        let mut closed = false;
+        loop {
+            match self.receive_some(&mut buf) {
+                Ok(0) => {
+                    closed = true;
+                    break;
+                }
+                Ok(count) => self.buffer.extend_from_slice(&buf[..count]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        let mut packets = Vec::new();
+        while let Some(packet) = self.next_packet()? {
+            packets.push(packet);
+        }
+
+        if closed && packets.is_empty() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(packets)
+    }
+
+    /// 调用recvmsg非阻塞地读取数据，附带的文件描述符放入队列
+    fn receive_some(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut iov = libc::iovec {
+            iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+            iov_len: buf.len(),
+        };
+        let space =
+            unsafe { libc::CMSG_SPACE((MAX_RECEIVED_FDS * mem::size_of::<RawFd>()) as u32) };
+        let mut control = vec![0u8; space as usize];
+
+        let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = space as _;
+
+        let count = unsafe {
+            libc::recvmsg(
+                self.stream.as_raw_fd(),
+                &mut msg,
+                libc::MSG_DONTWAIT | libc::MSG_CMSG_CLOEXEC,
+            )
+        };
+        if count < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                    let data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                    let header = libc::CMSG_LEN(0) as usize;
+                    let count = ((*cmsg).cmsg_len as usize - header) / mem::size_of::<RawFd>();
+                    for i in 0..count {
+                        let fd = ptr::read_unaligned(data.add(i));
+                        self.fds.push_back(OwnedFd::from_raw_fd(fd));
+                    }
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+
+        if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+            return Err(invalid_data("too many file descriptors".to_string()));
+        }
+        Ok(count as usize)
+    }
+
+    /// 阻塞等待下一个数据包
+    pub fn receive_blocking<P: Packet>(&mut self) -> io::Result<P> {
+        loop {
+            if let Some(packet) = self.next_packet()? {
+                return Ok(packet);
+            }
+
+            let mut buf = [0u8; 4096];
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(count) => self.buffer.extend_from_slice(&buf[..count]),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// 从缓冲区中取出一个完整的数据包
+    fn next_packet<P: Packet>(&mut self) -> io::Result<Option<P>> {
+        if self.buffer.len() < HEADER_SIZE {
+            return Ok(None);
+        }
+
+        let code = u32::from_le_bytes(self.buffer[0..4].try_into().unwrap());
+        let len = u32::from_le_bytes(self.buffer[4..8].try_into().unwrap()) as usize;
+        if len > MAX_PAYLOAD_SIZE {
+            return Err(invalid_data(format!("packet too large: {} bytes", len)));
+        }
+        if self.buffer.len() < HEADER_SIZE + len {
+            return Ok(None);
+        }
+
+        let packet = P::decode(code, &self.buffer[HEADER_SIZE..HEADER_SIZE + len]);
+        self.buffer.drain(..HEADER_SIZE + len);
+        packet.map(Some)
+    }
+}
+
+/// 数据包负载的编码器(小端序)
+struct PacketWriter {
+    data: Vec<u8>,
+}
+
+impl PacketWriter {
+    fn new() -> Self {
+        PacketWriter { data: Vec::new() }
+    }
+
+    fn u32(&mut self, value: u32) -> &mut Self {
+        self.data.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn i32(&mut self, value: i32) -> &mut Self {
+        self.data.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn i64(&mut self, value: i64) -> &mut Self {
+        self.data.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    fn str(&mut self, value: &str) -> &mut Self {
+        self.u32(value.len() as u32);
+        self.data.extend_from_slice(value.as_bytes());
+        self
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// 数据包负载的解码器(小端序)
+struct PacketReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> PacketReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        PacketReader { data }
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(invalid_data("packet payload truncated".to_string()));
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i64(&mut self) -> io::Result<i64> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn str(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| invalid_data("invalid utf-8 string".to_string()))
+    }
+
+    fn channel(&mut self) -> io::Result<Channel> {
+        Ok(Channel {
+            offset: self.u32()?,
+            length: self.u32()?,
+        })
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, os::unix::net::UnixStream};
+
+    use super::*;
+
+    /// 编码为带头部的完整数据包
+    fn frame<P: Packet>(packet: &P) -> Vec<u8> {
+        let (code, payload) = packet.encode();
+        let mut data = code.to_le_bytes().to_vec();
+        data.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        data.extend_from_slice(&payload);
+        data
+    }
+
+    fn round_trip<P: Packet + PartialEq + std::fmt::Debug>(packet: P) {
+        let (code, payload) = packet.encode();
+        assert_eq!(P::decode(code, &payload).unwrap(), packet);
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        round_trip(Request::WindowNew {
+            x: -1,
+            y: 20,
+            width: 640,
+            height: 480,
+            flags: WindowFlags::RESIZABLE | WindowFlags::UNCLOSABLE | WindowFlags::FRONT,
+            title: "标题 title".to_string(),
+        });
+        round_trip(Request::WindowDestroy);
+        round_trip(Request::WindowMove { x: -5, y: 7 });
+        round_trip(Request::WindowResize {
+            width: 1,
+            height: 2,
+        });
+        round_trip(Request::WindowSetTitle {
+            title: String::new(),
+        });
+        round_trip(Request::WindowDamage {
+            x: 1,
+            y: 2,
+            width: 3,
+            height: 4,
+        });
+        round_trip(Request::ScreenInfo);
+        round_trip(Request::ConfigReload);
+        round_trip(Request::WindowSetCursor {
+            cursor: WindowCursor::Text,
+        });
+        round_trip(Request::WindowSetSizeLimits {
+            min_width: 1,
+            min_height: 2,
+            max_width: 3,
+            max_height: u32::MAX,
+        });
+        round_trip(Request::WindowRaise);
+        round_trip(Request::WindowLower);
+        for layer in [WindowLayer::Back, WindowLayer::Normal, WindowLayer::Front] {
+            round_trip(Request::WindowSetLayer { layer });
+        }
+    }
+
+    #[test]
+    fn replies_round_trip() {
+        round_trip(Reply::WindowCreated { id: 3, x: -1, y: 2 });
+        round_trip(Reply::Event(Event {
+            code: 5,
+            a: i64::MIN,
+            b: i64::MAX,
+        }));
+        round_trip(Reply::ScreenInfo(ScreenInfo {
+            width: 800,
+            height: 600,
+            stride: 1600,
+            format: PixelFormat {
+                bits_per_pixel: 16,
+                red: Channel::new(11, 5),
+                green: Channel::new(5, 6),
+                blue: Channel::new(0, 5),
+                alpha: Channel::new(0, 0),
+            },
+        }));
+    }
+
+    #[test]
+    fn window_flags_keep_known_bits() {
+        let all = WindowFlags::all();
+        let request = Request::WindowNew {
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+            flags: all,
+            title: String::new(),
+        };
+        let (code, mut payload) = request.encode();
+        // 未知的属性位在解码时被忽略
+        payload[16..20].copy_from_slice(&(all.bits() | 0x8000_0000).to_le_bytes());
+        assert_eq!(Request::decode(code, &payload).unwrap(), request);
+    }
+
+    #[test]
+    fn receive_joins_split_payload() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let mut client = Connection::new(client);
+        let mut server = server;
+
+        let data = frame(&Request::WindowSetTitle {
+            title: "split".to_string(),
+        });
+        let (head, tail) = data.split_at(HEADER_SIZE + 3);
+
+        server.write_all(head).unwrap();
+        assert!(client.receive::<Request>().unwrap().is_empty());
+
+        server.write_all(tail).unwrap();
+        let mut second = frame(&Request::WindowDestroy);
+        second.truncate(4);
+        server.write_all(&second).unwrap();
+        assert_eq!(
+            client.receive::<Request>().unwrap(),
+            vec![Request::WindowSetTitle {
+                title: "split".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn truncated_payload_is_invalid_data() {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let mut client = Connection::new(client);
+
+        // WindowMove需要8字节负载，这里只给出4字节
+        let mut data = REQUEST_WINDOW_MOVE.to_le_bytes().to_vec();
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(&1i32.to_le_bytes());
+        server.write_all(&data).unwrap();
+
+        let err = client.receive::<Request>().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn oversized_payload_is_invalid_data() {
+        let (client, mut server) = UnixStream::pair().unwrap();
+        let mut client = Connection::new(client);
+
+        let mut data = REQUEST_WINDOW_SET_TITLE.to_le_bytes().to_vec();
+        data.extend_from_slice(&(MAX_PAYLOAD_SIZE as u32 + 1).to_le_bytes());
+        server.write_all(&data).unwrap();
+
+        let err = client.receive::<Request>().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn receive_reports_closed_peer() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let mut client = Connection::new(client);
+        drop(server);
+
+        let err = client.receive::<Request>().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn nonblocking_send_never_blocks_on_idle_peer() {
+        let (client, _peer) = UnixStream::pair().unwrap();
+        let mut client = Connection::new(client);
+        client.set_nonblocking(true).unwrap();
+
+        let event = Reply::Event(Event {
+            code: 2,
+            a: 1,
+            b: 1,
+        });
+        // 对端从不读取，积压超过上限后send返回错误而不是阻塞
+        let result = (0..MAX_PENDING_SIZE).try_for_each(|_| client.send(&event));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::WouldBlock);
+        assert!(client.has_pending());
+    }
+
+    #[test]
+    fn flush_drains_backlog_once_peer_reads() {
+        let (client, peer) = UnixStream::pair().unwrap();
+        let mut client = Connection::new(client);
+        let mut peer = Connection::new(peer);
+        client.set_nonblocking(true).unwrap();
+
+        let event = Reply::Event(Event {
+            code: 2,
+            a: 1,
+            b: 1,
+        });
+        let mut sent = 0;
+        while !client.has_pending() {
+            client.send(&event).unwrap();
+            sent += 1;
+        }
+        client.send(&event).unwrap();
+        sent += 1;
+
+        let mut received = 0;
+        while received < sent {
+            received += peer.receive::<Reply>().unwrap().len();
+            client.flush().unwrap();
+        }
+        assert_eq!(received, sent);
+        assert!(!client.has_pending());
+    }
+
+    #[test]
+    fn fd_travels_with_its_packet() {
+        use crate::base::{color::Color, shm::SharedMemory};
+
+        let (client, server) = UnixStream::pair().unwrap();
+        let mut client = Connection::new(client);
+        let mut server = Connection::new(server);
+
+        let mut shm = SharedMemory::create(4).unwrap();
+        shm.data_mut()[3] = Color::rgb(1, 2, 3);
+        client.send(&Request::WindowDestroy).unwrap();
+        client
+            .send_with_fd(
+                &Request::WindowResize {
+                    width: 2,
+                    height: 2,
+                },
+                shm.fd(),
+            )
+            .unwrap();
+
+        let requests = server.receive::<Request>().unwrap();
+        assert_eq!(requests.len(), 2);
+        let fd = server.take_fd().expect("fd not received");
+        assert!(server.take_fd().is_none());
+
+        let mapped = SharedMemory::from_fd(fd, 4).unwrap();
+        assert_eq!(mapped.data()[3].data, shm.data()[3].data);
+    }
+}
@@ -0,0 +1,108 @@
+use std::{io, sync::OnceLock};
+
+use super::protocol::{Connection, Reply, Request};
+
+/// 缓存的屏幕信息，屏幕属性在服务器运行期间不会改变
+static SCREEN_INFO: OnceLock<ScreenInfo> = OnceLock::new();
+
+/// 颜色通道在像素中的位置
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Channel {
+    /// 最低位的偏移(位)
+    pub offset: u32,
+    /// 通道长度(位)，为0表示不存在该通道
+    pub length: u32,
+}
+
+impl Channel {
+    pub const fn new(offset: u32, length: u32) -> Self {
+        Channel { offset, length }
+    }
+}
+
+/// 屏幕像素格式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PixelFormat {
+    /// 每个像素的位数
+    pub bits_per_pixel: u32,
+    /// 红色通道
+    pub red: Channel,
+    /// 绿色通道
+    pub green: Channel,
+    /// 蓝色通道
+    pub blue: Channel,
+    /// 透明度通道
+    pub alpha: Channel,
+}
+
+impl PixelFormat {
+    /// 32位ARGB格式(小端序，内存中依次为B、G、R、A)
+    pub const ARGB8888: PixelFormat = PixelFormat {
+        bits_per_pixel: 32,
+        red: Channel::new(16, 8),
+        green: Channel::new(8, 8),
+        blue: Channel::new(0, 8),
+        alpha: Channel::new(24, 8),
+    };
+
+    /// 每个像素的字节数
+    pub fn bytes_per_pixel(&self) -> u32 {
+        self.bits_per_pixel.div_ceil(8)
+    }
+}
+
+impl Default for PixelFormat {
+    fn default() -> Self {
+        PixelFormat::ARGB8888
+    }
+}
+
+/// 屏幕信息
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScreenInfo {
+    /// 屏幕宽度(像素)
+    pub width: u32,
+    /// 屏幕高度(像素)
+    pub height: u32,
+    /// 每行占用的字节数
+    pub stride: u32,
+    /// 像素格式
+    pub format: PixelFormat,
+}
+
+impl ScreenInfo {
+    /// 根据宽高创建ARGB8888格式、无行间填充的屏幕信息
+    pub fn new(width: u32, height: u32) -> Self {
+        ScreenInfo {
+            width,
+            height,
+            stride: width * PixelFormat::ARGB8888.bytes_per_pixel(),
+            format: PixelFormat::ARGB8888,
+        }
+    }
+
+    /// 向服务器查询屏幕信息
+    pub fn query() -> io::Result<Self> {
+        let mut connection = Connection::connect()?;
+        connection.send(&Request::ScreenInfo)?;
+        loop {
+            if let Reply::ScreenInfo(info) = connection.receive_blocking::<Reply>()? {
+                return Ok(info);
+            }
+        }
+    }
+}
+
+/// # 函数功能
+/// 获取屏幕信息，首次调用时向服务器查询，之后使用缓存的结果
+///
+/// ## 返回值
+/// 屏幕信息
+pub fn screen_info() -> io::Result<ScreenInfo> {
+    if let Some(info) = SCREEN_INFO.get() {
+        return Ok(*info);
+    }
+
+    let info = ScreenInfo::query()?;
+    Ok(*SCREEN_INFO.get_or_init(|| info))
+}
@@ -0,0 +1,215 @@
+use std::{
+    io,
+    os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+    ptr, slice,
+};
+
+use super::color::Color;
+
+/// 窗口宽度或高度的上限(像素)
+pub const MAX_DIMENSION: u32 = 16384;
+
+/// 服务器要求共享内存必须带有的密封标志
+/// 禁止缩小文件，避免对端截断后访问映射区域触发SIGBUS
+const REQUIRED_SEALS: libc::c_int = libc::F_SEAL_SHRINK;
+
+/// # 函数功能
+/// 计算指定大小的窗口所需的像素数量
+///
+/// ## 参数
+/// - width: 宽度
+/// - height: 高度
+///
+/// ## 返回值
+/// 像素数量，宽高超过上限时返回InvalidInput错误
+pub fn buffer_len(width: u32, height: u32) -> io::Result<usize> {
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "buffer size {}x{} exceeds {}x{}",
+                width, height, MAX_DIMENSION, MAX_DIMENSION
+            ),
+        ));
+    }
+    (width as usize)
+        .checked_mul(height as usize)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "buffer size overflow"))
+}
+
+/// 客户端与服务器共享的像素缓冲区
+/// 由客户端通过memfd创建并密封，文件描述符随请求发送给服务器
+/// 双方映射同一个匿名文件，看到的是相同的物理页
+pub struct SharedMemory {
+    /// 匿名文件
+    fd: OwnedFd,
+    /// 映射的起始地址
+    ptr: *mut Color,
+    /// 像素数量
+    len: usize,
+}
+
+impl SharedMemory {
+    /// # 函数功能
+    /// 创建新的共享内存(客户端使用)
+    ///
+    /// ## 参数
+    /// - len: 像素数量
+    ///
+    /// ## 返回值
+    /// 共享内存对象
+    pub fn create(len: usize) -> io::Result<Self> {
+        let size = Self::byte_size(len)?;
+
+        let raw_fd = unsafe {
+            libc::memfd_create(
+                c"starry-shm".as_ptr(),
+                libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING,
+            )
+        };
+        if raw_fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(raw_fd) };
+
+        if unsafe { libc::ftruncate(fd.as_raw_fd(), size as libc::off_t) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let seals = REQUIRED_SEALS | libc::F_SEAL_GROW | libc::F_SEAL_SEAL;
+        if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_ADD_SEALS, seals) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let ptr = Self::map(&fd, len)?;
+        Ok(SharedMemory { fd, ptr, len })
+    }
+
+    /// # 函数功能
+    /// 映射客户端发来的共享内存(服务器使用)
+    /// 文件必须已被密封为不可缩小，且大小足够容纳所有像素
+    ///
+    /// ## 参数
+    /// - fd: 客户端发来的文件描述符
+    /// - len: 像素数量
+    ///
+    /// ## 返回值
+    /// 共享内存对象
+    pub fn from_fd(fd: OwnedFd, len: usize) -> io::Result<Self> {
+        let size = Self::byte_size(len)?;
+
+        let seals = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GET_SEALS) };
+        if seals < 0 || seals & REQUIRED_SEALS != REQUIRED_SEALS {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "shared memory is not sealed against shrinking",
+            ));
+        }
+
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        if unsafe { libc::fstat(fd.as_raw_fd(), &mut stat) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if (stat.st_mode & libc::S_IFMT) != libc::S_IFREG || (stat.st_size as u64) < size as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("shared memory is smaller than {} pixels", len),
+            ));
+        }
+
+        let ptr = Self::map(&fd, len)?;
+        Ok(SharedMemory { fd, ptr, len })
+    }
+
+    /// 计算像素数量对应的字节数
+    fn byte_size(len: usize) -> io::Result<usize> {
+        len.checked_mul(4)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "buffer size overflow"))
+    }
+
+    /// 将文件映射到内存
+    fn map(fd: &OwnedFd, len: usize) -> io::Result<*mut Color> {
+        if len == 0 {
+            return Ok(ptr::NonNull::dangling().as_ptr());
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len * 4,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ptr as *mut Color)
+    }
+
+    /// 共享内存的文件描述符，用于发送给服务器
+    pub fn fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+
+    /// 像素数据
+    pub fn data(&self) -> &[Color] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    /// 可变像素数据
+    pub fn data_mut(&mut self) -> &mut [Color] {
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe {
+                libc::munmap(self.ptr as *mut libc::c_void, self.len * 4);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Write};
+
+    use super::*;
+
+    #[test]
+    fn buffer_len_rejects_absurd_sizes() {
+        assert_eq!(buffer_len(640, 480).unwrap(), 640 * 480);
+        assert_eq!(buffer_len(0, 480).unwrap(), 0);
+        assert!(buffer_len(MAX_DIMENSION + 1, 1).is_err());
+        assert!(buffer_len(u32::MAX, u32::MAX).is_err());
+    }
+
+    #[test]
+    fn from_fd_requires_seals() {
+        let path = std::env::temp_dir().join(format!("starry-shm-test-{}", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        file.write_all(&[0; 16]).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let err = SharedMemory::from_fd(OwnedFd::from(file), 4).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn from_fd_rejects_short_buffer() {
+        let shm = SharedMemory::create(4).unwrap();
+        let fd = shm.fd().try_clone_to_owned().unwrap();
+
+        assert!(SharedMemory::from_fd(fd, 5).is_err());
+    }
+
+    #[test]
+    fn sealed_buffer_cannot_shrink() {
+        let shm = SharedMemory::create(4).unwrap();
+        assert!(unsafe { libc::ftruncate(shm.fd().as_raw_fd(), 0) } < 0);
+    }
+}
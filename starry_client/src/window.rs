@@ -1,25 +1,14 @@
-use std::{
-    cell::Cell,
-    cmp::min,
-    fs::File,
-    io::{Seek, SeekFrom, Write},
-};
+use std::{cell::Cell, io};
 
 use crate::base::{
     color::Color,
+    event::{Event, EventOption},
+    protocol::{Connection, Reply, Request, WindowCursor, WindowFlags, WindowLayer},
     renderer::{RenderMode, Renderer},
+    shm::{buffer_len, SharedMemory},
 };
 
-// TODO: 读帧缓冲设备属性
-/// 屏幕宽度
-const SCREEN_WIDTH: usize = 1440;
-/// 屏幕高度
-#[allow(dead_code)]
-const SCREEN_HEIGHT: usize = 900;
-
-const FB_FILE_PATH: &str = "/dev/fb0";
-
-/// 客户端的窗口类，与服务端的窗口对象一一对应  
+/// 客户端的窗口类，与服务端的窗口对象一一对应
 /// 一般来说客户端应用程序不直接使用该类，而通过Toolkit库间接使用
 #[allow(dead_code)]
 pub struct Window {
@@ -37,14 +26,12 @@ pub struct Window {
     resizable: bool,
     /// 窗口的渲染模式
     mode: Cell<RenderMode>,
-    // 命名管道文件
-    file_opt: Option<File>,
-    // TODO: 改定长数组
-    // data_opt: Option<& 'static mut [Color]>,
-    /// 窗口的渲染数据
-    data_opt: Option<Box<[Color]>>,
-    /// 帧缓冲文件
-    fb_file: File,
+    /// 服务器分配的窗口id
+    id: u32,
+    /// 与服务器的连接
+    connection: Connection,
+    /// 窗口的渲染数据，与服务器共享
+    data_opt: Option<SharedMemory>,
 }
 
 impl Renderer for Window {
@@ -57,32 +44,15 @@ impl Renderer for Window {
     }
 
     fn data(&self) -> &[Color] {
-        self.data_opt.as_ref().unwrap()
+        self.data_opt.as_ref().unwrap().data()
     }
 
     fn data_mut(&mut self) -> &mut [Color] {
-        self.data_opt.as_mut().unwrap()
+        self.data_opt.as_mut().unwrap().data_mut()
     }
 
     fn sync(&mut self) -> bool {
-        // 处理窗口大小超限的情况
-        let width = min(self.width() as i32, SCREEN_WIDTH as i32 - self.x);
-        let height = min(self.height() as i32, SCREEN_HEIGHT as i32 - self.y);
-
-        for y in 0..height as i32 {
-            for x in 0..width as i32 {
-                let pixel = self.get_pixel(x, y);
-                let offset = (((y + self.y()) * SCREEN_WIDTH as i32) + x + self.x()) * 4;
-                // 写缓冲区
-                self.fb_file
-                    .seek(SeekFrom::Start(offset as u64))
-                    .expect("Unable to seek framebuffer");
-                self.fb_file
-                    .write_all(&pixel.to_bgra_bytes())
-                    .expect("Unable to write framebuffer");
-            }
-        }
-        true
+        self.damage(0, 0, self.w, self.h)
     }
 
     fn mode(&self) -> &Cell<RenderMode> {
@@ -92,23 +62,91 @@ impl Renderer for Window {
 
 #[allow(dead_code)]
 impl Window {
-    /// TODO: 接收flags
+    /// 创建窗口，x和y均为负数时由服务器决定窗口位置
     pub fn new(x: i32, y: i32, w: u32, h: u32, title: &str, color: Color) -> Self {
-        Window {
-            x: x,
-            y: y,
-            w: w,
-            h: h,
+        Window::new_flags(x, y, w, h, title, color, WindowFlags::empty())
+    }
+
+    /// 创建具有指定属性的窗口
+    pub fn new_flags(
+        x: i32,
+        y: i32,
+        w: u32,
+        h: u32,
+        title: &str,
+        color: Color,
+        flags: WindowFlags,
+    ) -> Self {
+        Window::try_new(x, y, w, h, title, color, flags)
+            .expect("[Error] Window failed to connect to server")
+    }
+
+    /// # 函数功能
+    /// 连接服务器并创建窗口
+    ///
+    /// ## 参数
+    /// - x: 窗口左上角x坐标
+    /// - y: 窗口左上角y坐标
+    /// - w: 窗口宽度
+    /// - h: 窗口高度
+    /// - title: 窗口标题
+    /// - color: 窗口初始颜色
+    /// - flags: 窗口属性
+    ///
+    /// ## 返回值
+    /// 创建成功的窗口对象
+    pub fn try_new(
+        x: i32,
+        y: i32,
+        w: u32,
+        h: u32,
+        title: &str,
+        color: Color,
+        flags: WindowFlags,
+    ) -> io::Result<Self> {
+        let mut data = SharedMemory::create(buffer_len(w, h)?)?;
+        data.data_mut().fill(color);
+
+        let mut connection = Connection::connect()?;
+        connection.send_with_fd(
+            &Request::WindowNew {
+                x,
+                y,
+                width: w,
+                height: h,
+                flags,
+                title: title.to_string(),
+            },
+            data.fd(),
+        )?;
+
+        let (id, x, y) = match connection.receive_blocking::<Reply>()? {
+            Reply::WindowCreated { id, x, y } => (id, x, y),
+            reply => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected reply {:?}", reply),
+                ))
+            }
+        };
+
+        Ok(Window {
+            x,
+            y,
+            w,
+            h,
             title: title.to_string(),
-            // window_async: false,
-            resizable: false,
+            resizable: flags.contains(WindowFlags::RESIZABLE),
             mode: Cell::new(RenderMode::Blend),
-            file_opt: None,
-            data_opt: Some(vec![color; (w * h) as usize].into_boxed_slice()),
-            fb_file: File::open(FB_FILE_PATH).expect("[Error] Window failed to open fb file"),
-        }
+            id,
+            connection,
+            data_opt: Some(data),
+        })
+    }
 
-        // TODO: 与服务器通信
+    /// 返回服务器分配的窗口id
+    pub fn id(&self) -> u32 {
+        self.id
     }
 
     /// 返回窗口x坐标
@@ -130,16 +168,165 @@ impl Window {
     pub fn set_pos(&mut self, x: i32, y: i32) {
         self.x = x;
         self.y = y;
+        self.send(&Request::WindowMove { x, y });
     }
 
-    /// 改变窗口的大小
+    /// 改变窗口的大小，重新分配共享内存并通知服务器
     pub fn set_size(&mut self, width: u32, height: u32) {
-        self.w = width;
-        self.h = height;
+        if let Err(err) = self.resize_data(width, height) {
+            println!("[Error] Window failed to resize buffer: {}", err);
+            return;
+        }
+
+        let request = Request::WindowResize { width, height };
+        let fd = self.data_opt.as_ref().unwrap().fd();
+        if let Err(err) = self.connection.send_with_fd(&request, fd) {
+            println!("[Error] Window failed to send request: {}", err);
+        }
     }
 
     /// 改变窗口标题
     pub fn set_title(&mut self, title: &str) {
         self.title = title.to_string();
+        self.send(&Request::WindowSetTitle {
+            title: title.to_string(),
+        });
+    }
+
+    /// 设置鼠标位于窗口内时的样式
+    pub fn set_cursor(&mut self, cursor: WindowCursor) {
+        self.send(&Request::WindowSetCursor { cursor });
+    }
+
+    /// 将窗口提升到所在层的最上方
+    pub fn raise(&mut self) {
+        self.send(&Request::WindowRaise);
+    }
+
+    /// 将窗口降低到所在层的最下方
+    pub fn lower(&mut self) {
+        self.send(&Request::WindowLower);
+    }
+
+    /// 将窗口移到指定的层
+    pub fn set_layer(&mut self, layer: WindowLayer) {
+        self.send(&Request::WindowSetLayer { layer });
+    }
+
+    /// # 函数功能
+    /// 设置用户拖动缩放窗口时允许的大小范围
+    ///
+    /// ## 参数
+    /// - min_width: 最小宽度
+    /// - min_height: 最小高度
+    /// - max_width: 最大宽度
+    /// - max_height: 最大高度
+    pub fn set_size_limits(
+        &mut self,
+        min_width: u32,
+        min_height: u32,
+        max_width: u32,
+        max_height: u32,
+    ) {
+        self.send(&Request::WindowSetSizeLimits {
+            min_width,
+            min_height,
+            max_width,
+            max_height,
+        });
+    }
+
+    /// # 函数功能
+    /// 通知服务器窗口的一块区域已更新
+    ///
+    /// ## 参数
+    /// - x: 区域左上角x坐标(局部坐标)
+    /// - y: 区域左上角y坐标(局部坐标)
+    /// - width: 区域宽度
+    /// - height: 区域高度
+    ///
+    /// ## 返回值
+    /// 是否通知成功
+    pub fn damage(&mut self, x: i32, y: i32, width: u32, height: u32) -> bool {
+        let request = Request::WindowDamage {
+            x,
+            y,
+            width,
+            height,
+        };
+
+        match self.connection.send(&request) {
+            Ok(()) => true,
+            Err(err) => {
+                println!("[Error] Window failed to sync with server: {}", err);
+                false
+            }
+        }
+    }
+
+    /// # 函数功能
+    /// 接收服务器发来的所有事件(非阻塞)
+    /// 窗口位置和大小改变的事件会先同步到窗口自身
+    ///
+    /// ## 返回值
+    /// 事件数组
+    pub fn events(&mut self) -> Vec<Event> {
+        let replies = match self.connection.receive::<Reply>() {
+            Ok(replies) => replies,
+            Err(err) => {
+                println!("[Error] Window failed to receive events: {}", err);
+                return Vec::new();
+            }
+        };
+
+        let mut events = Vec::new();
+        for reply in replies {
+            if let Reply::Event(event) = reply {
+                match event.to_option() {
+                    EventOption::WindowMove(move_event) => {
+                        self.x = move_event.x;
+                        self.y = move_event.y;
+                    }
+                    EventOption::WindowResize(resize_event) => {
+                        self.set_size(resize_event.width, resize_event.height);
+                    }
+                    _ => {}
+                }
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    /// 重新分配共享内存，保留原有内容
+    fn resize_data(&mut self, width: u32, height: u32) -> io::Result<()> {
+        let mut data = SharedMemory::create(buffer_len(width, height)?)?;
+        if let Some(old_data) = self.data_opt.as_ref() {
+            let old_data = old_data.data();
+            let new_data = data.data_mut();
+            let copy_w = width.min(self.w) as usize;
+            for y in 0..height.min(self.h) as usize {
+                let src = y * self.w as usize;
+                let dst = y * width as usize;
+                new_data[dst..dst + copy_w].copy_from_slice(&old_data[src..src + copy_w]);
+            }
+        }
+        self.w = width;
+        self.h = height;
+        self.data_opt = Some(data);
+        Ok(())
+    }
+
+    /// 向服务器发送请求
+    fn send(&mut self, request: &Request) {
+        if let Err(err) = self.connection.send(request) {
+            println!("[Error] Window failed to send request: {}", err);
+        }
+    }
+}
+
+impl Drop for Window {
+    fn drop(&mut self) {
+        let _ = self.connection.send(&Request::WindowDestroy);
     }
 }
@@ -0,0 +1,101 @@
+//! 比较逐像素写入与逐行拷贝两种帧缓冲输出方式的耗时
+//! 使用普通文件模拟帧缓冲设备，无需真实的显示设备
+//!
+//! 运行方式: `cargo bench --bench present`
+
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{Seek, SeekFrom, Write},
+    process,
+    time::{Duration, Instant},
+};
+
+use starry_client::base::{color::Color, renderer::Renderer, screen::ScreenInfo};
+use starry_server::{
+    base::{display::Display, rect::Rect},
+    core::backend::{fbdev::FbdevBackend, headless::HeadlessBackend, DisplayBackend},
+};
+
+const WIDTH: i32 = 1440;
+const HEIGHT: i32 = 900;
+
+/// 原先的输出方式：每个像素一次seek和一次write
+fn present_per_pixel(fb_file: &mut File, display: &Display, rect: &Rect) {
+    for y in 0..rect.height() {
+        for x in 0..rect.width() {
+            let pixel = display
+                .image
+                .get_pixel(x + rect.left() - display.x, y + rect.top() - display.y);
+            let offset = (((y + rect.top()) * WIDTH) + x + rect.left()) * 4;
+            fb_file
+                .seek(SeekFrom::Start(offset as u64))
+                .expect("Unable to seek framebuffer");
+            fb_file
+                .write_all(&pixel.to_bgra_bytes())
+                .expect("Unable to write framebuffer");
+        }
+    }
+}
+
+/// 重复执行并返回每次的平均耗时
+fn measure<F: FnMut()>(iterations: u32, mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    start.elapsed() / iterations
+}
+
+fn report(name: &str, duration: Duration, baseline: Duration) {
+    println!(
+        "{:<24} {:>10.3} ms/frame {:>10.1}x",
+        name,
+        duration.as_secs_f64() * 1000.0,
+        baseline.as_secs_f64() / duration.as_secs_f64()
+    );
+}
+
+fn main() {
+    let mut display = Display::new(0, 0, WIDTH, HEIGHT);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let color = Color::rgb((x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8);
+            display.image.pixel(x, y, color);
+        }
+    }
+    let rect = display.screen_rect();
+
+    let fb_path = env::temp_dir().join(format!("starry_bench_fb_{}", process::id()));
+    let mut fb_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&fb_path)
+        .expect("Unable to create framebuffer file");
+    fb_file
+        .set_len((WIDTH * HEIGHT * 4) as u64)
+        .expect("Unable to resize framebuffer file");
+
+    let per_pixel = measure(1, || present_per_pixel(&mut fb_file, &display, &rect));
+
+    let mut fbdev = FbdevBackend::new(
+        fb_path.to_str().unwrap(),
+        ScreenInfo::new(WIDTH as u32, HEIGHT as u32),
+        false,
+    )
+    .expect("Unable to open framebuffer file");
+    let mmap_rows = measure(50, || fbdev.present(&mut display, &rect));
+
+    let mut headless = HeadlessBackend::new(WIDTH, HEIGHT);
+    let headless_cover = measure(50, || headless.present(&mut display, &rect));
+
+    println!("full screen {}x{} present:", WIDTH, HEIGHT);
+    report("per-pixel seek/write", per_pixel, per_pixel);
+    report("fbdev mmap rows", mmap_rows, per_pixel);
+    report("headless cover", headless_cover, per_pixel);
+
+    drop(fbdev);
+    let _ = fs::remove_file(&fb_path);
+}
@@ -0,0 +1,153 @@
+use super::rect::Rect;
+
+/// 矩形数量超过该值时合并为一个外接矩形，避免碎片过多
+const MAX_RECTS: usize = 32;
+
+/// 待重绘的区域，由互不重叠的矩形组成
+/// 添加矩形时，与已有矩形的外接矩形面积不超过两者面积之和的矩形被合并(浪费的面积不超过重叠的面积)，
+/// 其余部分减去已有矩形后加入，因此每个像素只被重绘一次
+#[derive(Clone, Debug, Default)]
+pub struct Damage {
+    /// 互不重叠的矩形数组
+    rects: Vec<Rect>,
+}
+
+impl Damage {
+    pub fn new() -> Self {
+        Damage { rects: Vec::new() }
+    }
+
+    /// 组成区域的矩形
+    pub fn rects(&self) -> &[Rect] {
+        &self.rects
+    }
+
+    /// 区域是否为空
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    /// 区域的外接矩形，区域为空时返回None
+    pub fn bounds(&self) -> Option<Rect> {
+        self.rects.iter().copied().reduce(|a, b| a.container(&b))
+    }
+
+    /// 取出所有矩形并清空区域
+    pub fn take(&mut self) -> Vec<Rect> {
+        std::mem::take(&mut self.rects)
+    }
+
+    /// # 函数功能
+    /// 向区域中添加矩形
+    ///
+    /// ## 参数
+    /// - rect: 需要重绘的矩形
+    pub fn add(&mut self, rect: Rect) {
+        if rect.is_empty() || self.rects.iter().any(|r| r.contains_rect(&rect)) {
+            return;
+        }
+
+        // 合并浪费面积较小的矩形，合并后的矩形可能还能与其他矩形合并
+        let mut rect = rect;
+        while let Some(index) = self.rects.iter().position(|r| {
+            let container = r.container(&rect);
+            container.area() <= r.area() + rect.area()
+        }) {
+            rect = rect.container(&self.rects.swap_remove(index));
+        }
+
+        // 去掉已被包含的矩形，剩余部分减去与已有矩形重叠的部分
+        self.rects.retain(|r| !rect.contains_rect(r));
+        let mut pieces = vec![rect];
+        for existing in self.rects.iter() {
+            pieces = pieces
+                .iter()
+                .flat_map(|piece| piece.difference(existing))
+                .collect();
+        }
+        self.rects.extend(pieces);
+
+        if self.rects.len() > MAX_RECTS {
+            let bounds = self.bounds().unwrap();
+            self.rects = vec![bounds];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 检查矩形互不重叠，且恰好覆盖给定的点
+    fn assert_covers(damage: &Damage, points: &[(i32, i32)]) {
+        let rects = damage.rects();
+        for (i, a) in rects.iter().enumerate() {
+            assert!(!a.is_empty());
+            for b in rects[i + 1..].iter() {
+                assert!(a.intersection(b).is_empty(), "{:?} overlaps {:?}", a, b);
+            }
+        }
+        for &(x, y) in points {
+            assert!(
+                rects.iter().any(|rect| rect.contains(x, y)),
+                "({}, {}) is not covered",
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn contained_and_empty_rects_are_ignored() {
+        let mut damage = Damage::new();
+        damage.add(Rect::new(0, 0, 100, 100));
+        damage.add(Rect::new(10, 10, 20, 20));
+        damage.add(Rect::new(500, 500, 0, 10));
+        assert_eq!(damage.rects(), &[Rect::new(0, 0, 100, 100)]);
+    }
+
+    #[test]
+    fn adjacent_rects_are_merged() {
+        let mut damage = Damage::new();
+        damage.add(Rect::new(0, 0, 10, 10));
+        damage.add(Rect::new(10, 0, 10, 10));
+        assert_eq!(damage.rects(), &[Rect::new(0, 0, 20, 10)]);
+
+        // 合并后的矩形继续与其他矩形合并
+        damage.add(Rect::new(0, 20, 20, 10));
+        damage.add(Rect::new(0, 10, 20, 10));
+        assert_eq!(damage.rects(), &[Rect::new(0, 0, 20, 30)]);
+    }
+
+    #[test]
+    fn distant_rects_stay_separate() {
+        let mut damage = Damage::new();
+        damage.add(Rect::new(0, 0, 10, 10));
+        damage.add(Rect::new(100, 100, 10, 10));
+        assert_eq!(damage.rects().len(), 2);
+        assert_eq!(damage.bounds(), Some(Rect::new(0, 0, 110, 110)));
+    }
+
+    #[test]
+    fn overlapping_rects_do_not_overlap_after_adding() {
+        let mut damage = Damage::new();
+        // 十字形：外接矩形浪费的面积过大，不合并
+        damage.add(Rect::new(0, 40, 100, 20));
+        damage.add(Rect::new(40, 0, 20, 100));
+        assert_eq!(damage.rects().len(), 3);
+        assert_covers(&damage, &[(0, 40), (99, 59), (40, 0), (59, 99), (50, 50)]);
+        let area: i32 = damage.rects().iter().map(|rect| rect.area()).sum();
+        assert_eq!(area, 100 * 20 + 20 * 100 - 20 * 20);
+    }
+
+    #[test]
+    fn too_many_rects_collapse() {
+        let mut damage = Damage::new();
+        for i in 0..=MAX_RECTS as i32 {
+            damage.add(Rect::new(i * 10, i * 10, 1, 1));
+        }
+        assert_eq!(damage.rects(), &[Rect::new(0, 0, 321, 321)]);
+        assert_eq!(damage.take().len(), 1);
+        assert!(damage.is_empty());
+    }
+}
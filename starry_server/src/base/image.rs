@@ -1,14 +1,19 @@
-use std::{cell::Cell, cmp, mem, ptr, slice};
+use std::{
+    cell::Cell,
+    cmp, mem,
+    ops::{Deref, DerefMut},
+    ptr, slice,
+};
 
 use image::GenericImageView;
+use log::error;
 use resize::Type;
 use starry_client::base::{
     color::Color,
     renderer::{RenderMode, Renderer},
+    shm::SharedMemory,
 };
 
-use crate::core::{SCREEN_HEIGHT, SCREEN_WIDTH};
-
 use super::rect::Rect;
 
 /// Roi区域中的行数据
@@ -227,6 +232,41 @@ impl<'a> Renderer for ImageRef<'a> {
     }
 }
 
+/// 图像的像素存储
+pub enum ImageData {
+    /// 自身持有的像素数据
+    Owned(Box<[Color]>),
+    /// 与客户端共享的像素数据
+    Shared(SharedMemory),
+}
+
+impl Deref for ImageData {
+    type Target = [Color];
+
+    fn deref(&self) -> &[Color] {
+        match self {
+            ImageData::Owned(data) => data,
+            ImageData::Shared(shm) => shm.data(),
+        }
+    }
+}
+
+impl DerefMut for ImageData {
+    fn deref_mut(&mut self) -> &mut [Color] {
+        match self {
+            ImageData::Owned(data) => data,
+            ImageData::Shared(shm) => shm.data_mut(),
+        }
+    }
+}
+
+// 共享内存无法被复制，克隆时转为自身持有的数据
+impl Clone for ImageData {
+    fn clone(&self) -> Self {
+        ImageData::Owned(self.deref().into())
+    }
+}
+
 /// 包含帧缓冲区的图像
 #[derive(Clone)]
 pub struct Image {
@@ -235,7 +275,7 @@ pub struct Image {
     /// 高度
     h: i32,
     /// 像素数据
-    data: Box<[Color]>,
+    data: ImageData,
     /// 渲染模式
     mode: Cell<RenderMode>,
 }
@@ -260,22 +300,42 @@ impl Image {
         Image {
             w: width,
             h: height,
-            data,
+            data: ImageData::Owned(data),
+            mode: Cell::new(RenderMode::Blend),
+        }
+    }
+
+    /// 根据共享内存创建图像，图像与客户端窗口共用像素数据
+    pub fn from_shared(width: i32, height: i32, shm: SharedMemory) -> Self {
+        Image {
+            w: width,
+            h: height,
+            data: ImageData::Shared(shm),
             mode: Cell::new(RenderMode::Blend),
         }
     }
 
     pub fn from_path(path: &[u8]) -> Option<Self> {
+        Self::from_path_fit(path, u32::MAX, u32::MAX)
+    }
+
+    /// # 函数功能
+    /// 从图片数据创建图像，超出指定大小时按比例缩小
+    ///
+    /// ## 参数
+    /// - path: 图片数据
+    /// - max_width: 最大宽度
+    /// - max_height: 最大高度
+    ///
+    /// ## 返回值
+    /// 解码成功时返回图像
+    pub fn from_path_fit(path: &[u8], max_width: u32, max_height: u32) -> Option<Self> {
         if let Ok(mut img) = image::load_from_memory(path) {
             // let img = img.resize(20, 20, image::imageops::FilterType::Gaussian);
 
             let (mut img_width, mut img_heigh) = img.dimensions();
-            if img_width > SCREEN_WIDTH as u32 || img_heigh > SCREEN_HEIGHT as u32 {
-                img = img.resize(
-                    SCREEN_WIDTH as u32,
-                    SCREEN_HEIGHT as u32,
-                    image::imageops::FilterType::Gaussian,
-                );
+            if img_width > max_width || img_heigh > max_height {
+                img = img.resize(max_width, max_height, image::imageops::FilterType::Gaussian);
                 (img_width, img_heigh) = img.dimensions();
             }
 
@@ -297,7 +357,7 @@ impl Image {
 
             return Some(image);
         } else {
-            println!("[Error] Image created from path failed");
+            error!("Image created from path failed");
             return None;
         }
     }
@@ -329,7 +389,7 @@ impl Image {
         ImageRoi {
             rect: *rect,
             w: self.w,
-            data: &mut self.data,
+            data: &mut self.data[..],
         }
     }
 
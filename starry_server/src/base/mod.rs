@@ -1,3 +1,4 @@
+pub mod damage;
 pub mod display;
 pub mod image;
 pub mod rect;
@@ -1,7 +1,7 @@
 use std::cmp::{max, min};
 
 /// 表示一个矩形区域
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Rect {
     /// 矩形左上角x坐标
     x: i32,
@@ -81,9 +81,9 @@ impl Rect {
         Rect::new(left, top, max(0, right - left), max(0, bottom - top))
     }
 
-    /// 判断点是否在矩形中
+    /// 判断点是否在矩形中(不含右边界和下边界)
     pub fn contains(&self, x: i32, y: i32) -> bool {
-        self.left() <= x && self.right() >= x && self.top() <= y && self.bottom() >= y
+        self.left() <= x && x < self.right() && self.top() <= y && y < self.bottom()
     }
 
     /// 判断矩形是否为空
@@ -91,6 +91,78 @@ impl Rect {
         self.w == 0 || self.h == 0
     }
 
+    /// 判断另一矩形是否完全位于矩形中，空矩形总是位于矩形中
+    pub fn contains_rect(&self, other: &Rect) -> bool {
+        other.is_empty()
+            || (self.left() <= other.left()
+                && other.right() <= self.right()
+                && self.top() <= other.top()
+                && other.bottom() <= self.bottom())
+    }
+
+    /// # 函数功能
+    /// 求矩形减去另一矩形后剩余的区域
+    ///
+    /// ## 参数
+    /// - other: 被减去的矩形
+    ///
+    /// ## 返回值
+    /// 互不重叠的矩形数组(最多4个)，依次为上、下、左、右方的剩余部分
+    pub fn difference(&self, other: &Rect) -> Vec<Rect> {
+        let intersect = self.intersection(other);
+        if intersect.is_empty() {
+            return if self.is_empty() {
+                Vec::new()
+            } else {
+                vec![*self]
+            };
+        }
+
+        let pieces = [
+            // 上方和下方的部分占满整个宽度
+            Rect::new(self.x, self.y, self.w, intersect.top() - self.top()),
+            Rect::new(
+                self.x,
+                intersect.bottom(),
+                self.w,
+                self.bottom() - intersect.bottom(),
+            ),
+            // 左方和右方的部分只占交集的高度
+            Rect::new(
+                self.x,
+                intersect.top(),
+                intersect.left() - self.left(),
+                intersect.height(),
+            ),
+            Rect::new(
+                intersect.right(),
+                intersect.top(),
+                self.right() - intersect.right(),
+                intersect.height(),
+            ),
+        ];
+        pieces.into_iter().filter(|rect| !rect.is_empty()).collect()
+    }
+
+    /// # 函数功能
+    /// 求矩形的边框
+    ///
+    /// ## 参数
+    /// - thickness: 边框宽度
+    ///
+    /// ## 返回值
+    /// 上、下、左、右四条边框对应的矩形(位于矩形内部)
+    pub fn outline(&self, thickness: i32) -> [Rect; 4] {
+        let horizontal = thickness.clamp(0, self.h);
+        let vertical = thickness.clamp(0, self.w);
+        [
+            Rect::new(self.x, self.y, self.w, horizontal),
+            Rect::new(self.x, self.bottom() - horizontal, self.w, horizontal),
+            Rect::new(self.x, self.y, vertical, self.h),
+            Rect::new(self.right() - vertical, self.y, vertical, self.h),
+        ]
+    }
+
     /// # 函数功能
     /// 偏移矩形的位置
     /// 可用于矩形绝对和相对位置的转换
@@ -1,11 +1,125 @@
-use std::fs::File;
+use std::mem;
 
-use starry_client::base::event::Event;
+use log::error;
+use starry_client::base::{
+    color::Color,
+    event::{Event, EVENT_MOUSE_UPDATE},
+    protocol::{Connection, Reply, WindowLayer},
+    renderer::Renderer,
+    shm::MAX_DIMENSION,
+};
 
-use crate::core::SCREEN_HEIGHT;
+use crate::core::window_manager::CursorKind;
 
 use super::{display::Display, image::Image, rect::Rect};
 
+/// 标题栏高度
+pub const TITLE_HEIGHT: i32 = 20;
+/// 标题文字与标题栏左边缘的距离
+const TITLE_PADDING: i32 = 6;
+/// 字符宽度(unifont半角字符)
+const CHAR_WIDTH: i32 = 8;
+/// 字符高度
+const CHAR_HEIGHT: i32 = 16;
+/// 可缩放窗口边缘的感应宽度
+pub const RESIZE_BORDER: i32 = 6;
+/// 默认的窗体最小宽度，保证标题栏按钮可见
+pub const MIN_WINDOW_WIDTH: u32 = 3 * TITLE_HEIGHT as u32;
+/// 默认的窗体最小高度
+pub const MIN_WINDOW_HEIGHT: u32 = 1;
+
+/// 拖动缩放窗口时按住的边缘
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResizeEdge {
+    /// 左边
+    Left,
+    /// 右边
+    Right,
+    /// 底边
+    Bottom,
+    /// 左下角
+    BottomLeft,
+    /// 右下角
+    BottomRight,
+}
+
+impl ResizeEdge {
+    /// 对应的鼠标样式
+    pub fn cursor(self) -> CursorKind {
+        match self {
+            ResizeEdge::Left => CursorKind::LeftSide,
+            ResizeEdge::Right => CursorKind::RightSide,
+            ResizeEdge::Bottom => CursorKind::BottomSide,
+            ResizeEdge::BottomLeft => CursorKind::BottomLeftCorner,
+            ResizeEdge::BottomRight => CursorKind::BottomRightCorner,
+        }
+    }
+
+    /// # 函数功能
+    /// 根据鼠标的位移计算缩放后的窗体矩形
+    /// 拖动左边时保持右边界不动
+    ///
+    /// ## 参数
+    /// - origin: 开始缩放时的窗体矩形
+    /// - dx: 鼠标水平位移
+    /// - dy: 鼠标垂直位移
+    /// - min: 最小宽高
+    /// - max: 最大宽高
+    ///
+    /// ## 返回值
+    /// 缩放后的窗体矩形
+    pub fn apply(self, origin: Rect, dx: i32, dy: i32, min: (u32, u32), max: (u32, u32)) -> Rect {
+        let (left, right, bottom) = match self {
+            ResizeEdge::Left => (true, false, false),
+            ResizeEdge::Right => (false, true, false),
+            ResizeEdge::Bottom => (false, false, true),
+            ResizeEdge::BottomLeft => (true, false, true),
+            ResizeEdge::BottomRight => (false, true, true),
+        };
+
+        let clamp = |value: i32, min: u32, max: u32| value.clamp(min as i32, max as i32);
+        let mut width = origin.width();
+        if left {
+            width = clamp(origin.width() - dx, min.0, max.0);
+        } else if right {
+            width = clamp(origin.width() + dx, min.0, max.0);
+        }
+        let height = if bottom {
+            clamp(origin.height() + dy, min.1, max.1)
+        } else {
+            origin.height()
+        };
+        let x = if left {
+            origin.right() - width
+        } else {
+            origin.left()
+        };
+
+        Rect::new(x, origin.top(), width, height)
+    }
+}
+
+/// 窗口装饰(标题栏)的样式
+#[derive(Clone)]
+pub struct WindowTheme {
+    /// 关闭按钮图片
+    pub close: Option<Image>,
+    /// 未聚焦时关闭按钮图片
+    pub close_unfocused: Option<Image>,
+    /// 最大化按钮图片
+    pub max: Option<Image>,
+    /// 未聚焦时最大化按钮图片
+    pub max_unfocused: Option<Image>,
+    /// 标题栏颜色
+    pub bar_color: Color,
+    /// 聚焦窗口的标题栏颜色
+    pub bar_highlight_color: Color,
+    /// 标题文字颜色
+    pub text_color: Color,
+    /// 聚焦窗口的标题文字颜色
+    pub text_highlight_color: Color,
+}
+
 /// 窗口按Z值排序的模式
 #[derive(Debug, Copy, Clone, Eq, PartialEq, PartialOrd, Ord)]
 pub enum WindowZOrderMode {
@@ -17,6 +131,16 @@ pub enum WindowZOrderMode {
     Front,
 }
 
+impl From<WindowLayer> for WindowZOrderMode {
+    fn from(layer: WindowLayer) -> Self {
+        match layer {
+            WindowLayer::Back => WindowZOrderMode::Back,
+            WindowLayer::Normal => WindowZOrderMode::Normal,
+            WindowLayer::Front => WindowZOrderMode::Front,
+        }
+    }
+}
+
 /// 服务端的窗口类，与客户端的窗口类一一对应    
 #[allow(dead_code)]
 pub struct Window {
@@ -40,14 +164,24 @@ pub struct Window {
     pub zorder: WindowZOrderMode,
     /// 窗体图像
     pub image: Image,
-    /// 事件数组
+    /// 鼠标位于窗体内时的样式
+    pub cursor: CursorKind,
+    /// 最大化之前的窗体矩形，未最大化时为None
+    pub restore_rect: Option<Rect>,
+    /// 拖动缩放时允许的最小宽高
+    pub min_size: (u32, u32),
+    /// 拖动缩放时允许的最大宽高
+    pub max_size: (u32, u32),
+    /// 已渲染的标题栏及其对应的聚焦状态
+    title_bar: Option<(bool, Image)>,
+    /// 等待发送给客户端的事件队列
     pub events: Vec<Event>,
-    // 命名管道文件
-    pub file_opt: Option<File>,
+    /// 与客户端的连接(服务器自身创建的窗口没有连接)
+    pub connection_opt: Option<Connection>,
 }
 
 impl Window {
-    pub fn new(x: i32, y: i32, _w: i32, _h: i32, scale: i32, image_path: &[u8]) -> Window {
+    pub fn new(x: i32, y: i32, scale: i32, image: Image) -> Window {
         Window {
             x: x,
             y: y,
@@ -58,10 +192,14 @@ impl Window {
             resizable: true,
             unclosable: false,
             zorder: WindowZOrderMode::Normal,
-            image: Image::from_path(image_path)
-                .unwrap_or(Image::new(SCREEN_HEIGHT as i32, SCREEN_HEIGHT as i32)),
+            image,
+            cursor: CursorKind::Normal,
+            restore_rect: None,
+            min_size: (MIN_WINDOW_WIDTH, MIN_WINDOW_HEIGHT),
+            max_size: (MAX_DIMENSION, MAX_DIMENSION),
+            title_bar: None,
             events: Vec::new(),
-            file_opt: None,
+            connection_opt: None,
         }
     }
 
@@ -80,8 +218,295 @@ impl Window {
         Rect::new(self.x, self.y, self.width(), self.height())
     }
 
-    // TODO
-    // pub fn title_rect(&self) -> Rect {}
+    /// 返回标题栏对应矩形，无边界窗口返回空矩形
+    pub fn title_rect(&self) -> Rect {
+        if self.barderless {
+            Rect::new(self.x, self.y, 0, 0)
+        } else {
+            Rect::new(self.x, self.y - TITLE_HEIGHT, self.width(), TITLE_HEIGHT)
+        }
+    }
+
+    /// 返回包括标题栏在内的整个窗口的矩形
+    pub fn frame_rect(&self) -> Rect {
+        if self.barderless {
+            self.rect()
+        } else {
+            self.rect().container(&self.title_rect())
+        }
+    }
+
+    /// 能否拖动边缘缩放
+    pub fn can_resize(&self) -> bool {
+        self.resizable && !self.barderless
+    }
+
+    /// 返回鼠标能够命中窗口的区域，包括标题栏和可缩放窗口的边缘
+    pub fn hit_rect(&self) -> Rect {
+        let frame_rect = self.frame_rect();
+        if !self.can_resize() {
+            return frame_rect;
+        }
+        Rect::new(
+            frame_rect.left() - RESIZE_BORDER,
+            frame_rect.top(),
+            frame_rect.width() + 2 * RESIZE_BORDER,
+            frame_rect.height() + RESIZE_BORDER,
+        )
+    }
+
+    /// # 函数功能
+    /// 判断一点位于窗口的哪条可缩放边缘上
+    /// 感应区域位于窗体左、右、下边界之外`RESIZE_BORDER`宽的范围内
+    ///
+    /// ## 参数
+    /// - x: x坐标
+    /// - y: y坐标
+    ///
+    /// ## 返回值
+    /// 边缘，不在感应区域内或窗口不可缩放时返回None
+    pub fn resize_edge(&self, x: i32, y: i32) -> Option<ResizeEdge> {
+        if !self.can_resize() {
+            return None;
+        }
+
+        let rect = self.rect();
+        let left = x >= rect.left() - RESIZE_BORDER && x < rect.left();
+        let right = x >= rect.right() && x < rect.right() + RESIZE_BORDER;
+        let bottom = y >= rect.bottom() && y < rect.bottom() + RESIZE_BORDER;
+        let in_x = x >= rect.left() - RESIZE_BORDER && x < rect.right() + RESIZE_BORDER;
+        let in_y = y >= rect.top() && y < rect.bottom() + RESIZE_BORDER;
+        if !in_x || !in_y {
+            return None;
+        }
+
+        match (left, right, bottom) {
+            (true, _, true) => Some(ResizeEdge::BottomLeft),
+            (_, true, true) => Some(ResizeEdge::BottomRight),
+            (_, _, true) => Some(ResizeEdge::Bottom),
+            (true, _, _) => Some(ResizeEdge::Left),
+            (_, true, _) => Some(ResizeEdge::Right),
+            _ => None,
+        }
+    }
+
+    /// # 函数功能
+    /// 设置拖动缩放的大小限制，限制会被约束到合法范围内
+    ///
+    /// ## 参数
+    /// - min: 最小宽高
+    /// - max: 最大宽高
+    pub fn set_size_limits(&mut self, min: (u32, u32), max: (u32, u32)) {
+        let min = (min.0.clamp(1, MAX_DIMENSION), min.1.clamp(1, MAX_DIMENSION));
+        self.min_size = min;
+        self.max_size = (
+            max.0.clamp(min.0, MAX_DIMENSION),
+            max.1.clamp(min.1, MAX_DIMENSION),
+        );
+    }
+
+    /// 返回关闭按钮对应矩形，不可关闭或无边界的窗口返回空矩形
+    pub fn close_rect(&self) -> Rect {
+        if self.barderless || self.unclosable {
+            return Rect::new(self.x, self.y, 0, 0);
+        }
+        let title_rect = self.title_rect();
+        Rect::new(
+            title_rect.right() - TITLE_HEIGHT,
+            title_rect.top(),
+            TITLE_HEIGHT,
+            TITLE_HEIGHT,
+        )
+    }
+
+    /// 返回最大化按钮对应矩形，大小不可变或无边界的窗口返回空矩形
+    pub fn max_rect(&self) -> Rect {
+        if self.barderless || !self.resizable {
+            return Rect::new(self.x, self.y, 0, 0);
+        }
+        let title_rect = self.title_rect();
+        let right = if self.unclosable {
+            title_rect.right()
+        } else {
+            title_rect.right() - TITLE_HEIGHT
+        };
+        Rect::new(
+            right - TITLE_HEIGHT,
+            title_rect.top(),
+            TITLE_HEIGHT,
+            TITLE_HEIGHT,
+        )
+    }
+
+    /// 设置窗口标题
+    pub fn set_title(&mut self, title: String) {
+        self.title = title;
+        self.invalidate_title();
+    }
+
+    /// 丢弃已渲染的标题栏，下次绘制时重新渲染
+    pub fn invalidate_title(&mut self) {
+        self.title_bar = None;
+    }
+
+    /// # 函数功能
+    /// 客户端更新了共享内存中的一块区域
+    ///
+    /// ## 参数
+    /// - rect: 更新的矩形区域(窗口内相对位置)
+    ///
+    /// ## 返回值
+    /// 需要重绘的矩形区域(绝对位置)
+    pub fn damage(&self, rect: &Rect) -> Rect {
+        rect.intersection(&Rect::new(0, 0, self.width(), self.height()))
+            .offset(self.x, self.y)
+    }
+
+    /// # 函数功能
+    /// 将事件加入发送队列，由`flush_events`发送给客户端
+    /// 连续的鼠标位置更新事件只保留最后一个
+    ///
+    /// ## 参数
+    /// - event: 事件
+    pub fn send_event(&mut self, event: Event) {
+        if event.code == EVENT_MOUSE_UPDATE {
+            if let Some(last) = self.events.last_mut() {
+                if last.code == EVENT_MOUSE_UPDATE {
+                    *last = event;
+                    return;
+                }
+            }
+        }
+        self.events.push(event);
+    }
+
+    /// 将队列中的事件发送给客户端
+    pub fn flush_events(&mut self) {
+        let events = mem::take(&mut self.events);
+        if let Some(connection) = self.connection_opt.as_mut() {
+            for event in events {
+                if let Err(err) = connection.send(&Reply::Event(event)) {
+                    error!("Window failed to send event to client: {}", err);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// # 函数功能
+    /// 渲染标题栏到显示窗口中
+    /// 渲染结果会被缓存，直到标题、宽度或聚焦状态改变
+    ///
+    /// ## 参数
+    /// - display: 展示窗口
+    /// - rect: 渲染的矩形区域(绝对位置)
+    /// - focused: 窗口是否处于聚焦状态
+    /// - theme: 窗口装饰样式
+    pub fn draw_title(
+        &mut self,
+        display: &mut Display,
+        rect: &Rect,
+        focused: bool,
+        theme: &mut WindowTheme,
+    ) {
+        let title_rect = self.title_rect();
+        let intersect = title_rect.intersection(rect);
+        if intersect.is_empty() {
+            return;
+        }
+
+        let cached = matches!(&self.title_bar, Some((bar_focused, bar))
+            if *bar_focused == focused && bar.width() == title_rect.width());
+        if !cached {
+            self.title_bar = Some((focused, self.render_title(focused, theme)));
+        }
+
+        if let Some((_, bar)) = self.title_bar.as_mut() {
+            display
+                .roi(&intersect)
+                .blend(&bar.roi(&intersect.offset(-title_rect.left(), -title_rect.top())));
+        }
+    }
+
+    /// # 函数功能
+    /// 渲染整个标题栏
+    ///
+    /// ## 参数
+    /// - focused: 窗口是否处于聚焦状态
+    /// - theme: 窗口装饰样式
+    ///
+    /// ## 返回值
+    /// 标题栏图像
+    fn render_title(&self, focused: bool, theme: &mut WindowTheme) -> Image {
+        let title_rect = self.title_rect();
+        let (bar_color, text_color, close, max) = if focused {
+            (
+                theme.bar_highlight_color,
+                theme.text_highlight_color,
+                &mut theme.close,
+                &mut theme.max,
+            )
+        } else {
+            (
+                theme.bar_color,
+                theme.text_color,
+                &mut theme.close_unfocused,
+                &mut theme.max_unfocused,
+            )
+        };
+
+        let mut bar = Image::from_color(title_rect.width(), title_rect.height(), bar_color);
+
+        // 按钮(局部坐标)
+        let mut text_right = title_rect.width();
+        for (button_rect, image_opt, fallback) in
+            [(self.close_rect(), close, 'x'), (self.max_rect(), max, '+')]
+        {
+            if button_rect.is_empty() {
+                continue;
+            }
+            let button_rect = button_rect.offset(-title_rect.left(), -title_rect.top());
+            text_right = text_right.min(button_rect.left());
+            match image_opt {
+                Some(image) => {
+                    let x = button_rect.left() + (button_rect.width() - image.width()) / 2;
+                    let y = button_rect.top() + (button_rect.height() - image.height()) / 2;
+                    let dst = Rect::new(x, y, image.width(), image.height())
+                        .intersection(&Rect::new(0, 0, bar.width(), bar.height()));
+                    bar.roi(&dst).blend(&image.roi(&dst.offset(-x, -y)));
+                }
+                None => bar.char(
+                    button_rect.left() + (button_rect.width() - CHAR_WIDTH) / 2,
+                    (TITLE_HEIGHT - CHAR_HEIGHT) / 2,
+                    fallback,
+                    text_color,
+                ),
+            }
+        }
+
+        // 标题文字，超出部分截断
+        let max_chars = ((text_right - TITLE_PADDING) / CHAR_WIDTH).max(0) as usize;
+        for (i, c) in self.title.chars().take(max_chars).enumerate() {
+            bar.char(
+                TITLE_PADDING + i as i32 * CHAR_WIDTH,
+                (TITLE_HEIGHT - CHAR_HEIGHT) / 2,
+                c,
+                text_color,
+            );
+        }
+
+        bar
+    }
+
+    /// 窗口完全不透明的区域(窗体部分)，(半)透明窗口返回空矩形
+    /// 标题栏颜色可能半透明，不计入不透明区域
+    pub fn opaque_rect(&self) -> Rect {
+        if self.transparent {
+            Rect::default()
+        } else {
+            self.rect()
+        }
+    }
 
     /// # 函数功能
     /// 渲染窗体到显示窗口中
@@ -112,3 +537,96 @@ impl Window {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window() -> Window {
+        Window::new(100, 100, 1, Image::new(200, 150))
+    }
+
+    #[test]
+    fn resize_edge_hit_testing() {
+        let window = window();
+        assert_eq!(window.resize_edge(97, 150), Some(ResizeEdge::Left));
+        assert_eq!(window.resize_edge(300, 150), Some(ResizeEdge::Right));
+        assert_eq!(window.resize_edge(200, 252), Some(ResizeEdge::Bottom));
+        assert_eq!(window.resize_edge(95, 250), Some(ResizeEdge::BottomLeft));
+        assert_eq!(window.resize_edge(305, 255), Some(ResizeEdge::BottomRight));
+        // 窗体内部、标题栏旁和感应区域之外
+        assert_eq!(window.resize_edge(150, 150), None);
+        assert_eq!(window.resize_edge(97, 90), None);
+        assert_eq!(window.resize_edge(306, 150), None);
+        assert!(window.hit_rect().contains(97, 150));
+    }
+
+    #[test]
+    fn resize_edge_ignores_fixed_size_windows() {
+        let mut window = window();
+        window.resizable = false;
+        assert_eq!(window.resize_edge(97, 150), None);
+        assert!(!window.hit_rect().contains(97, 150));
+    }
+
+    #[test]
+    fn apply_keeps_opposite_edge_and_limits() {
+        let origin = Rect::new(100, 100, 200, 150);
+        let min = (60, 40);
+        let max = (400, 300);
+
+        let rect = ResizeEdge::Left.apply(origin, -50, 30, min, max);
+        assert_eq!(rect, Rect::new(50, 100, 250, 150));
+        assert_eq!(rect.right(), origin.right());
+
+        let rect = ResizeEdge::Left.apply(origin, 500, 0, min, max);
+        assert_eq!(rect, Rect::new(240, 100, 60, 150));
+
+        let rect = ResizeEdge::BottomRight.apply(origin, 1000, 1000, min, max);
+        assert_eq!(rect, Rect::new(100, 100, 400, 300));
+
+        let rect = ResizeEdge::Bottom.apply(origin, 80, -500, min, max);
+        assert_eq!(rect, Rect::new(100, 100, 200, 40));
+    }
+
+    #[test]
+    fn size_limits_are_sanitized() {
+        let mut window = window();
+        window.set_size_limits((0, 50), (10, 20));
+        assert_eq!(window.min_size, (1, 50));
+        assert_eq!(window.max_size, (10, 50));
+
+        window.set_size_limits((u32::MAX, 1), (u32::MAX, u32::MAX));
+        assert_eq!(window.min_size, (MAX_DIMENSION, 1));
+        assert_eq!(window.max_size, (MAX_DIMENSION, MAX_DIMENSION));
+    }
+
+    #[test]
+    fn queued_mouse_updates_are_coalesced() {
+        use starry_client::base::event::{KeyEvent, MouseUpdateEvent};
+
+        let mut window = window();
+        window.send_event(MouseUpdateEvent { x: 1, y: 1 }.to_event());
+        window.send_event(MouseUpdateEvent { x: 2, y: 3 }.to_event());
+        let key = KeyEvent {
+            character: 'a',
+            scancode: 0x1E,
+            pressed: true,
+        };
+        window.send_event(key.to_event());
+        window.send_event(MouseUpdateEvent { x: 4, y: 5 }.to_event());
+
+        let events: Vec<(i64, i64, i64)> = window
+            .events
+            .iter()
+            .map(|event| (event.code, event.a, event.b))
+            .collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0], (EVENT_MOUSE_UPDATE, 2, 3));
+        assert_eq!(events[2], (EVENT_MOUSE_UPDATE, 4, 5));
+
+        // 没有连接的窗口直接丢弃事件
+        window.flush_events();
+        assert!(window.events.is_empty());
+    }
+}
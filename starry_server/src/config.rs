@@ -1,88 +1,561 @@
-use std::{fs::File, io::Read};
+use std::{env, fmt, fs, io, path::Path};
 
-use log::debug;
+use log::{info, LevelFilter};
+use serde::{
+    de::{self, MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
 use serde_derive::Deserialize;
+use starry_client::base::color::Color;
+
+use crate::core::{backend::DEFAULT_BACKEND, input::evdev::IDENTITY_MATRIX};
+
+/// 指定配置文件路径的环境变量
+pub const CONFIG_ENV: &str = "STARRY_CONFIG";
+/// 默认的配置文件路径
+pub const DEFAULT_CONFIG_PATH: &str = "/etc/starry/starry.toml";
 
-/// TODO: 了解serde_derive::Deserialize及依赖
 /// 配置信息
+/// 配置文件中未出现的字段使用默认值
 #[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
-    // TODO: 补充注释
-    pub normal: String,
-    pub bottom_left_corner: String,
-    pub bottom_right_corner: String,
-    pub bottom_side: String,
-    pub left_side: String,
-    pub right_side: String,
+    /// 普通鼠标样式
+    pub normal: CursorConfig,
+    /// 左下角缩放鼠标样式
+    pub bottom_left_corner: CursorConfig,
+    /// 右下角缩放鼠标样式
+    pub bottom_right_corner: CursorConfig,
+    /// 底边缩放鼠标样式
+    pub bottom_side: CursorConfig,
+    /// 左边缩放鼠标样式
+    pub left_side: CursorConfig,
+    /// 右边缩放鼠标样式
+    pub right_side: CursorConfig,
+    /// 文本输入鼠标样式
+    pub text: CursorConfig,
+    /// 移动鼠标样式
+    #[serde(rename = "move")]
+    pub move_: CursorConfig,
+    /// 忙碌鼠标样式
+    pub busy: CursorConfig,
+    /// 禁止操作鼠标样式
+    pub not_allowed: CursorConfig,
+    /// 最大化按钮图片路径
     pub window_max: String,
+    /// 未聚焦时最大化按钮图片路径
     pub window_max_unfocused: String,
+    /// 关闭按钮图片路径
     pub window_close: String,
+    /// 未聚焦时关闭按钮图片路径
     pub window_close_unfocused: String,
-    // TODO: 实现Color反序列化
-    // #[serde(default = "background_color_default")]
-    // pub background_color: Color,
-    // #[serde(default = "bar_color_default")]
-    // pub bar_color: Color,
-    // #[serde(default = "bar_highlight_color_default")]
-    // pub bar_highlight_color: Color,
-    // #[serde(default = "text_color_default")]
-    // pub text_color: Color,
-    // #[serde(default = "text_highlight_color_default")]
-    // pub text_highlight_color: Color,
-}
-
-// fn background_color_default() -> Color { Color::rgb(0, 0, 0) }
-// fn bar_color_default() -> Color { Color::rgba(47, 52, 63, 224) }
-// fn bar_highlight_color_default() -> Color { Color::rgba(80, 86, 102, 224) }
-// fn text_color_default() -> Color { Color::rgb(204, 210, 224) }
-// fn text_highlight_color_default() -> Color { Color::rgb(204, 210, 224) }
+    /// 桌面背景图片路径，为空时使用内置图片
+    pub desktop_background: String,
+    /// 显示后端，格式见`core::backend::create_backend`
+    pub display_backend: String,
+    /// 16位帧缓冲是否启用有序抖动
+    pub display_dither: bool,
+    /// 背景颜色(没有窗口覆盖的区域)
+    pub background_color: Color,
+    /// 标题栏颜色
+    pub bar_color: Color,
+    /// 聚焦窗口的标题栏颜色
+    pub bar_highlight_color: Color,
+    /// 标题文字颜色
+    pub text_color: Color,
+    /// 聚焦窗口的标题文字颜色
+    pub text_highlight_color: Color,
+    /// PS/2鼠标设备路径，为空时不使用
+    pub mouse_device: String,
+    /// PS/2键盘设备路径，为空时不使用
+    pub keyboard_device: String,
+    /// evdev设备路径，以`*`结尾时匹配所有以该前缀开头的设备，如`/dev/input/event*`
+    pub evdev_devices: Vec<String>,
+    /// 键盘布局(内置布局名称或布局文件路径)，使用第一个布局，左Alt+Shift切换到下一个
+    pub keyboard_layouts: Vec<String>,
+    /// 焦点跟随鼠标：鼠标移入窗口时聚焦该窗口(不提升)，关闭时点击聚焦
+    pub focus_follows_mouse: bool,
+    /// 指针速度，相对移动量乘以该系数
+    pub pointer_speed: f32,
+    /// 指针加速方式
+    pub pointer_acceleration: AccelProfile,
+    /// 左手模式：交换鼠标左右键
+    pub left_handed: bool,
+    /// 绝对坐标设备x轴的取值范围[min, max]，未配置时使用设备报告的范围
+    pub absolute_x_range: Option<[i32; 2]>,
+    /// 绝对坐标设备y轴的取值范围[min, max]，未配置时使用设备报告的范围
+    pub absolute_y_range: Option<[i32; 2]>,
+    /// 是否交换绝对坐标设备的x轴和y轴
+    pub absolute_swap_axes: bool,
+    /// 是否翻转绝对坐标设备的x轴
+    pub absolute_invert_x: bool,
+    /// 是否翻转绝对坐标设备的y轴
+    pub absolute_invert_y: bool,
+    /// 绝对坐标设备的校准矩阵，见`core::input::evdev::Calibration`
+    pub absolute_calibration: [f32; 6],
+    /// 输出的最低日志级别
+    pub log_level: LogLevel,
+    /// 日志文件路径，为空时输出到标准错误
+    pub log_file: String,
+}
+
+/// 日志级别
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    /// 不输出日志
+    Off,
+    /// 只输出错误
+    Error,
+    /// 输出错误和警告
+    Warn,
+    /// 输出错误、警告和一般信息
+    #[default]
+    Info,
+    /// 输出调试信息
+    Debug,
+    /// 输出所有信息
+    Trace,
+}
+
+impl LogLevel {
+    /// 对应的`log`级别过滤器
+    pub fn filter(self) -> LevelFilter {
+        match self {
+            LogLevel::Off => LevelFilter::Off,
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+/// 指针加速方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccelProfile {
+    /// 不加速，移动量只乘以指针速度
+    #[default]
+    Flat,
+    /// 移动越快，移动量放大得越多
+    Adaptive,
+}
+
+/// 鼠标样式配置
+/// 配置文件中可以只写图片路径，也可以写作`{ path = "...", hotspot = [x, y] }`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CursorConfig {
+    /// 图片路径，为空时使用内置样式
+    pub path: String,
+    /// 热点(指针实际指向的位置)在图片中的坐标
+    pub hotspot: (i32, i32),
+}
+
+/// 反序列化鼠标样式，两种写法的错误都能指出具体的字段
+impl<'de> Deserialize<'de> for CursorConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(CursorVisitor)
+    }
+}
+
+struct CursorVisitor;
+
+impl<'de> Visitor<'de> for CursorVisitor {
+    type Value = CursorConfig;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "an image path or a table with path and optional hotspot = [x, y]"
+        )
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<CursorConfig, E> {
+        Ok(CursorConfig {
+            path: value.to_string(),
+            hotspot: (0, 0),
+        })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<CursorConfig, A::Error> {
+        let (mut path, mut hotspot) = (None, None);
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "path" if path.is_none() => path = Some(map.next_value()?),
+                "hotspot" if hotspot.is_none() => hotspot = Some(map.next_value()?),
+                "path" | "hotspot" => {
+                    return Err(de::Error::custom(format!("duplicate field `{}`", key)))
+                }
+                _ => return Err(de::Error::unknown_field(&key, &["path", "hotspot"])),
+            }
+        }
+
+        Ok(CursorConfig {
+            path: path.ok_or_else(|| de::Error::missing_field("path"))?,
+            hotspot: hotspot.unwrap_or((0, 0)),
+        })
+    }
+}
+
+fn display_backend_default() -> String {
+    DEFAULT_BACKEND.to_string()
+}
+
+fn display_dither_default() -> bool {
+    true
+}
+
+fn background_color_default() -> Color {
+    Color::rgb(0, 0, 0)
+}
+
+fn bar_color_default() -> Color {
+    Color::rgba(47, 52, 63, 224)
+}
+
+fn bar_highlight_color_default() -> Color {
+    Color::rgba(80, 86, 102, 224)
+}
+
+fn text_color_default() -> Color {
+    Color::rgb(204, 210, 224)
+}
+
+fn text_highlight_color_default() -> Color {
+    Color::rgb(204, 210, 224)
+}
+
+fn mouse_device_default() -> String {
+    "/dev/char/psmouse".to_string()
+}
+
+fn keyboard_device_default() -> String {
+    "/dev/char/ps2_keyboard".to_string()
+}
+
+fn keyboard_layouts_default() -> Vec<String> {
+    vec!["us".to_string()]
+}
 
 impl Default for Config {
     fn default() -> Self {
         Config {
-            normal: String::default(),
-            bottom_left_corner: String::default(),
-            bottom_right_corner: String::default(),
-            bottom_side: String::default(),
-            left_side: String::default(),
-            right_side: String::default(),
+            normal: CursorConfig::default(),
+            bottom_left_corner: CursorConfig::default(),
+            bottom_right_corner: CursorConfig::default(),
+            bottom_side: CursorConfig::default(),
+            left_side: CursorConfig::default(),
+            right_side: CursorConfig::default(),
+            text: CursorConfig::default(),
+            move_: CursorConfig::default(),
+            busy: CursorConfig::default(),
+            not_allowed: CursorConfig::default(),
             window_max: String::default(),
             window_max_unfocused: String::default(),
             window_close: String::default(),
             window_close_unfocused: String::default(),
-            // background_color: background_color_default(),
-            // bar_color: bar_color_default(),
-            // bar_highlight_color: bar_highlight_color_default(),
-            // text_color: text_color_default(),
-            // text_highlight_color: text_highlight_color_default(),
+            desktop_background: String::default(),
+            display_backend: display_backend_default(),
+            display_dither: display_dither_default(),
+            background_color: background_color_default(),
+            bar_color: bar_color_default(),
+            bar_highlight_color: bar_highlight_color_default(),
+            text_color: text_color_default(),
+            text_highlight_color: text_highlight_color_default(),
+            mouse_device: mouse_device_default(),
+            keyboard_device: keyboard_device_default(),
+            evdev_devices: Vec::new(),
+            keyboard_layouts: keyboard_layouts_default(),
+            focus_follows_mouse: false,
+            pointer_speed: 1.0,
+            pointer_acceleration: AccelProfile::default(),
+            left_handed: false,
+            absolute_x_range: None,
+            absolute_y_range: None,
+            absolute_swap_axes: false,
+            absolute_invert_x: false,
+            absolute_invert_y: false,
+            absolute_calibration: IDENTITY_MATRIX,
+            log_level: LogLevel::default(),
+            log_file: String::default(),
+        }
+    }
+}
+
+/// 配置文件错误
+#[derive(Debug)]
+pub enum ConfigError {
+    /// 读取文件失败
+    Io { path: String, err: io::Error },
+    /// 解析失败，附带出错的行号和列号(从1开始)
+    Parse {
+        path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl ConfigError {
+    /// # 函数功能
+    /// 将TOML解析错误转换为带行号和列号的错误
+    ///
+    /// ## 参数
+    /// - err: 解析错误
+    /// - source: 被解析的内容
+    /// - path: 文件路径
+    ///
+    /// ## 返回值
+    /// 配置文件错误
+    pub fn from_toml(err: toml::de::Error, source: &str, path: &str) -> Self {
+        let offset = err.span().map(|span| span.start).unwrap_or(0);
+        let (line, column) = Config::line_column(source, offset);
+        ConfigError::Parse {
+            path: path.to_string(),
+            line,
+            column,
+            message: err.message().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, err } => write!(f, "{}: {}", path, err),
+            ConfigError::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}:{}: {}", path, line, column, message),
         }
     }
 }
 
 impl Config {
-    /// 通过字符串解析配置
-    fn config_from_string(config: &str) -> Config {
-        match toml::from_str(config) {
-            Ok(config) => config,
-            Err(err) => {
-                println!("[Error] Config failed to parse config '{}'", err);
-                Config::default()
+    /// # 函数功能
+    /// 解析配置字符串
+    ///
+    /// ## 参数
+    /// - config: 配置内容
+    /// - path: 配置文件路径，用于错误信息
+    ///
+    /// ## 返回值
+    /// 解析成功时返回配置，否则返回带行号的错误
+    pub fn from_string(config: &str, path: &str) -> Result<Config, ConfigError> {
+        toml::from_str(config).map_err(|err| ConfigError::from_toml(err, config, path))
+    }
+
+    /// 读取并解析配置文件
+    pub fn from_path(path: &str) -> Result<Config, ConfigError> {
+        let config = fs::read_to_string(path).map_err(|err| ConfigError::Io {
+            path: path.to_string(),
+            err,
+        })?;
+        Self::from_string(&config, path)
+    }
+
+    /// # 函数功能
+    /// 按优先级确定配置文件路径：命令行参数指定的路径、环境变量指定的路径、默认路径
+    /// 显式指定的路径总是返回(不存在时在读取时报错)，默认路径仅在存在时返回
+    ///
+    /// ## 参数
+    /// - path_opt: 命令行参数指定的路径
+    ///
+    /// ## 返回值
+    /// 配置文件路径，没有可用的配置文件时返回None
+    pub fn find_path(path_opt: Option<&str>) -> Option<String> {
+        if let Some(path) = path_opt {
+            return Some(path.to_string());
+        }
+        if let Ok(path) = env::var(CONFIG_ENV) {
+            return Some(path);
+        }
+        if Path::new(DEFAULT_CONFIG_PATH).exists() {
+            return Some(DEFAULT_CONFIG_PATH.to_string());
+        }
+        None
+    }
+
+    /// # 函数功能
+    /// 加载配置
+    ///
+    /// ## 参数
+    /// - path_opt: 配置文件路径，为None时使用默认配置
+    ///
+    /// ## 返回值
+    /// 配置信息
+    pub fn load(path_opt: Option<&str>) -> Result<Config, ConfigError> {
+        match path_opt {
+            Some(path) => Self::from_path(path),
+            None => {
+                info!("No config file found, use default config");
+                Ok(Config::default())
             }
         }
     }
 
-    /// 通过文件路径解析配置
-    pub fn config_from_path(path: &str) -> Config {
-        let mut string = String::new();
+    /// 将字节偏移转换为行号和列号(从1开始)
+    fn line_column(string: &str, offset: usize) -> (usize, usize) {
+        let before = &string[..offset.min(string.len())];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+        (line, column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        match File::open(path) {
-            Ok(mut file) => match file.read_to_string(&mut string) {
-                Ok(_) => debug!("[Info] Reading config from path: '{}'", path),
-                Err(err) => debug!("[Error] Config failed to read config '{}': {}", path, err),
-            },
-            Err(err) => debug!("[Error] Config failed to open config '{}': {}", path, err),
+    fn parse(config: &str) -> Result<Config, ConfigError> {
+        Config::from_string(config, "test.toml")
+    }
+
+    /// 解析失败时返回(行号，列号，错误信息)
+    fn parse_error(config: &str) -> (usize, usize, String) {
+        match parse(config) {
+            Err(ConfigError::Parse {
+                line,
+                column,
+                message,
+                ..
+            }) => (line, column, message),
+            Err(err) => panic!("unexpected error {}", err),
+            Ok(_) => panic!("config should not parse"),
         }
+    }
+
+    #[test]
+    fn empty_config_uses_defaults() {
+        let config = parse("").unwrap();
+        let default = Config::default();
+        assert_eq!(config.display_backend, default.display_backend);
+        assert_eq!(config.display_dither, default.display_dither);
+        assert_eq!(config.bar_color.data, default.bar_color.data);
+        assert_eq!(config.normal, CursorConfig::default());
+    }
+
+    #[test]
+    fn shipped_config_parses() {
+        parse(include_str!("starry.toml")).unwrap();
+    }
+
+    #[test]
+    fn colors_accept_hex_and_tables() {
+        let config = parse(
+            r##"
+background_color = "#abc"
+bar_color = "#102030"
+bar_highlight_color = "#10203040"
+text_color = { r = 1, g = 2, b = 3 }
+text_highlight_color = { r = 1, g = 2, b = 3, a = 4 }
+"##,
+        )
+        .unwrap();
+
+        assert_eq!(config.background_color.data, 0xFFAABBCC);
+        assert_eq!(config.bar_color.data, 0xFF102030);
+        assert_eq!(config.bar_highlight_color.data, 0x40102030);
+        assert_eq!(config.text_color.data, 0xFF010203);
+        assert_eq!(config.text_highlight_color.data, 0x04010203);
+    }
+
+    #[test]
+    fn bad_color_reports_line_and_column() {
+        let (line, column, message) = parse_error(
+            r##"display_dither = false
+
+bar_color = "#12345"
+"##,
+        );
+        assert_eq!((line, column), (3, 13));
+        assert!(message.contains("#12345"), "{}", message);
+    }
+
+    #[test]
+    fn incomplete_color_table_is_rejected() {
+        let (line, _, message) = parse_error("text_color = { r = 1, g = 2 }\n");
+        assert_eq!(line, 1);
+        assert!(message.contains("missing field `b`"), "{}", message);
+
+        let (_, _, message) = parse_error("text_color = { r = 1, g = 2, b = 300 }\n");
+        assert!(message.contains("300"), "{}", message);
+    }
+
+    #[test]
+    fn unknown_field_reports_line() {
+        let (line, column, message) =
+            parse_error("display_dither = true\ndisplay_dihter = false\n");
+        assert_eq!((line, column), (2, 1));
+        assert!(message.contains("display_dihter"), "{}", message);
+    }
+
+    #[test]
+    fn cursors_accept_path_or_table() {
+        let config = parse(
+            r#"
+normal = "/cursors/normal.png"
+text = { path = "/cursors/text.png", hotspot = [4, 8] }
+busy = { path = "/cursors/busy.png" }
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.normal.path, "/cursors/normal.png");
+        assert_eq!(config.normal.hotspot, (0, 0));
+        assert_eq!(config.text.path, "/cursors/text.png");
+        assert_eq!(config.text.hotspot, (4, 8));
+        assert_eq!(config.busy.hotspot, (0, 0));
+    }
+
+    #[test]
+    fn cursor_errors_name_the_field() {
+        let (line, _, message) = parse_error("\ntext = { paht = \"/cursors/text.png\" }\n");
+        assert_eq!(line, 2);
+        assert!(message.contains("unknown field `paht`"), "{}", message);
+
+        let (_, _, message) = parse_error("text = { hotspot = [1, 2] }\n");
+        assert!(message.contains("missing field `path`"), "{}", message);
+
+        let (_, _, message) = parse_error("text = { path = \"a.png\", hotspot = [1] }\n");
+        assert!(
+            !message.contains("did not match any variant"),
+            "{}",
+            message
+        );
+
+        let (_, _, message) = parse_error("text = 1\n");
+        assert!(message.contains("image path"), "{}", message);
+    }
+
+    #[test]
+    fn log_level_is_lowercase() {
+        let config = parse("log_level = \"debug\"\nlog_file = \"/tmp/starry.log\"").unwrap();
+        assert_eq!(config.log_level.filter(), LevelFilter::Debug);
+        assert_eq!(config.log_file, "/tmp/starry.log");
+
+        let (line, _, message) = parse_error("\nlog_level = \"verbose\"");
+        assert_eq!(line, 2);
+        assert!(message.contains("unknown variant"), "{}", message);
+    }
+
+    #[test]
+    fn missing_file_is_io_error() {
+        match Config::from_path("/nonexistent/starry.toml") {
+            Err(ConfigError::Io { path, .. }) => assert_eq!(path, "/nonexistent/starry.toml"),
+            _ => panic!("expected io error"),
+        }
+    }
 
-        Self::config_from_string(&string)
+    #[test]
+    fn line_column_counts_from_one() {
+        let text = "ab\ncd\n";
+        assert_eq!(Config::line_column(text, 0), (1, 1));
+        assert_eq!(Config::line_column(text, 4), (2, 2));
+        assert_eq!(Config::line_column(text, 100), (3, 1));
     }
 }
@@ -0,0 +1,130 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+use log::error;
+use starry_client::base::{renderer::Renderer, screen::ScreenInfo};
+
+use crate::base::{display::Display, image::Image, rect::Rect};
+
+use super::DisplayBackend;
+
+/// 图片文件格式
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DumpFormat {
+    Ppm,
+    Png,
+}
+
+/// 将每一帧画面保存为图片文件的显示后端
+/// 用于调试和在无显示设备的环境中观察输出
+pub struct DumpBackend {
+    /// 图片保存目录
+    dir: PathBuf,
+    /// 图片格式
+    format: DumpFormat,
+    /// 当前画面
+    frame: Image,
+    /// 当前帧是否有更新
+    dirty: bool,
+    /// 已保存的帧数
+    frame_count: usize,
+}
+
+impl DumpBackend {
+    /// # 函数功能
+    /// 创建图片输出后端
+    ///
+    /// ## 参数
+    /// - arg: `目录[:ppm|png]`，目录为空时使用当前目录
+    /// - width: 屏幕宽度
+    /// - height: 屏幕高度
+    pub fn new(arg: &str, width: i32, height: i32) -> io::Result<Self> {
+        let (dir, format) = match arg.rsplit_once(':') {
+            Some((dir, "png")) => (dir, DumpFormat::Png),
+            Some((dir, "ppm")) => (dir, DumpFormat::Ppm),
+            _ => (arg, DumpFormat::Ppm),
+        };
+        let dir = if dir.is_empty() { "." } else { dir };
+        fs::create_dir_all(dir)?;
+
+        Ok(DumpBackend {
+            dir: PathBuf::from(dir),
+            format,
+            frame: Image::new(width, height),
+            dirty: false,
+            frame_count: 0,
+        })
+    }
+
+    /// 保存当前画面
+    fn save_frame(&self) -> io::Result<()> {
+        let extension = match self.format {
+            DumpFormat::Ppm => "ppm",
+            DumpFormat::Png => "png",
+        };
+        let path = self
+            .dir
+            .join(format!("frame_{:06}.{}", self.frame_count, extension));
+
+        let (width, height) = self.frame.dimensions();
+        match self.format {
+            DumpFormat::Ppm => {
+                let mut writer = BufWriter::new(File::create(&path)?);
+                write!(writer, "P6\n{} {}\n255\n", width, height)?;
+                for pixel in self.frame.data() {
+                    writer.write_all(&[pixel.r(), pixel.g(), pixel.b()])?;
+                }
+                writer.flush()
+            }
+            DumpFormat::Png => {
+                let mut bytes = Vec::with_capacity(self.frame.data().len() * 4);
+                for pixel in self.frame.data() {
+                    bytes.extend_from_slice(&[pixel.r(), pixel.g(), pixel.b(), 255]);
+                }
+                image::save_buffer(
+                    &path,
+                    &bytes,
+                    width as u32,
+                    height as u32,
+                    image::ColorType::Rgba8,
+                )
+                .map_err(io::Error::other)
+            }
+        }
+    }
+}
+
+impl DisplayBackend for DumpBackend {
+    fn name(&self) -> &str {
+        "dump"
+    }
+
+    fn screen_info(&self) -> ScreenInfo {
+        ScreenInfo::new(self.frame.width() as u32, self.frame.height() as u32)
+    }
+
+    fn present(&mut self, display: &mut Display, rect: &Rect) {
+        let rect = rect.intersection(&Rect::new(0, 0, self.frame.width(), self.frame.height()));
+        if rect.is_empty() {
+            return;
+        }
+
+        self.frame.roi(&rect).cover(&display.roi(&rect));
+        self.dirty = true;
+    }
+
+    fn flush(&mut self) {
+        if !self.dirty {
+            return;
+        }
+
+        if let Err(err) = self.save_frame() {
+            error!("DumpBackend failed to save frame: {}", err);
+        }
+        self.dirty = false;
+        self.frame_count += 1;
+    }
+}
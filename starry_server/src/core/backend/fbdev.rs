@@ -0,0 +1,292 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Seek, SeekFrom, Write},
+    os::unix::io::AsRawFd,
+    ptr, slice,
+};
+
+use log::{error, warn};
+use starry_client::base::{
+    renderer::Renderer,
+    screen::{Channel, PixelFormat, ScreenInfo},
+};
+
+use crate::base::{display::Display, rect::Rect};
+
+use super::{format::PixelConverter, DisplayBackend};
+
+/// 默认的帧缓冲设备路径
+pub const FB_FILE_PATH: &str = "/dev/fb0";
+
+/// 获取可变屏幕信息
+const FBIOGET_VSCREENINFO: libc::c_ulong = 0x4600;
+/// 获取固定屏幕信息
+const FBIOGET_FSCREENINFO: libc::c_ulong = 0x4602;
+
+/// 对应内核的`struct fb_bitfield`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct FbBitfield {
+    offset: u32,
+    length: u32,
+    msb_right: u32,
+}
+
+/// 对应内核的`struct fb_var_screeninfo`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct FbVarScreenInfo {
+    xres: u32,
+    yres: u32,
+    xres_virtual: u32,
+    yres_virtual: u32,
+    xoffset: u32,
+    yoffset: u32,
+    bits_per_pixel: u32,
+    grayscale: u32,
+    red: FbBitfield,
+    green: FbBitfield,
+    blue: FbBitfield,
+    transp: FbBitfield,
+    nonstd: u32,
+    activate: u32,
+    height: u32,
+    width: u32,
+    accel_flags: u32,
+    pixclock: u32,
+    left_margin: u32,
+    right_margin: u32,
+    upper_margin: u32,
+    lower_margin: u32,
+    hsync_len: u32,
+    vsync_len: u32,
+    sync: u32,
+    vmode: u32,
+    rotate: u32,
+    colorspace: u32,
+    reserved: [u32; 4],
+}
+
+/// 对应内核的`struct fb_fix_screeninfo`
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct FbFixScreenInfo {
+    id: [u8; 16],
+    smem_start: libc::c_ulong,
+    smem_len: u32,
+    type_: u32,
+    type_aux: u32,
+    visual: u32,
+    xpanstep: u16,
+    ypanstep: u16,
+    ywrapstep: u16,
+    line_length: u32,
+    mmio_start: libc::c_ulong,
+    mmio_len: u32,
+    accel: u32,
+    capabilities: u16,
+    reserved: [u16; 2],
+}
+
+impl From<FbBitfield> for Channel {
+    fn from(bitfield: FbBitfield) -> Self {
+        Channel::new(bitfield.offset, bitfield.length)
+    }
+}
+
+/// 映射到内存的帧缓冲区
+struct FbMapping {
+    /// 映射的起始地址
+    ptr: *mut u8,
+    /// 映射的字节数
+    len: usize,
+}
+
+impl FbMapping {
+    /// 将帧缓冲文件的前len字节映射到内存
+    fn new(fb_file: &File, len: usize) -> io::Result<Self> {
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fb_file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(FbMapping {
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr, self.len) }
+    }
+}
+
+impl Drop for FbMapping {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+/// 帧缓冲设备显示后端
+pub struct FbdevBackend {
+    /// 帧缓冲文件
+    fb_file: File,
+    /// 屏幕信息
+    info: ScreenInfo,
+    /// 像素格式转换器
+    converter: PixelConverter,
+    /// 帧缓冲的内存映射，设备不支持映射时为None
+    mapping: Option<FbMapping>,
+    /// 无法映射时用于逐行写入的缓冲区
+    row_buffer: Vec<u8>,
+}
+
+impl FbdevBackend {
+    /// # 函数功能
+    /// 打开帧缓冲设备并读取屏幕属性
+    ///
+    /// ## 参数
+    /// - path: 帧缓冲设备路径
+    /// - fallback: 设备不支持查询时使用的屏幕信息
+    /// - dither: 16位帧缓冲是否启用有序抖动
+    pub fn new(path: &str, fallback: ScreenInfo, dither: bool) -> io::Result<Self> {
+        let fb_file = OpenOptions::new().read(true).write(true).open(path)?;
+        let info = match Self::query_screen_info(&fb_file) {
+            Ok(info) => info,
+            Err(err) => {
+                warn!(
+                    "Failed to query framebuffer '{}' ({}), assume {}x{}",
+                    path, err, fallback.width, fallback.height
+                );
+                fallback
+            }
+        };
+
+        let converter = PixelConverter::new(info.format, dither).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported framebuffer format {:?}", info.format),
+            )
+        })?;
+
+        let mapping = match FbMapping::new(&fb_file, (info.stride * info.height) as usize) {
+            Ok(mapping) => Some(mapping),
+            Err(err) => {
+                warn!(
+                    "Failed to mmap framebuffer '{}' ({}), fall back to write",
+                    path, err
+                );
+                None
+            }
+        };
+
+        Ok(FbdevBackend {
+            fb_file,
+            info,
+            converter,
+            mapping,
+            row_buffer: Vec::new(),
+        })
+    }
+
+    /// 通过ioctl读取帧缓冲设备的分辨率、行宽和像素格式
+    fn query_screen_info(fb_file: &File) -> io::Result<ScreenInfo> {
+        let mut var_info = FbVarScreenInfo::default();
+        let mut fix_info = FbFixScreenInfo::default();
+
+        unsafe {
+            if libc::ioctl(fb_file.as_raw_fd(), FBIOGET_VSCREENINFO as _, &mut var_info) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::ioctl(fb_file.as_raw_fd(), FBIOGET_FSCREENINFO as _, &mut fix_info) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        let format = PixelFormat {
+            bits_per_pixel: var_info.bits_per_pixel,
+            red: var_info.red.into(),
+            green: var_info.green.into(),
+            blue: var_info.blue.into(),
+            alpha: var_info.transp.into(),
+        };
+        let stride = if fix_info.line_length > 0 {
+            fix_info.line_length
+        } else {
+            var_info.xres * format.bytes_per_pixel()
+        };
+
+        Ok(ScreenInfo {
+            width: var_info.xres,
+            height: var_info.yres,
+            stride,
+            format,
+        })
+    }
+}
+
+impl DisplayBackend for FbdevBackend {
+    fn name(&self) -> &str {
+        "fbdev"
+    }
+
+    fn screen_info(&self) -> ScreenInfo {
+        self.info
+    }
+
+    fn present(&mut self, display: &mut Display, rect: &Rect) {
+        let rect = rect.intersection(&Rect::new(
+            0,
+            0,
+            self.info.width as i32,
+            self.info.height as i32,
+        ));
+        if rect.is_empty() {
+            return;
+        }
+
+        let stride = self.info.stride as usize;
+        let bytes_per_pixel = self.converter.bytes_per_pixel();
+        let image_width = display.image.width();
+        let data = display.image.data();
+        let row_len = rect.width() as usize * bytes_per_pixel;
+
+        // 逐行转换，每行只需一次拷贝(或一次写入)
+        for y in rect.top()..rect.bottom() {
+            let src_start = ((y - display.y) * image_width + rect.left() - display.x) as usize;
+            let src = &data[src_start..src_start + rect.width() as usize];
+            let offset = y as usize * stride + rect.left() as usize * bytes_per_pixel;
+
+            match self.mapping.as_mut() {
+                Some(mapping) => {
+                    let dst = &mut mapping.data_mut()[offset..offset + row_len];
+                    self.converter.convert_row(src, rect.left(), y, dst);
+                }
+                None => {
+                    self.row_buffer.resize(row_len, 0);
+                    self.converter
+                        .convert_row(src, rect.left(), y, &mut self.row_buffer);
+                    let result = self
+                        .fb_file
+                        .seek(SeekFrom::Start(offset as u64))
+                        .and_then(|_| self.fb_file.write_all(&self.row_buffer));
+                    // 写入失败时放弃这一帧剩余的行，下次重绘时再尝试
+                    if let Err(err) = result {
+                        error!("Failed to write framebuffer: {}", err);
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
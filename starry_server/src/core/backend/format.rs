@@ -0,0 +1,393 @@
+use std::slice;
+
+use starry_client::base::{
+    color::Color,
+    screen::{Channel, PixelFormat},
+};
+
+/// 4x4有序抖动矩阵(Bayer矩阵)，取值0~15
+const BAYER_MATRIX: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// 帧缓冲像素的存储方式
+/// 名称表示像素值从高位到低位的通道顺序，按小端序存储
+/// X表示未使用的填充字节，其取值不作保证
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelLayout {
+    /// 16位，R5 G6 B5
+    Rgb565,
+    /// 16位，B5 G6 R5
+    Bgr565,
+    /// 24位，内存中依次为B、G、R
+    Rgb888,
+    /// 24位，内存中依次为R、G、B
+    Bgr888,
+    /// 32位，内存中依次为B、G、R、X
+    Xrgb8888,
+    /// 32位，内存中依次为B、G、R、A
+    Argb8888,
+    /// 32位，内存中依次为R、G、B、X
+    Xbgr8888,
+    /// 32位，内存中依次为R、G、B、A
+    Abgr8888,
+    /// 32位，内存中依次为A、B、G、R
+    Rgba8888,
+    /// 32位，内存中依次为A、R、G、B
+    Bgra8888,
+    /// 其他通道排列，按通道偏移逐个组装
+    Other,
+}
+
+/// 将Color转换为帧缓冲像素格式的转换器
+#[derive(Clone, Copy, Debug)]
+pub struct PixelConverter {
+    /// 目标像素格式
+    format: PixelFormat,
+    /// 存储方式
+    layout: PixelLayout,
+    /// 每个像素的字节数
+    bytes_per_pixel: usize,
+    /// 是否对低位深通道进行有序抖动
+    dither: bool,
+}
+
+impl PixelConverter {
+    /// # 函数功能
+    /// 根据帧缓冲的像素格式创建转换器
+    ///
+    /// ## 参数
+    /// - format: 帧缓冲像素格式
+    /// - dither: 是否对16位格式启用有序抖动
+    ///
+    /// ## 返回值
+    /// 支持该格式时返回转换器，仅支持16/24/32位
+    pub fn new(format: PixelFormat, dither: bool) -> Option<Self> {
+        if !matches!(format.bits_per_pixel, 16 | 24 | 32) {
+            return None;
+        }
+
+        let channels = (
+            format.bits_per_pixel,
+            format.red,
+            format.green,
+            format.blue,
+            format.alpha.length > 0,
+        );
+        let c = Channel::new;
+        let layout = match channels {
+            (16, r, g, b, _) if (r, g, b) == (c(11, 5), c(5, 6), c(0, 5)) => PixelLayout::Rgb565,
+            (16, r, g, b, _) if (r, g, b) == (c(0, 5), c(5, 6), c(11, 5)) => PixelLayout::Bgr565,
+            (24, r, g, b, _) if (r, g, b) == (c(16, 8), c(8, 8), c(0, 8)) => PixelLayout::Rgb888,
+            (24, r, g, b, _) if (r, g, b) == (c(0, 8), c(8, 8), c(16, 8)) => PixelLayout::Bgr888,
+            (32, r, g, b, false) if (r, g, b) == (c(16, 8), c(8, 8), c(0, 8)) => {
+                PixelLayout::Xrgb8888
+            }
+            (32, r, g, b, true) if (r, g, b) == (c(16, 8), c(8, 8), c(0, 8)) => {
+                PixelLayout::Argb8888
+            }
+            (32, r, g, b, false) if (r, g, b) == (c(0, 8), c(8, 8), c(16, 8)) => {
+                PixelLayout::Xbgr8888
+            }
+            (32, r, g, b, true) if (r, g, b) == (c(0, 8), c(8, 8), c(16, 8)) => {
+                PixelLayout::Abgr8888
+            }
+            (32, r, g, b, _) if (r, g, b) == (c(24, 8), c(16, 8), c(8, 8)) => PixelLayout::Rgba8888,
+            (32, r, g, b, _) if (r, g, b) == (c(8, 8), c(16, 8), c(24, 8)) => PixelLayout::Bgra8888,
+            _ => PixelLayout::Other,
+        };
+
+        Some(PixelConverter {
+            format,
+            layout,
+            bytes_per_pixel: format.bytes_per_pixel() as usize,
+            dither: dither && format.bits_per_pixel == 16,
+        })
+    }
+
+    /// 存储方式
+    pub fn layout(&self) -> PixelLayout {
+        self.layout
+    }
+
+    /// 每个像素的字节数
+    pub fn bytes_per_pixel(&self) -> usize {
+        self.bytes_per_pixel
+    }
+
+    /// # 函数功能
+    /// 转换一个像素
+    ///
+    /// ## 参数
+    /// - color: 颜色
+    /// - x: 像素在屏幕上的x坐标(用于抖动)
+    /// - y: 像素在屏幕上的y坐标(用于抖动)
+    /// - out: 输出缓冲区，长度至少为一个像素的字节数
+    pub fn convert_pixel(&self, color: Color, x: i32, y: i32, out: &mut [u8]) {
+        let color = if self.dither {
+            Self::dither(color, x, y)
+        } else {
+            color
+        };
+        let (r, g, b, a) = (color.r(), color.g(), color.b(), color.a());
+
+        match self.layout {
+            PixelLayout::Rgb565 => out[..2].copy_from_slice(&Self::pack_565(r, g, b)),
+            PixelLayout::Bgr565 => out[..2].copy_from_slice(&Self::pack_565(b, g, r)),
+            PixelLayout::Rgb888 => out[..3].copy_from_slice(&[b, g, r]),
+            PixelLayout::Bgr888 => out[..3].copy_from_slice(&[r, g, b]),
+            PixelLayout::Xrgb8888 => out[..4].copy_from_slice(&[b, g, r, 0xFF]),
+            PixelLayout::Argb8888 => out[..4].copy_from_slice(&[b, g, r, a]),
+            PixelLayout::Xbgr8888 => out[..4].copy_from_slice(&[r, g, b, 0xFF]),
+            PixelLayout::Abgr8888 => out[..4].copy_from_slice(&[r, g, b, a]),
+            PixelLayout::Rgba8888 => out[..4].copy_from_slice(&[a, b, g, r]),
+            PixelLayout::Bgra8888 => out[..4].copy_from_slice(&[a, r, g, b]),
+            PixelLayout::Other => {
+                let value = Self::pack_channel(r, self.format.red)
+                    | Self::pack_channel(g, self.format.green)
+                    | Self::pack_channel(b, self.format.blue)
+                    | Self::pack_channel(a, self.format.alpha);
+                out[..self.bytes_per_pixel]
+                    .copy_from_slice(&value.to_le_bytes()[..self.bytes_per_pixel]);
+            }
+        }
+    }
+
+    /// # 函数功能
+    /// 转换一行像素
+    ///
+    /// ## 参数
+    /// - src: 源像素
+    /// - x: 首个像素在屏幕上的x坐标
+    /// - y: 该行在屏幕上的y坐标
+    /// - out: 输出缓冲区，长度至少为`src.len()`个像素的字节数
+    pub fn convert_row(&self, src: &[Color], x: i32, y: i32, out: &mut [u8]) {
+        // 与Color内存布局一致时直接拷贝
+        if !self.dither
+            && cfg!(target_endian = "little")
+            && matches!(self.layout, PixelLayout::Argb8888 | PixelLayout::Xrgb8888)
+        {
+            let bytes = unsafe { slice::from_raw_parts(src.as_ptr() as *const u8, src.len() * 4) };
+            out[..bytes.len()].copy_from_slice(bytes);
+            return;
+        }
+
+        let bpp = self.bytes_per_pixel;
+        for (i, (color, pixel)) in src.iter().zip(out.chunks_exact_mut(bpp)).enumerate() {
+            self.convert_pixel(*color, x + i as i32, y, pixel);
+        }
+    }
+
+    /// 按16位RGB565的位宽组装像素，first位于高位
+    fn pack_565(first: u8, green: u8, last: u8) -> [u8; 2] {
+        let value = ((first as u16 >> 3) << 11) | ((green as u16 >> 2) << 5) | (last as u16 >> 3);
+        value.to_le_bytes()
+    }
+
+    /// 将8位通道值缩放到通道位宽并移动到对应位置
+    fn pack_channel(value: u8, channel: Channel) -> u32 {
+        let value = match channel.length {
+            0 => return 0,
+            length @ 1..=8 => (value as u32) >> (8 - length),
+            // 超过8位的通道将高位对齐，保证最大值仍接近满量程
+            length => (value as u32) << (length.min(24) - 8),
+        };
+        value << channel.offset
+    }
+
+    /// # 函数功能
+    /// 对颜色进行有序抖动，使截断到5/6位后的平均值接近原色
+    ///
+    /// ## 参数
+    /// - color: 颜色
+    /// - x: 像素x坐标
+    /// - y: 像素y坐标
+    ///
+    /// ## 返回值
+    /// 抖动后的颜色
+    fn dither(color: Color, x: i32, y: i32) -> Color {
+        let threshold = BAYER_MATRIX[(y & 3) as usize][(x & 3) as usize] as u16;
+        // 红蓝通道截断3位(步长8)，绿色通道截断2位(步长4)
+        let offset = |value: u8, step: u16| (value as u16 + threshold * step / 16).min(255) as u8;
+        Color::rgba(
+            offset(color.r(), 8),
+            offset(color.g(), 4),
+            offset(color.b(), 8),
+            color.a(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLOR: Color = Color::rgba(0x12, 0x34, 0x56, 0x78);
+
+    fn format(bits_per_pixel: u32, channels: [(u32, u32); 4]) -> PixelFormat {
+        let [red, green, blue, alpha] =
+            channels.map(|(offset, length)| Channel::new(offset, length));
+        PixelFormat {
+            bits_per_pixel,
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    fn convert(format: PixelFormat, color: Color) -> (PixelLayout, Vec<u8>) {
+        let converter = PixelConverter::new(format, false).unwrap();
+        let mut out = vec![0u8; converter.bytes_per_pixel()];
+        converter.convert_pixel(color, 0, 0, &mut out);
+        (converter.layout(), out)
+    }
+
+    #[test]
+    fn known_layouts_produce_expected_bytes() {
+        let cases = [
+            (
+                format(16, [(11, 5), (5, 6), (0, 5), (0, 0)]),
+                PixelLayout::Rgb565,
+                vec![0xAA, 0x11],
+            ),
+            (
+                format(16, [(0, 5), (5, 6), (11, 5), (0, 0)]),
+                PixelLayout::Bgr565,
+                vec![0xA2, 0x51],
+            ),
+            (
+                format(24, [(16, 8), (8, 8), (0, 8), (0, 0)]),
+                PixelLayout::Rgb888,
+                vec![0x56, 0x34, 0x12],
+            ),
+            (
+                format(24, [(0, 8), (8, 8), (16, 8), (0, 0)]),
+                PixelLayout::Bgr888,
+                vec![0x12, 0x34, 0x56],
+            ),
+            (
+                format(32, [(16, 8), (8, 8), (0, 8), (0, 0)]),
+                PixelLayout::Xrgb8888,
+                vec![0x56, 0x34, 0x12, 0xFF],
+            ),
+            (
+                format(32, [(16, 8), (8, 8), (0, 8), (24, 8)]),
+                PixelLayout::Argb8888,
+                vec![0x56, 0x34, 0x12, 0x78],
+            ),
+            (
+                format(32, [(0, 8), (8, 8), (16, 8), (0, 0)]),
+                PixelLayout::Xbgr8888,
+                vec![0x12, 0x34, 0x56, 0xFF],
+            ),
+            (
+                format(32, [(0, 8), (8, 8), (16, 8), (24, 8)]),
+                PixelLayout::Abgr8888,
+                vec![0x12, 0x34, 0x56, 0x78],
+            ),
+            (
+                format(32, [(24, 8), (16, 8), (8, 8), (0, 8)]),
+                PixelLayout::Rgba8888,
+                vec![0x78, 0x56, 0x34, 0x12],
+            ),
+            (
+                format(32, [(8, 8), (16, 8), (24, 8), (0, 8)]),
+                PixelLayout::Bgra8888,
+                vec![0x78, 0x12, 0x34, 0x56],
+            ),
+        ];
+
+        for (format, layout, bytes) in cases {
+            assert_eq!(convert(format, COLOR), (layout, bytes), "{:?}", layout);
+        }
+    }
+
+    #[test]
+    fn other_layouts_pack_each_channel() {
+        // RGB555
+        let rgb555 = format(16, [(10, 5), (5, 5), (0, 5), (0, 0)]);
+        assert_eq!(
+            convert(rgb555, COLOR),
+            (PixelLayout::Other, vec![0xCA, 0x08])
+        );
+
+        // ARGB2101010，10位通道按高位对齐
+        let argb2101010 = format(32, [(20, 10), (10, 10), (0, 10), (30, 2)]);
+        assert_eq!(
+            convert(argb2101010, COLOR),
+            (PixelLayout::Other, vec![0x58, 0x41, 0x83, 0x44])
+        );
+    }
+
+    #[test]
+    fn unsupported_depths_are_rejected() {
+        assert!(PixelConverter::new(format(8, [(5, 3), (2, 3), (0, 2), (0, 0)]), false).is_none());
+        assert!(
+            PixelConverter::new(format(15, [(10, 5), (5, 5), (0, 5), (0, 0)]), false).is_none()
+        );
+    }
+
+    #[test]
+    fn row_conversion_matches_pixel_conversion() {
+        let row: Vec<Color> = (0..7u8)
+            .map(|i| Color::rgba(i * 30, 255 - i * 20, i * 7, 128 + i))
+            .collect();
+
+        for format in [
+            PixelFormat::ARGB8888,
+            format(32, [(16, 8), (8, 8), (0, 8), (0, 0)]),
+            format(24, [(0, 8), (8, 8), (16, 8), (0, 0)]),
+            format(16, [(11, 5), (5, 6), (0, 5), (0, 0)]),
+        ] {
+            let converter = PixelConverter::new(format, false).unwrap();
+            let bpp = converter.bytes_per_pixel();
+            let mut fast = vec![0u8; row.len() * bpp];
+            converter.convert_row(&row, 3, 5, &mut fast);
+
+            let mut slow = vec![0u8; row.len() * bpp];
+            for (i, color) in row.iter().enumerate() {
+                converter.convert_pixel(*color, 3 + i as i32, 5, &mut slow[i * bpp..]);
+            }
+
+            // 忽略填充字节
+            if converter.layout() == PixelLayout::Xrgb8888 {
+                for pixel in fast.chunks_exact_mut(4).chain(slow.chunks_exact_mut(4)) {
+                    pixel[3] = 0;
+                }
+            }
+            assert_eq!(fast, slow, "{:?}", converter.layout());
+        }
+    }
+
+    #[test]
+    fn dithering_averages_to_source_over_bayer_tile() {
+        let rgb565 = format(16, [(11, 5), (5, 6), (0, 5), (0, 0)]);
+        let converter = PixelConverter::new(rgb565, true).unwrap();
+
+        // 除去无法向上取整的最高一档，4x4区域内的平均值应与原色一致
+        for value in 0..=247u8 {
+            let color = Color::rgb(value, value.min(251), value);
+            let mut sums = [0u32; 3];
+            for y in 0..4 {
+                for x in 0..4 {
+                    let mut out = [0u8; 2];
+                    converter.convert_pixel(color, x, y, &mut out);
+                    let pixel = u16::from_le_bytes(out) as u32;
+                    sums[0] += (pixel >> 11) * 8;
+                    sums[1] += ((pixel >> 5) & 0x3F) * 4;
+                    sums[2] += (pixel & 0x1F) * 8;
+                }
+            }
+            assert_eq!(sums, [value as u32 * 16; 3], "value {}", value);
+        }
+    }
+
+    #[test]
+    fn dithering_only_applies_to_16_bits() {
+        let converter = PixelConverter::new(PixelFormat::ARGB8888, true).unwrap();
+        let mut first = [0u8; 4];
+        let mut second = [0u8; 4];
+        converter.convert_pixel(COLOR, 0, 0, &mut first);
+        converter.convert_pixel(COLOR, 3, 1, &mut second);
+        assert_eq!(first, second);
+    }
+}
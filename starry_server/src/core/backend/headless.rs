@@ -0,0 +1,61 @@
+use starry_client::base::{color::Color, renderer::Renderer, screen::ScreenInfo};
+
+use crate::base::{display::Display, image::Image, rect::Rect};
+
+use super::DisplayBackend;
+
+/// 无显示设备的后端，画面仅保存在内存中
+/// 用于在没有帧缓冲设备的环境中运行和测试
+pub struct HeadlessBackend {
+    /// 最近一次输出的画面
+    frame: Image,
+    /// 已输出的帧数
+    frame_count: usize,
+}
+
+impl HeadlessBackend {
+    pub fn new(width: i32, height: i32) -> Self {
+        HeadlessBackend {
+            frame: Image::new(width, height),
+            frame_count: 0,
+        }
+    }
+
+    /// 最近一次输出的画面
+    pub fn frame(&self) -> &Image {
+        &self.frame
+    }
+
+    /// 已输出的帧数
+    pub fn frame_count(&self) -> usize {
+        self.frame_count
+    }
+
+    /// 获取画面中指定位置的像素
+    pub fn pixel(&self, x: i32, y: i32) -> Color {
+        self.frame.get_pixel(x, y)
+    }
+}
+
+impl DisplayBackend for HeadlessBackend {
+    fn name(&self) -> &str {
+        "headless"
+    }
+
+    fn screen_info(&self) -> ScreenInfo {
+        ScreenInfo::new(self.frame.width() as u32, self.frame.height() as u32)
+    }
+
+    fn present(&mut self, display: &mut Display, rect: &Rect) {
+        let rect = rect.intersection(&Rect::new(0, 0, self.frame.width(), self.frame.height()));
+        if rect.is_empty() {
+            return;
+        }
+
+        self.frame.roi(&rect).cover(&display.roi(&rect));
+    }
+
+    fn flush(&mut self) {
+        self.frame_count += 1;
+    }
+}
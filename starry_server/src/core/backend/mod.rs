@@ -0,0 +1,85 @@
+use std::io;
+
+use starry_client::base::screen::ScreenInfo;
+
+use crate::base::{display::Display, rect::Rect};
+
+use self::{dump::DumpBackend, fbdev::FbdevBackend, headless::HeadlessBackend};
+
+pub mod dump;
+pub mod fbdev;
+pub mod format;
+pub mod headless;
+
+/// 默认使用的显示后端
+pub const DEFAULT_BACKEND: &str = "fbdev";
+/// 无法获取屏幕属性时默认的屏幕宽度
+pub const DEFAULT_SCREEN_WIDTH: u32 = 1440;
+/// 无法获取屏幕属性时默认的屏幕高度
+pub const DEFAULT_SCREEN_HEIGHT: u32 = 900;
+
+/// 显示后端需要实现的特性
+/// 合成渲染器将合成好的画面交给显示后端输出
+pub trait DisplayBackend {
+    /// 后端名称
+    fn name(&self) -> &str;
+
+    /// 屏幕分辨率、行宽和像素格式
+    fn screen_info(&self) -> ScreenInfo;
+
+    /// # 函数功能
+    /// 将显示窗口中的一块区域输出
+    ///
+    /// ## 参数
+    /// - display: 显示窗口
+    /// - rect: 输出的矩形区域(绝对位置)
+    fn present(&mut self, display: &mut Display, rect: &Rect);
+
+    /// 一帧画面输出完毕
+    fn flush(&mut self) {}
+}
+
+/// # 函数功能
+/// 根据描述字符串创建显示后端
+/// 描述字符串格式为`名称[:参数]`，支持:
+/// - `fbdev[:设备路径]`: 帧缓冲设备
+/// - `headless`: 仅输出到内存
+/// - `dump[:目录[:ppm|png]]`: 将每一帧保存为图片文件
+///
+/// ## 参数
+/// - spec: 后端描述字符串
+/// - width: 屏幕宽度，帧缓冲设备以设备属性为准
+/// - height: 屏幕高度，帧缓冲设备以设备属性为准
+/// - dither: 16位帧缓冲是否启用有序抖动
+///
+/// ## 返回值
+/// 显示后端对象
+pub fn create_backend(
+    spec: &str,
+    width: u32,
+    height: u32,
+    dither: bool,
+) -> io::Result<Box<dyn DisplayBackend>> {
+    let (name, arg) = match spec.split_once(':') {
+        Some((name, arg)) => (name, Some(arg)),
+        None => (spec, None),
+    };
+
+    match name {
+        "fbdev" => Ok(Box::new(FbdevBackend::new(
+            arg.unwrap_or(fbdev::FB_FILE_PATH),
+            ScreenInfo::new(width, height),
+            dither,
+        )?)),
+        "headless" => Ok(Box::new(HeadlessBackend::new(width as i32, height as i32))),
+        "dump" => Ok(Box::new(DumpBackend::new(
+            arg.unwrap_or(""),
+            width as i32,
+            height as i32,
+        )?)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unknown display backend '{}'", name),
+        )),
+    }
+}
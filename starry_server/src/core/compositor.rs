@@ -1,92 +1,105 @@
 use std::{
     cell::RefCell,
-    fs::File,
-    io::{Seek, SeekFrom, Write},
-    sync::Arc,
+    collections::BTreeMap,
+    rc::{Rc, Weak},
 };
 
-use starry_client::base::renderer::Renderer;
+use crate::base::{damage::Damage, rect::Rect, window::Window};
 
-use crate::base::rect::Rect;
-
-use super::{starry_server, window_manager::window_manager, SCREEN_WIDTH};
-
-static mut COMPOSITOR: Option<Arc<Compositor>> = None;
-
-const FB_FILE_PATH: &str = "/dev/fb0";
-
-/// 获得合成渲染器实例
-pub fn compositor() -> Option<Arc<Compositor>> {
-    unsafe { COMPOSITOR.clone() }
-}
+use super::{backend::DisplayBackend, window_manager::RESIZE_OUTLINE, Context};
 
 #[allow(dead_code)]
 /// 合成渲染器
 pub struct Compositor {
-    /// 待重绘的矩形区域
-    redraws: RefCell<Vec<Rect>>,
-    /// 帧缓冲文件
-    fb_file: RefCell<File>,
+    /// 所属的服务器上下文
+    ctx: Weak<Context>,
+    /// 待重绘的区域
+    redraws: RefCell<Damage>,
+    /// 显示后端
+    backend: RefCell<Box<dyn DisplayBackend>>,
 }
 
 #[allow(dead_code)]
 impl Compositor {
+    /// # 函数功能
     /// 创建合成渲染器
-    pub fn new() {
-        let compositor = Compositor {
-            redraws: RefCell::new(Vec::new()),
-            fb_file: RefCell::new(
-                File::open(FB_FILE_PATH).expect("[Error] Compositor failed to open fb file"),
-            ),
-        };
-
-        unsafe {
-            COMPOSITOR = Some(Arc::new(compositor));
+    ///
+    /// ## 参数
+    /// - ctx: 所属的服务器上下文
+    /// - backend: 显示后端
+    pub fn new(ctx: Weak<Context>, backend: Box<dyn DisplayBackend>) -> Self {
+        // println!("[Init] Compositor created successfully!");
+        Compositor {
+            ctx,
+            redraws: RefCell::new(Damage::new()),
+            backend: RefCell::new(backend),
         }
+    }
 
-        // println!("[Init] Compositor created successfully!");
+    fn ctx(&self) -> Rc<Context> {
+        Context::upgrade(&self.ctx)
     }
 
-    /// TODO
-    /// 重绘所有请求的窗口
+    /// 重绘所有请求的区域并输出到显示后端
     pub fn redraw_all(&self) {
         // println!("[Info] Compositor begin redraw_all...");
-        let window_manager = window_manager().unwrap();
-        let server = starry_server().unwrap();
+        let rects = self.redraws.borrow_mut().take();
+        if rects.is_empty() {
+            return;
+        }
+
+        let ctx = self.ctx();
+        let window_manager = &ctx.window_manager;
+        let server = &ctx.server;
         let cursor_rect = window_manager.cursor_rect();
 
         // 对窗口排序
         window_manager.rezbuffer();
+        let order: Vec<usize> = window_manager
+            .zbuffer
+            .borrow()
+            .iter()
+            .map(|entry| entry.0)
+            .collect();
+
+        let background_color = server.config.borrow().background_color;
+        let focused_opt = window_manager.focused_window();
+        let preview_opt = window_manager.resize_preview();
+        let mut theme = server.theme.borrow_mut();
+        let mut windows = window_manager.windows.borrow_mut();
+        let mut backend = self.backend.borrow_mut();
+
+        // 遍历所有显示窗口
+        for display in server.displays.borrow_mut().iter_mut() {
+            for original_rect in rects.iter() {
+                let rect = original_rect.intersection(&display.screen_rect());
+                if rect.is_empty() {
+                    continue;
+                }
 
-        let mut total_redraw_rect_opt: Option<Rect> = None;
-        for original_rect in self.redraws.borrow_mut().drain(..) {
-            // 更新重绘的总矩形区域
-            if !original_rect.is_empty() {
-                total_redraw_rect_opt = match total_redraw_rect_opt {
-                    Some(total_redraw) => Some(total_redraw.container(&original_rect)),
-                    None => Some(original_rect),
+                // 背景没有被完全遮挡时填充背景颜色
+                let (visible, covered) = Self::visible_windows(&order, &windows, &rect);
+                if !covered {
+                    display.rect(&rect, background_color);
                 }
-            }
 
-            // 遍历所有显示窗口
-            for display in server.displays.borrow_mut().iter_mut() {
-                let rect = original_rect.intersection(&display.screen_rect());
-                if !rect.is_empty() {
-                    // TODO: 填充默认颜色
-
-                    // 倒序渲染所有窗口
-                    let zbuffer = window_manager.zbuffer.borrow_mut();
-                    let len = zbuffer.len();
-                    for index in (0..len).rev() {
-                        let entry = zbuffer.get(index).unwrap();
-                        let _id = entry.0;
-                        let index = entry.2;
-                        let mut windows = window_manager.windows.borrow_mut();
-                        if let Some(window) = windows.get_mut(&index) {
-                            // TODO: 渲染窗口标题
-
-                            // 渲染窗体
-                            window.draw(display, &rect);
+                // 从下到上渲染可见的窗口
+                for &id in visible.iter().rev() {
+                    if let Some(window) = windows.get_mut(&id) {
+                        // 渲染窗口标题
+                        window.draw_title(display, &rect, focused_opt == Some(id), &mut theme);
+
+                        // 渲染窗体
+                        window.draw(display, &rect);
+                    }
+                }
+
+                // 渲染缩放预览边框
+                if let Some(preview) = preview_opt {
+                    for edge in preview.outline(RESIZE_OUTLINE) {
+                        let intersect = edge.intersection(&rect);
+                        if !intersect.is_empty() {
+                            display.rect(&intersect, theme.text_highlight_color);
                         }
                     }
                 }
@@ -98,59 +111,121 @@ impl Compositor {
                         .borrow_mut()
                         .get_mut(&window_manager.cursor_i.get())
                     {
-                        display.roi(&cursor_intersect).blend(&cursor.roi(
+                        display.roi(&cursor_intersect).blend(&cursor.image.roi(
                             &cursor_intersect.offset(-cursor_rect.left(), -cursor_rect.top()),
                         ));
                     }
                 }
+
+                // 重绘区域互不重叠，逐个输出不会重复输出同一像素
+                backend.present(display, &rect);
             }
         }
+        backend.flush();
+    }
 
-        // println!("[Info] Compositor calculate total redraw rect done!");
-
-        // TODO
-        let mut fb = self.fb_file.borrow_mut();
-
-        if let Some(total_redraw_rect) = total_redraw_rect_opt {
-            for display in server.displays.borrow_mut().iter_mut() {
-                let display_redraw = total_redraw_rect.intersection(&display.screen_rect());
-                if !display_redraw.is_empty() {
-                    for y in 0..display_redraw.height() {
-                        for x in 0..display_redraw.width() {
-                            let pixel = display.image.get_pixel(
-                                x + display_redraw.left() - display.x,
-                                y + display_redraw.top() - display.y,
-                            );
-                            let offset = (((y + display_redraw.top()) * SCREEN_WIDTH as i32)
-                                + x
-                                + display_redraw.left())
-                                * 4;
-                            fb.seek(SeekFrom::Start(offset as u64))
-                                .expect("Unable to seek framebuffer");
-                            fb.write_all(&pixel.to_bgra_bytes())
-                                .expect("Unable to write framebuffer");
-                        }
-                    }
-                }
+    /// # 函数功能
+    /// 遮挡剔除：求重绘区域中需要渲染的窗口
+    /// 在重绘区域内的部分被上方某个不透明窗口完全遮挡的窗口被跳过，
+    /// 遇到完全覆盖重绘区域的不透明窗口时，其下方的窗口和背景都不需要渲染
+    ///
+    /// ## 参数
+    /// - order: 从上到下排列的窗口id
+    /// - windows: 窗口字典
+    /// - rect: 重绘区域
+    ///
+    /// ## 返回值
+    /// (需要渲染的窗口id，从上到下排列；背景是否被完全遮挡)
+    fn visible_windows(
+        order: &[usize],
+        windows: &BTreeMap<usize, Window>,
+        rect: &Rect,
+    ) -> (Vec<usize>, bool) {
+        let mut visible = Vec::new();
+        let mut occluders: Vec<Rect> = Vec::new();
+        for id in order {
+            let window = match windows.get(id) {
+                Some(window) => window,
+                None => continue,
+            };
+            let region = window.frame_rect().intersection(rect);
+            if region.is_empty() || occluders.iter().any(|o| o.contains_rect(&region)) {
+                continue;
+            }
+
+            visible.push(*id);
+            let opaque = window.opaque_rect().intersection(rect);
+            if opaque == *rect {
+                return (visible, true);
+            }
+            if !opaque.is_empty() {
+                occluders.push(opaque);
             }
         }
+        (visible, false)
     }
 
-    /// 窗口请求重绘
+    /// 显示后端
+    pub fn backend(&self) -> &RefCell<Box<dyn DisplayBackend>> {
+        &self.backend
+    }
+
+    /// 请求重绘矩形区域，在下一次`redraw_all`时重绘
     pub fn request_redraw(&self, rect: Rect) {
         // println!("[Info] Compositor request redraw rect {:?}", rect);
-        let mut push = true;
+        self.redraws.borrow_mut().add(rect);
+    }
+}
 
-        for rect in self.redraws.borrow_mut().iter_mut() {
-            let container = rect.container(&rect);
-            if container.area() <= rect.area() + rect.area() {
-                *rect = container;
-                push = false;
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use crate::base::image::Image;
 
-        if push {
-            self.redraws.borrow_mut().push(rect);
-        }
+    use super::*;
+
+    fn window(x: i32, y: i32, width: i32, height: i32, transparent: bool) -> Window {
+        let mut window = Window::new(x, y, 1, Image::new(width, height));
+        window.barderless = true;
+        window.transparent = transparent;
+        window
+    }
+
+    #[test]
+    fn windows_hidden_by_opaque_windows_are_skipped() {
+        let windows = BTreeMap::from([
+            (0, window(0, 0, 100, 100, false)),
+            (1, window(10, 10, 20, 20, false)),
+            (2, window(0, 0, 50, 50, false)),
+            (3, window(200, 200, 10, 10, false)),
+        ]);
+        // 窗口2遮挡窗口1，窗口3不在重绘区域内
+        let order = [2, 1, 3, 0];
+        let (visible, covered) =
+            Compositor::visible_windows(&order, &windows, &Rect::new(0, 0, 100, 100));
+        assert_eq!(visible, vec![2, 0]);
+        assert!(covered);
+
+        // 重绘区域只在窗口2内时，下方的窗口和背景都被遮挡
+        let (visible, covered) =
+            Compositor::visible_windows(&order, &windows, &Rect::new(5, 5, 10, 10));
+        assert_eq!(visible, vec![2]);
+        assert!(covered);
+    }
+
+    #[test]
+    fn transparent_windows_do_not_occlude() {
+        let windows = BTreeMap::from([
+            (0, window(0, 0, 100, 100, false)),
+            (1, window(0, 0, 100, 100, true)),
+        ]);
+        let (visible, covered) =
+            Compositor::visible_windows(&[1, 0], &windows, &Rect::new(0, 0, 100, 100));
+        assert_eq!(visible, vec![1, 0]);
+        assert!(covered);
+
+        let (visible, covered) =
+            Compositor::visible_windows(&[1], &windows, &Rect::new(0, 0, 100, 100));
+        assert_eq!(visible, vec![1]);
+        assert!(!covered);
     }
 }
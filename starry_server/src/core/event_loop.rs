@@ -0,0 +1,316 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    os::unix::io::RawFd,
+    time::{Duration, Instant},
+};
+
+/// 等待文件描述符就绪的轮询器，封装poll系统调用
+/// 每轮循环先清空，再由各模块登记需要等待的文件描述符
+pub struct Poller {
+    /// 登记的文件描述符
+    fds: Vec<libc::pollfd>,
+}
+
+impl Default for Poller {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Poller {
+    pub fn new() -> Self {
+        Poller { fds: Vec::new() }
+    }
+
+    /// 清空登记的文件描述符
+    pub fn clear(&mut self) {
+        self.fds.clear();
+    }
+
+    /// # 函数功能
+    /// 登记文件描述符
+    ///
+    /// ## 参数
+    /// - fd: 文件描述符
+    /// - writable: 是否同时等待可写(有积压的发送数据时)
+    pub fn add(&mut self, fd: RawFd, writable: bool) {
+        let mut events = libc::POLLIN;
+        if writable {
+            events |= libc::POLLOUT;
+        }
+        self.fds.push(libc::pollfd {
+            fd,
+            events,
+            revents: 0,
+        });
+    }
+
+    /// # 函数功能
+    /// 等待任一文件描述符就绪或超时
+    ///
+    /// ## 参数
+    /// - timeout: 超时时间，为None时一直等待
+    ///
+    /// ## 返回值
+    /// 就绪的文件描述符数量，被信号打断时返回0
+    pub fn wait(&mut self, timeout: Option<Duration>) -> io::Result<usize> {
+        let timeout = match timeout {
+            // 向上取整，避免定时器到期前被提前唤醒后空转
+            Some(timeout) => {
+                let millis = timeout.as_nanos().div_ceil(1_000_000);
+                millis.min(i32::MAX as u128) as libc::c_int
+            }
+            None => -1,
+        };
+
+        let result = unsafe {
+            libc::poll(
+                self.fds.as_mut_ptr(),
+                self.fds.len() as libc::nfds_t,
+                timeout,
+            )
+        };
+        if result < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(0);
+            }
+            return Err(err);
+        }
+        Ok(result as usize)
+    }
+
+    /// 文件描述符是否就绪(可读、可写、挂断或出错)
+    pub fn is_ready(&self, fd: RawFd) -> bool {
+        self.revents(fd) != 0
+    }
+
+    /// 文件描述符就绪的事件(POLLIN、POLLHUP等)，未登记或未就绪时返回0
+    pub fn revents(&self, fd: RawFd) -> libc::c_short {
+        self.fds
+            .iter()
+            .filter(|pollfd| pollfd.fd == fd)
+            .fold(0, |revents, pollfd| revents | pollfd.revents)
+    }
+}
+
+/// 定时器
+struct Timer {
+    /// 下一次到期的时间
+    deadline: Instant,
+    /// 重复间隔，为None时只触发一次
+    interval: Option<Duration>,
+    /// 到期时调用的函数
+    callback: Box<dyn FnMut()>,
+}
+
+/// 定时器集合，用于动画、光标闪烁和定期检查等任务
+/// 主循环等待到最近的定时器到期为止
+pub struct Timers {
+    /// 下一个定时器的id
+    next_id: usize,
+    /// 定时器字典
+    timers: BTreeMap<usize, Timer>,
+}
+
+impl Default for Timers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Timers {
+            next_id: 0,
+            timers: BTreeMap::new(),
+        }
+    }
+
+    /// # 函数功能
+    /// 添加定时器
+    ///
+    /// ## 参数
+    /// - delay: 第一次到期前的延迟
+    /// - interval: 重复间隔，为None时只触发一次
+    /// - callback: 到期时调用的函数
+    ///
+    /// ## 返回值
+    /// 定时器id
+    pub fn add(
+        &mut self,
+        delay: Duration,
+        interval: Option<Duration>,
+        callback: Box<dyn FnMut()>,
+    ) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.timers.insert(
+            id,
+            Timer {
+                deadline: Instant::now() + delay,
+                interval,
+                callback,
+            },
+        );
+        id
+    }
+
+    /// 取消定时器
+    pub fn cancel(&mut self, id: usize) {
+        self.timers.remove(&id);
+    }
+
+    /// 最近的到期时间，没有定时器时返回None
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.timers.values().map(|timer| timer.deadline).min()
+    }
+
+    /// # 函数功能
+    /// 取出已到期的定时器的回调函数
+    /// 重复定时器按间隔推迟到期时间，错过多次时只触发一次
+    ///
+    /// ## 参数
+    /// - now: 当前时间
+    ///
+    /// ## 返回值
+    /// (定时器id，回调函数)数组，按到期时间排序
+    fn take_expired(&mut self, now: Instant) -> Vec<(usize, Box<dyn FnMut()>)> {
+        let mut ids: Vec<(Instant, usize)> = self
+            .timers
+            .iter()
+            .filter(|(_, timer)| timer.deadline <= now)
+            .map(|(&id, timer)| (timer.deadline, id))
+            .collect();
+        ids.sort();
+
+        let mut expired = Vec::new();
+        for (_, id) in ids {
+            let timer = match self.timers.get_mut(&id) {
+                Some(timer) => timer,
+                None => continue,
+            };
+            match timer.interval {
+                Some(interval) => {
+                    timer.deadline = (timer.deadline + interval).max(now);
+                    // 回调执行期间暂时替换为空函数，执行后放回
+                    let callback = std::mem::replace(&mut timer.callback, Box::new(|| {}));
+                    expired.push((id, callback));
+                }
+                None => {
+                    let timer = self.timers.remove(&id).unwrap();
+                    expired.push((id, timer.callback));
+                }
+            }
+        }
+        expired
+    }
+
+    /// 放回重复定时器的回调函数，定时器已被取消时丢弃
+    fn restore(&mut self, id: usize, callback: Box<dyn FnMut()>) {
+        if let Some(timer) = self.timers.get_mut(&id) {
+            if timer.interval.is_some() {
+                timer.callback = callback;
+            }
+        }
+    }
+}
+
+/// # 函数功能
+/// 执行所有到期的定时器
+/// 执行回调时不持有定时器集合的借用，回调中可以添加或取消定时器
+///
+/// ## 参数
+/// - timers: 定时器集合
+pub fn run_timers(timers: &std::cell::RefCell<Timers>) {
+    let expired = timers.borrow_mut().take_expired(Instant::now());
+    for (id, mut callback) in expired {
+        callback();
+        timers.borrow_mut().restore(id, callback);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io::Write, os::unix::io::AsRawFd, os::unix::net::UnixStream, rc::Rc};
+
+    use super::*;
+
+    #[test]
+    fn poller_waits_for_readable_fd() {
+        let (mut a, b) = UnixStream::pair().unwrap();
+        let mut poller = Poller::new();
+        poller.add(b.as_raw_fd(), false);
+        assert_eq!(poller.wait(Some(Duration::ZERO)).unwrap(), 0);
+        assert!(!poller.is_ready(b.as_raw_fd()));
+
+        a.write_all(&[1]).unwrap();
+        poller.clear();
+        poller.add(b.as_raw_fd(), false);
+        assert_eq!(poller.wait(None).unwrap(), 1);
+        assert!(poller.is_ready(b.as_raw_fd()));
+    }
+
+    #[test]
+    fn poller_times_out() {
+        let mut poller = Poller::new();
+        let start = Instant::now();
+        assert_eq!(poller.wait(Some(Duration::from_millis(20))).unwrap(), 0);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[test]
+    fn timers_fire_once_or_repeat() {
+        let timers = RefCell::new(Timers::new());
+        let log = Rc::new(RefCell::new(Vec::new()));
+
+        let once_log = log.clone();
+        timers.borrow_mut().add(
+            Duration::ZERO,
+            None,
+            Box::new(move || once_log.borrow_mut().push("once")),
+        );
+        let repeat_log = log.clone();
+        let repeat = timers.borrow_mut().add(
+            Duration::ZERO,
+            Some(Duration::from_secs(60)),
+            Box::new(move || repeat_log.borrow_mut().push("repeat")),
+        );
+
+        run_timers(&timers);
+        assert_eq!(log.borrow().len(), 2);
+        // 一次性定时器已被移除，重复定时器在一分钟后再次到期
+        run_timers(&timers);
+        assert_eq!(log.borrow().len(), 2);
+        let deadline = timers.borrow().next_deadline().unwrap();
+        assert!(deadline > Instant::now() + Duration::from_secs(59));
+
+        timers.borrow_mut().cancel(repeat);
+        assert!(timers.borrow().next_deadline().is_none());
+    }
+
+    #[test]
+    fn callbacks_may_cancel_their_timer() {
+        let timers = Rc::new(RefCell::new(Timers::new()));
+        let count = Rc::new(RefCell::new(0));
+
+        let id = Rc::new(RefCell::new(None));
+        let (inner_timers, inner_count, inner_id) = (timers.clone(), count.clone(), id.clone());
+        *id.borrow_mut() = Some(timers.borrow_mut().add(
+            Duration::ZERO,
+            Some(Duration::ZERO),
+            Box::new(move || {
+                *inner_count.borrow_mut() += 1;
+                if let Some(id) = *inner_id.borrow() {
+                    inner_timers.borrow_mut().cancel(id);
+                }
+            }),
+        ));
+
+        run_timers(&timers);
+        run_timers(&timers);
+        assert_eq!(*count.borrow(), 1);
+        assert!(timers.borrow().next_deadline().is_none());
+    }
+}
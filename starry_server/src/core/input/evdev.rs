@@ -0,0 +1,801 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io, mem,
+    os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+};
+
+use log::warn;
+use starry_client::base::event::{
+    ButtonEvent, Event, MouseRelativeEvent, MouseUpdateEvent, ScrollEvent,
+};
+
+use super::{keymap::Keymap, scancode::ScancodeDecoder, InputHandler};
+use crate::config::Config;
+
+/// 一条`struct input_event`记录的字节数
+pub const EVENT_SIZE: usize = mem::size_of::<libc::timeval>() + 8;
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_REL: u16 = 0x02;
+const EV_ABS: u16 = 0x03;
+
+const SYN_REPORT: u16 = 0x00;
+const SYN_DROPPED: u16 = 0x03;
+
+const REL_X: u16 = 0x00;
+const REL_Y: u16 = 0x01;
+const REL_HWHEEL: u16 = 0x06;
+const REL_WHEEL: u16 = 0x08;
+
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+
+const BTN_LEFT: u16 = 0x110;
+const BTN_RIGHT: u16 = 0x111;
+const BTN_MIDDLE: u16 = 0x112;
+const BTN_SIDE: u16 = 0x113;
+const BTN_EXTRA: u16 = 0x114;
+const BTN_TOUCH: u16 = 0x14A;
+
+/// 与PS/2第一套扫描码编号相同的键码上限(KEY_F12)
+const KEY_SET1_MAX: u16 = 88;
+
+/// 对应0xE0前缀扩展扫描码的键码(evdev键码，第一套扫描码)
+const EXTENDED_KEYS: [(u16, u8); 14] = [
+    (96, 0x1C),  // KEY_KPENTER
+    (97, 0x1D),  // KEY_RIGHTCTRL
+    (98, 0x35),  // KEY_KPSLASH
+    (100, 0x38), // KEY_RIGHTALT
+    (102, 0x47), // KEY_HOME
+    (103, 0x48), // KEY_UP
+    (104, 0x49), // KEY_PAGEUP
+    (105, 0x4B), // KEY_LEFT
+    (106, 0x4D), // KEY_RIGHT
+    (107, 0x4F), // KEY_END
+    (108, 0x50), // KEY_DOWN
+    (109, 0x51), // KEY_PAGEDOWN
+    (110, 0x52), // KEY_INSERT
+    (111, 0x53), // KEY_DELETE
+];
+
+/// 绝对坐标轴的取值范围
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AbsRange {
+    pub min: i32,
+    pub max: i32,
+}
+
+impl AbsRange {
+    /// # 函数功能
+    /// 将轴上的值线性映射到[0, 1]
+    ///
+    /// ## 参数
+    /// - value: 轴上的值
+    ///
+    /// ## 返回值
+    /// 归一化的坐标，超出范围的值被限制在边界上
+    fn normalize(&self, value: i32) -> f32 {
+        if self.max <= self.min {
+            return 0.0;
+        }
+        let value = value.clamp(self.min, self.max) as f64 - self.min as f64;
+        (value / (self.max as f64 - self.min as f64)) as f32
+    }
+
+    /// 配置中的取值范围，max不大于min时无效
+    fn from_config(range: Option<[i32; 2]>, name: &str) -> Option<AbsRange> {
+        let [min, max] = range?;
+        if max <= min {
+            warn!(
+                "{} [{}, {}] is empty, use the range reported by the device",
+                name, min, max
+            );
+            return None;
+        }
+        Some(AbsRange { min, max })
+    }
+}
+
+impl Default for AbsRange {
+    fn default() -> Self {
+        AbsRange { min: 0, max: 32767 }
+    }
+}
+
+/// 绝对坐标设备(触摸屏、数位板)的校准
+/// 坐标依次经过：按取值范围归一化、交换x/y轴、翻转、校准矩阵，最后映射到屏幕
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calibration {
+    /// x轴的取值范围
+    pub x_range: AbsRange,
+    /// y轴的取值范围
+    pub y_range: AbsRange,
+    /// 是否交换x轴和y轴
+    pub swap_axes: bool,
+    /// 是否翻转x轴
+    pub invert_x: bool,
+    /// 是否翻转y轴
+    pub invert_y: bool,
+    /// 校准矩阵[a, b, c, d, e, f]，作用于归一化坐标：x' = ax + by + c，y' = dx + ey + f
+    pub matrix: [f32; 6],
+}
+
+impl Calibration {
+    /// # 函数功能
+    /// 根据配置创建校准，配置中未指定的取值范围使用设备报告的范围
+    ///
+    /// ## 参数
+    /// - config: 配置信息
+    /// - device_range: 设备报告的x、y轴取值范围
+    pub fn new(config: &Config, device_range: (AbsRange, AbsRange)) -> Self {
+        let matrix = if config.absolute_calibration.iter().all(|v| v.is_finite()) {
+            config.absolute_calibration
+        } else {
+            warn!("absolute_calibration is not finite, use the identity matrix");
+            IDENTITY_MATRIX
+        };
+        Calibration {
+            x_range: AbsRange::from_config(config.absolute_x_range, "absolute_x_range")
+                .unwrap_or(device_range.0),
+            y_range: AbsRange::from_config(config.absolute_y_range, "absolute_y_range")
+                .unwrap_or(device_range.1),
+            swap_axes: config.absolute_swap_axes,
+            invert_x: config.absolute_invert_x,
+            invert_y: config.absolute_invert_y,
+            matrix,
+        }
+    }
+
+    /// # 函数功能
+    /// 将设备坐标映射到屏幕坐标
+    ///
+    /// ## 参数
+    /// - raw: x、y轴上的值
+    /// - screen: 屏幕宽高
+    ///
+    /// ## 返回值
+    /// 屏幕坐标，范围为[0, 宽)和[0, 高)
+    pub fn map(&self, raw: (i32, i32), screen: (i32, i32)) -> (i32, i32) {
+        let (mut x, mut y) = (self.x_range.normalize(raw.0), self.y_range.normalize(raw.1));
+        if self.swap_axes {
+            mem::swap(&mut x, &mut y);
+        }
+        if self.invert_x {
+            x = 1.0 - x;
+        }
+        if self.invert_y {
+            y = 1.0 - y;
+        }
+        let [a, b, c, d, e, f] = self.matrix;
+        let (x, y) = (a * x + b * y + c, d * x + e * y + f);
+
+        let scale = |value: f32, size: i32| {
+            (value.clamp(0.0, 1.0) * (size - 1).max(0) as f32).round() as i32
+        };
+        (scale(x, screen.0), scale(y, screen.1))
+    }
+}
+
+/// 单位校准矩阵
+pub const IDENTITY_MATRIX: [f32; 6] = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0];
+
+/// evdev事件流解码器
+/// 按字节接收`struct input_event`记录，在每个`SYN_REPORT`处输出这一帧累积的鼠标事件
+/// 按键事件转换为第一套扫描码后交给`ScancodeDecoder`，与PS/2键盘共用修饰键和布局处理
+pub struct EvdevDecoder {
+    /// 未凑满一条记录的字节
+    buffer: Vec<u8>,
+    /// 键盘扫描码解码器
+    keyboard: ScancodeDecoder,
+    /// 本帧累积的相对移动
+    dx: i32,
+    dy: i32,
+    /// 本帧累积的水平、垂直滚动量
+    scroll: (i32, i32),
+    /// 当前的按键状态(左，右，中，后退，前进)
+    buttons: [bool; 5],
+    /// 本帧按键状态是否改变
+    buttons_changed: bool,
+    /// 绝对坐标的校准
+    calibration: Calibration,
+    /// 最近的绝对坐标(轴上的值)
+    abs: (i32, i32),
+    /// 本帧绝对坐标是否改变
+    abs_changed: bool,
+    /// 屏幕大小
+    screen: (i32, i32),
+    /// 内核缓冲区溢出后丢弃记录，直到下一个`SYN_REPORT`
+    dropping: bool,
+}
+
+impl EvdevDecoder {
+    /// # 函数功能
+    /// 创建解码器
+    ///
+    /// ## 参数
+    /// - calibration: 绝对坐标的校准
+    /// - screen: 屏幕宽高
+    pub fn new(calibration: Calibration, screen: (i32, i32)) -> Self {
+        EvdevDecoder {
+            buffer: Vec::with_capacity(EVENT_SIZE),
+            keyboard: ScancodeDecoder::new(),
+            dx: 0,
+            dy: 0,
+            scroll: (0, 0),
+            buttons: [false; 5],
+            buttons_changed: false,
+            calibration,
+            abs: (0, 0),
+            abs_changed: false,
+            screen,
+            dropping: false,
+        }
+    }
+
+    /// 设置绝对坐标的校准
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = calibration;
+    }
+
+    /// 设置可切换的键盘布局
+    pub fn set_keymaps(&mut self, keymaps: Vec<Keymap>) {
+        self.keyboard.set_keymaps(keymaps);
+    }
+
+    /// # 函数功能
+    /// 解码一个字节
+    ///
+    /// ## 参数
+    /// - byte: 设备文件中读出的字节
+    ///
+    /// ## 返回值
+    /// 凑满一条记录时返回解码出的事件
+    pub fn feed(&mut self, byte: u8) -> Vec<Event> {
+        self.buffer.push(byte);
+        if self.buffer.len() < EVENT_SIZE {
+            return Vec::new();
+        }
+
+        // 跳过时间戳
+        let record = &self.buffer[mem::size_of::<libc::timeval>()..];
+        let type_ = u16::from_ne_bytes([record[0], record[1]]);
+        let code = u16::from_ne_bytes([record[2], record[3]]);
+        let value = i32::from_ne_bytes([record[4], record[5], record[6], record[7]]);
+        self.buffer.clear();
+
+        self.handle_record(type_, code, value)
+    }
+
+    /// 处理一条记录
+    fn handle_record(&mut self, type_: u16, code: u16, value: i32) -> Vec<Event> {
+        if type_ == EV_SYN {
+            return match code {
+                SYN_REPORT if self.dropping => {
+                    self.dropping = false;
+                    self.discard_frame();
+                    Vec::new()
+                }
+                SYN_REPORT => self.report(),
+                SYN_DROPPED => {
+                    self.dropping = true;
+                    Vec::new()
+                }
+                _ => Vec::new(),
+            };
+        }
+        if self.dropping {
+            return Vec::new();
+        }
+
+        match (type_, code) {
+            // 触摸屏按下和抬起作为左键
+            (EV_KEY, BTN_LEFT..=BTN_EXTRA | BTN_TOUCH) => {
+                let index = match code {
+                    BTN_LEFT | BTN_TOUCH => 0,
+                    BTN_RIGHT => 1,
+                    BTN_MIDDLE => 2,
+                    BTN_SIDE => 3,
+                    _ => 4,
+                };
+                let pressed = value != 0;
+                if self.buttons[index] != pressed {
+                    self.buttons[index] = pressed;
+                    self.buttons_changed = true;
+                }
+                Vec::new()
+            }
+            (EV_KEY, _) => self.handle_key(code, value),
+            (EV_REL, REL_X) => {
+                self.dx += value;
+                Vec::new()
+            }
+            (EV_REL, REL_Y) => {
+                self.dy += value;
+                Vec::new()
+            }
+            (EV_REL, REL_HWHEEL) => {
+                self.scroll.0 += value;
+                Vec::new()
+            }
+            (EV_REL, REL_WHEEL) => {
+                self.scroll.1 += value;
+                Vec::new()
+            }
+            (EV_ABS, ABS_X) => {
+                self.abs.0 = value;
+                self.abs_changed = true;
+                Vec::new()
+            }
+            (EV_ABS, ABS_Y) => {
+                self.abs.1 = value;
+                self.abs_changed = true;
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// 将键盘按键转换为第一套扫描码并解码，value为0表示松开，1表示按下，2表示自动重复
+    fn handle_key(&mut self, code: u16, value: i32) -> Vec<Event> {
+        let (extended, scancode) = match EXTENDED_KEYS.iter().find(|(key, _)| *key == code) {
+            Some(&(_, scancode)) => (true, scancode),
+            None if code > 0 && code <= KEY_SET1_MAX => (false, code as u8),
+            None => return Vec::new(),
+        };
+        let byte = if value == 0 {
+            scancode | 0x80
+        } else {
+            scancode
+        };
+
+        let mut events = Vec::new();
+        if extended {
+            self.keyboard.feed(0xE0);
+        }
+        if let Some(event) = self.keyboard.feed(byte) {
+            events.push(event.to_event());
+        }
+        events
+    }
+
+    /// 输出一帧累积的鼠标事件
+    fn report(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        if self.dx != 0 || self.dy != 0 {
+            // evdev的y轴向下为正，MouseRelativeEvent与PS/2一致，向上为正
+            events.push(
+                MouseRelativeEvent {
+                    dx: self.dx,
+                    dy: -self.dy,
+                }
+                .to_event(),
+            );
+        }
+        // 在按键事件之前输出，触摸按下时指针已位于触摸点
+        if self.abs_changed {
+            let (x, y) = self.calibration.map(self.abs, self.screen);
+            events.push(MouseUpdateEvent { x, y }.to_event());
+        }
+        if self.scroll != (0, 0) {
+            // evdev的滚轮向上、向右为正，与ScrollEvent一致
+            events.push(
+                ScrollEvent {
+                    x: self.scroll.0,
+                    y: self.scroll.1,
+                }
+                .to_event(),
+            );
+        }
+        if self.buttons_changed {
+            let [left, right, middle, back, forward] = self.buttons;
+            events.push(
+                ButtonEvent {
+                    left,
+                    right,
+                    middle,
+                    back,
+                    forward,
+                }
+                .to_event(),
+            );
+        }
+        self.discard_frame();
+        events
+    }
+
+    /// 清除本帧累积的状态
+    fn discard_frame(&mut self) {
+        self.dx = 0;
+        self.dy = 0;
+        self.scroll = (0, 0);
+        self.abs_changed = false;
+        self.buttons_changed = false;
+    }
+}
+
+/// 读取Linux evdev设备(`/dev/input/event*`)的输入处理器
+pub struct EvdevInputHandler {
+    /// 读取的文件
+    file: File,
+    /// 事件流解码器
+    decoder: EvdevDecoder,
+    /// 设备报告的x、y轴取值范围
+    device_range: (AbsRange, AbsRange),
+}
+
+impl EvdevInputHandler {
+    /// # 函数功能
+    /// 以非阻塞方式打开evdev设备
+    ///
+    /// ## 参数
+    /// - path: 设备文件路径
+    /// - config: 配置信息
+    /// - screen: 屏幕大小(宽，高)
+    ///
+    /// ## 返回值
+    /// 输入处理器
+    pub fn new(
+        path: &str,
+        config: &Config,
+        screen: (i32, i32),
+    ) -> io::Result<Box<EvdevInputHandler>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)?;
+
+        let device_range = (
+            Self::abs_range(&file, ABS_X).unwrap_or_default(),
+            Self::abs_range(&file, ABS_Y).unwrap_or_default(),
+        );
+        let mut decoder = EvdevDecoder::new(Calibration::new(config, device_range), screen);
+        decoder.set_keymaps(Keymap::load_all(&config.keyboard_layouts));
+
+        Ok(Box::new(EvdevInputHandler {
+            file,
+            decoder,
+            device_range,
+        }))
+    }
+
+    /// 通过EVIOCGABS查询绝对坐标轴的取值范围，设备没有该轴时返回None
+    fn abs_range(file: &File, axis: u16) -> Option<AbsRange> {
+        const IOC_READ: libc::c_ulong = 2;
+        let size = mem::size_of::<libc::input_absinfo>() as libc::c_ulong;
+        let request = (IOC_READ << 30)
+            | (size << 16)
+            | ((b'E' as libc::c_ulong) << 8)
+            | (0x40 + axis as libc::c_ulong);
+
+        let mut info: libc::input_absinfo = unsafe { mem::zeroed() };
+        let result = unsafe { libc::ioctl(file.as_raw_fd(), request as _, &mut info) };
+        if result < 0 || info.maximum <= info.minimum {
+            return None;
+        }
+        Some(AbsRange {
+            min: info.minimum,
+            max: info.maximum,
+        })
+    }
+
+    /// # 函数功能
+    /// 展开设备路径，以`*`结尾的路径匹配目录中所有以该前缀开头的文件
+    ///
+    /// ## 参数
+    /// - pattern: 设备路径，如`/dev/input/event*`
+    ///
+    /// ## 返回值
+    /// 按名称排序的设备路径
+    pub fn expand_path(pattern: &str) -> Vec<String> {
+        let prefix = match pattern.strip_suffix('*') {
+            Some(prefix) => prefix,
+            None => return vec![pattern.to_string()],
+        };
+        let (dir, name_prefix) = match prefix.rfind('/') {
+            Some(index) => (&prefix[..=index], &prefix[index + 1..]),
+            None => ("./", prefix),
+        };
+
+        let mut paths: Vec<String> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| name.starts_with(name_prefix))
+                .map(|name| format!("{}{}", dir, name))
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        paths.sort();
+        paths
+    }
+}
+
+impl InputHandler for EvdevInputHandler {
+    fn get_listening_file(&mut self) -> &File {
+        &self.file
+    }
+
+    fn set_listening_file(&mut self, file: File) {
+        self.file = file;
+    }
+
+    fn handle(&mut self, packet: u8) -> Vec<Event> {
+        self.decoder.feed(packet)
+    }
+
+    fn reload_config(&mut self, config: &Config) {
+        self.decoder
+            .set_keymaps(Keymap::load_all(&config.keyboard_layouts));
+        self.decoder
+            .set_calibration(Calibration::new(config, self.device_range));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starry_client::base::event::EventOption;
+
+    use super::*;
+
+    /// 按内核的格式编码一串记录，时间戳取递增的值
+    fn record(events: &[(u16, u16, i32)]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (index, &(type_, code, value)) in events.iter().enumerate() {
+            let time = libc::timeval {
+                tv_sec: 1_700_000_000 + index as libc::time_t,
+                tv_usec: 250_000,
+            };
+            let time: [u8; mem::size_of::<libc::timeval>()] = unsafe { mem::transmute(time) };
+            bytes.extend_from_slice(&time);
+            bytes.extend_from_slice(&type_.to_ne_bytes());
+            bytes.extend_from_slice(&code.to_ne_bytes());
+            bytes.extend_from_slice(&value.to_ne_bytes());
+        }
+        bytes
+    }
+
+    const RANGE: (AbsRange, AbsRange) = (
+        AbsRange { min: 0, max: 1000 },
+        AbsRange { min: 0, max: 1000 },
+    );
+
+    fn decode(bytes: &[u8]) -> Vec<EventOption> {
+        decode_with(bytes, &Config::default())
+    }
+
+    fn decode_with(bytes: &[u8], config: &Config) -> Vec<EventOption> {
+        let mut decoder = EvdevDecoder::new(Calibration::new(config, RANGE), (801, 601));
+        bytes
+            .iter()
+            .flat_map(|&byte| decoder.feed(byte))
+            .map(Event::to_option)
+            .collect()
+    }
+
+    #[test]
+    fn relative_motion_is_reported_per_frame() {
+        let bytes = record(&[
+            (EV_REL, REL_X, 3),
+            (EV_REL, REL_Y, 2),
+            (EV_REL, REL_X, 4),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_REL, REL_Y, -5),
+            (EV_SYN, SYN_REPORT, 0),
+        ]);
+        let events = decode(&bytes);
+        assert_eq!(events.len(), 2);
+        match (events[0], events[1]) {
+            (EventOption::MouseRelative(first), EventOption::MouseRelative(second)) => {
+                assert_eq!((first.dx, first.dy), (7, -2));
+                assert_eq!((second.dx, second.dy), (0, 5));
+            }
+            other => panic!("unexpected events {:?}", other),
+        }
+    }
+
+    #[test]
+    fn wheel_and_side_buttons() {
+        let bytes = record(&[
+            (EV_REL, REL_WHEEL, 1),
+            (EV_REL, REL_WHEEL, 1),
+            (EV_REL, REL_HWHEEL, -1),
+            (EV_KEY, BTN_SIDE, 1),
+            (EV_SYN, SYN_REPORT, 0),
+        ]);
+        let events = decode(&bytes);
+        assert_eq!(events.len(), 2);
+        match (events[0], events[1]) {
+            (EventOption::Scroll(scroll), EventOption::Button(button)) => {
+                assert_eq!((scroll.x, scroll.y), (-1, 2));
+                assert!(button.back && !button.forward && !button.left);
+            }
+            other => panic!("unexpected events {:?}", other),
+        }
+    }
+
+    #[test]
+    fn buttons_are_reported_on_change() {
+        let bytes = record(&[
+            (EV_KEY, BTN_LEFT, 1),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_KEY, BTN_LEFT, 1),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_KEY, BTN_RIGHT, 1),
+            (EV_KEY, BTN_LEFT, 0),
+            (EV_SYN, SYN_REPORT, 0),
+        ]);
+        let buttons: Vec<(bool, bool, bool)> = decode(&bytes)
+            .into_iter()
+            .map(|event| match event {
+                EventOption::Button(event) => (event.left, event.right, event.middle),
+                other => panic!("unexpected event {:?}", other),
+            })
+            .collect();
+        assert_eq!(buttons, vec![(true, false, false), (false, true, false)]);
+    }
+
+    #[test]
+    fn keys_go_through_scancode_decoder() {
+        // Shift+A、方向键上、松开
+        let bytes = record(&[
+            (EV_KEY, 42, 1),
+            (EV_KEY, 30, 1),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_KEY, 30, 2),
+            (EV_KEY, 30, 0),
+            (EV_KEY, 42, 0),
+            (EV_KEY, 103, 1),
+            (EV_KEY, 103, 0),
+            (EV_SYN, SYN_REPORT, 0),
+        ]);
+        let keys: Vec<(char, u8, bool)> = decode(&bytes)
+            .into_iter()
+            .map(|event| match event {
+                EventOption::Key(event) => (event.character, event.scancode, event.pressed),
+                other => panic!("unexpected event {:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                ('\0', 0x2A, true),
+                ('A', 0x1E, true),
+                ('A', 0x1E, true),
+                ('A', 0x1E, false),
+                ('\0', 0x2A, false),
+                ('\0', 0xC8, true),
+                ('\0', 0xC8, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn absolute_position_is_scaled_to_screen() {
+        let bytes = record(&[
+            (EV_ABS, ABS_X, 500),
+            (EV_ABS, ABS_Y, 1000),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_ABS, ABS_X, 2000),
+            (EV_SYN, SYN_REPORT, 0),
+        ]);
+        let positions: Vec<(i32, i32)> = decode(&bytes)
+            .into_iter()
+            .map(|event| match event {
+                EventOption::MouseUpdate(event) => (event.x, event.y),
+                other => panic!("unexpected event {:?}", other),
+            })
+            .collect();
+        assert_eq!(positions, vec![(400, 600), (800, 600)]);
+    }
+
+    #[test]
+    fn calibration_swaps_inverts_and_transforms() {
+        let config = Config {
+            absolute_swap_axes: true,
+            absolute_invert_x: true,
+            ..Config::default()
+        };
+        let calibration = Calibration::new(&config, RANGE);
+        // 交换后x取原来的y(250)，再翻转为750
+        assert_eq!(calibration.map((500, 250), (801, 601)), (600, 300));
+
+        // 设备只使用了中间一半的范围：x' = 2x - 0.5
+        let config = Config {
+            absolute_calibration: [2.0, 0.0, -0.5, 0.0, 1.0, 0.0],
+            absolute_y_range: Some([100, 200]),
+            ..Config::default()
+        };
+        let calibration = Calibration::new(&config, RANGE);
+        assert_eq!(calibration.map((250, 150), (801, 601)), (0, 300));
+        assert_eq!(calibration.map((600, 300), (801, 601)), (560, 600));
+        assert_eq!(calibration.map((900, 0), (801, 601)), (800, 0));
+    }
+
+    #[test]
+    fn invalid_calibration_falls_back() {
+        let config = Config {
+            absolute_x_range: Some([10, 10]),
+            absolute_calibration: [f32::NAN; 6],
+            ..Config::default()
+        };
+        let calibration = Calibration::new(&config, RANGE);
+        assert_eq!(calibration.x_range, RANGE.0);
+        assert_eq!(calibration.matrix, IDENTITY_MATRIX);
+    }
+
+    #[test]
+    fn touch_is_left_button_at_touch_point() {
+        let bytes = record(&[
+            (EV_ABS, ABS_X, 1000),
+            (EV_ABS, ABS_Y, 0),
+            (EV_KEY, BTN_TOUCH, 1),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_KEY, BTN_TOUCH, 0),
+            (EV_SYN, SYN_REPORT, 0),
+        ]);
+        let config = Config {
+            absolute_invert_y: true,
+            ..Config::default()
+        };
+        let events = decode_with(&bytes, &config);
+        match events[..] {
+            [EventOption::MouseUpdate(position), EventOption::Button(down), EventOption::Button(up)] =>
+            {
+                assert_eq!((position.x, position.y), (800, 600));
+                assert!(down.left && !up.left);
+            }
+            ref other => panic!("unexpected events {:?}", other),
+        }
+    }
+
+    #[test]
+    fn dropped_frames_are_discarded() {
+        let bytes = record(&[
+            (EV_REL, REL_X, 1),
+            (EV_SYN, SYN_DROPPED, 0),
+            (EV_REL, REL_X, 10),
+            (EV_SYN, SYN_REPORT, 0),
+            (EV_REL, REL_X, 2),
+            (EV_SYN, SYN_REPORT, 0),
+        ]);
+        let events = decode(&bytes);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], EventOption::MouseRelative(event) if event.dx == 2));
+    }
+
+    #[test]
+    fn records_may_arrive_in_pieces() {
+        let bytes = record(&[(EV_REL, REL_X, -1), (EV_SYN, SYN_REPORT, 0)]);
+        let mut decoder = EvdevDecoder::new(Calibration::new(&Config::default(), RANGE), (1, 1));
+        let mut events = Vec::new();
+        for chunk in bytes.chunks(5) {
+            for &byte in chunk {
+                events.extend(decoder.feed(byte));
+            }
+        }
+        assert_eq!(events.len(), 1);
+    }
+
+    #[test]
+    fn expand_path_matches_prefix() {
+        let dir = std::env::temp_dir().join(format!("starry-evdev-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["event1", "event0", "mice"] {
+            File::create(dir.join(name)).unwrap();
+        }
+
+        let pattern = format!("{}/event*", dir.display());
+        let paths = EvdevInputHandler::expand_path(&pattern);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            paths,
+            vec![
+                format!("{}/event0", dir.display()),
+                format!("{}/event1", dir.display())
+            ]
+        );
+        assert_eq!(EvdevInputHandler::expand_path("/dev/x"), vec!["/dev/x"]);
+    }
+}
@@ -1,10 +1,15 @@
-use super::InputHandler;
+use super::{keymap::Keymap, poll_file, scancode::ScancodeDecoder, InputHandler};
+use crate::config::Config;
 use bitflags::bitflags;
-use starry_client::base::event::{ButtonEvent, Event, MouseRelativeEvent};
-use std::{fs::File, io::Read};
-
-const MOUSE_DEVICE_PATH: &str = "/dev/char/psmouse";
-const KBD_DEVICE_PATH: &str = "/dev/char/tty0";
+use log::{error, info, warn};
+use starry_client::base::event::{ButtonEvent, Event, MouseRelativeEvent, ScrollEvent};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    mem,
+    os::unix::fs::OpenOptionsExt,
+    time::{Duration, Instant},
+};
 
 bitflags! {
     /// 鼠标状态掩码
@@ -36,40 +41,312 @@ bitflags! {
     }
 }
 
-#[allow(dead_code)]
+/// PS/2鼠标命令：设置采样率
+const CMD_SET_SAMPLE_RATE: u8 = 0xF3;
+/// PS/2鼠标命令：读取设备ID
+const CMD_GET_DEVICE_ID: u8 = 0xF2;
+/// PS/2鼠标命令：开启数据报告
+const CMD_ENABLE_REPORTING: u8 = 0xF4;
+/// PS/2鼠标命令：关闭数据报告
+const CMD_DISABLE_REPORTING: u8 = 0xF5;
+/// 鼠标对命令的应答
+const MOUSE_ACK: u8 = 0xFA;
+/// 协商的超时时间，鼠标不应答时使用标准协议
+const NEGOTIATION_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// PS/2鼠标协议
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MouseProtocol {
+    /// 标准协议，3字节数据包
+    Standard,
+    /// IntelliMouse协议，4字节数据包，第4字节为滚轮
+    IntelliMouse,
+    /// IntelliMouse Explorer协议，4字节数据包，第4字节低4位为滚轮，bit4、bit5为第4、5键
+    Explorer,
+}
+
+impl MouseProtocol {
+    /// 数据包长度
+    fn packet_size(self) -> usize {
+        match self {
+            MouseProtocol::Standard => 3,
+            MouseProtocol::IntelliMouse | MouseProtocol::Explorer => 4,
+        }
+    }
+}
+
+/// 协议协商的步骤
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Negotiation {
+    /// 已发送IntelliMouse的采样率序列，等待应答和设备ID
+    IntelliMouse,
+    /// 已发送Explorer的采样率序列，等待应答和设备ID
+    Explorer,
+    /// 已发送开启数据报告命令，等待应答
+    Enabling,
+}
+
+/// PS/2鼠标数据流解码器
+/// 负责协议协商：依次用采样率序列200,100,80和200,200,80"敲门"，根据设备ID(3或4)选择协议
+pub struct Ps2MouseDecoder {
+    /// 当前协议
+    protocol: MouseProtocol,
+    /// 正在进行的协商步骤
+    negotiation: Option<Negotiation>,
+    /// 当前协商步骤还需等待的应答数
+    acks: usize,
+    /// 待发送给鼠标的命令
+    commands: Vec<u8>,
+    /// 当前数据包
+    packet: [u8; 4],
+    /// 当前数据包已接收的字节数
+    packet_index: usize,
+}
+
+impl Default for Ps2MouseDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ps2MouseDecoder {
+    /// 创建使用标准协议的解码器
+    pub fn new() -> Self {
+        Ps2MouseDecoder {
+            protocol: MouseProtocol::Standard,
+            negotiation: None,
+            acks: 0,
+            commands: Vec::new(),
+            packet: [0; 4],
+            packet_index: 0,
+        }
+    }
+
+    /// 当前协议
+    pub fn protocol(&self) -> MouseProtocol {
+        self.protocol
+    }
+
+    /// 是否正在协商协议
+    pub fn negotiating(&self) -> bool {
+        self.negotiation.is_some()
+    }
+
+    /// 开始协商协议，需要发送的命令通过`take_commands`取出
+    pub fn start_negotiation(&mut self) {
+        self.send(&[CMD_DISABLE_REPORTING]);
+        self.send_knock(&[200, 100, 80], Negotiation::IntelliMouse);
+    }
+
+    /// 放弃协商，使用已确定的协议并开启数据报告
+    pub fn abort_negotiation(&mut self) {
+        if self.negotiation.take().is_some() {
+            self.acks = 0;
+            self.commands.push(CMD_ENABLE_REPORTING);
+        }
+    }
+
+    /// 取出待发送给鼠标的命令
+    pub fn take_commands(&mut self) -> Vec<u8> {
+        mem::take(&mut self.commands)
+    }
+
+    /// 发送命令，每个字节(包括参数)都会得到一个应答
+    fn send(&mut self, bytes: &[u8]) {
+        self.commands.extend_from_slice(bytes);
+        self.acks += bytes.len();
+    }
+
+    /// 发送采样率序列和读取设备ID命令
+    fn send_knock(&mut self, rates: &[u8], step: Negotiation) {
+        for &rate in rates {
+            self.send(&[CMD_SET_SAMPLE_RATE, rate]);
+        }
+        self.send(&[CMD_GET_DEVICE_ID]);
+        self.negotiation = Some(step);
+    }
+
+    /// # 函数功能
+    /// 解码一个字节
+    ///
+    /// ## 参数
+    /// - byte: 鼠标发来的字节
+    ///
+    /// ## 返回值
+    /// 凑满一个数据包时返回按键、移动和滚轮事件
+    pub fn feed(&mut self, byte: u8) -> Vec<Event> {
+        if let Some(step) = self.negotiation {
+            if self.acks > 0 {
+                if byte == MOUSE_ACK {
+                    self.acks -= 1;
+                    if self.acks == 0 && step == Negotiation::Enabling {
+                        self.negotiation = None;
+                    }
+                    return Vec::new();
+                }
+                // 鼠标不支持协商，按数据处理
+                self.negotiation = None;
+                self.acks = 0;
+            } else {
+                self.handle_device_id(step, byte);
+                return Vec::new();
+            }
+        }
+
+        self.feed_packet(byte)
+    }
+
+    /// 根据设备ID确定协议并进行下一步协商
+    fn handle_device_id(&mut self, step: Negotiation, id: u8) {
+        match (step, id) {
+            (Negotiation::IntelliMouse, 3) => {
+                self.protocol = MouseProtocol::IntelliMouse;
+                self.send_knock(&[200, 200, 80], Negotiation::Explorer);
+            }
+            (Negotiation::Explorer, 4) => {
+                self.protocol = MouseProtocol::Explorer;
+                self.enable_reporting();
+            }
+            _ => self.enable_reporting(),
+        }
+    }
+
+    /// 协商结束，开启数据报告
+    fn enable_reporting(&mut self) {
+        self.send(&[CMD_ENABLE_REPORTING]);
+        self.negotiation = Some(Negotiation::Enabling);
+    }
+
+    /// 接收数据包的一个字节
+    fn feed_packet(&mut self, byte: u8) -> Vec<Event> {
+        // 首字节的bit3总为1，不满足时丢弃该字节以重新同步
+        if self.packet_index == 0
+            && !MouseFlags::from_bits_truncate(byte).contains(MouseFlags::ALWAYS_ONE)
+        {
+            return Vec::new();
+        }
+
+        self.packet[self.packet_index] = byte;
+        self.packet_index += 1;
+        if self.packet_index < self.protocol.packet_size() {
+            return Vec::new();
+        }
+        self.packet_index = 0;
+        self.decode_packet()
+    }
+
+    /// 解码完整的数据包
+    fn decode_packet(&self) -> Vec<Event> {
+        /// 根据符号位和溢出位求位移
+        fn delta(value: u8, negative: bool, overflow: bool) -> i32 {
+            if overflow {
+                0
+            } else if negative {
+                value as i32 - 0x100
+            } else {
+                value as i32
+            }
+        }
+
+        let flags = MouseFlags::from_bits_truncate(self.packet[0]);
+        let dx = delta(
+            self.packet[1],
+            flags.contains(MouseFlags::X_SIGN),
+            flags.contains(MouseFlags::X_OVERFLOW),
+        );
+        let dy = delta(
+            self.packet[2],
+            flags.contains(MouseFlags::Y_SIGN),
+            flags.contains(MouseFlags::Y_OVERFLOW),
+        );
+
+        let mut buttons = ButtonEvent::new(self.packet[0]);
+        // 滚轮向用户方向滚动时为正
+        let dz = match self.protocol {
+            MouseProtocol::Standard => 0,
+            MouseProtocol::IntelliMouse => self.packet[3] as i8 as i32,
+            MouseProtocol::Explorer => {
+                buttons.back = self.packet[3] & (1 << 4) != 0;
+                buttons.forward = self.packet[3] & (1 << 5) != 0;
+                // 低4位为补码表示的滚动量
+                ((self.packet[3] << 4) as i8 >> 4) as i32
+            }
+        };
+
+        let mut events = vec![buttons.to_event()];
+        if dx != 0 || dy != 0 {
+            events.push(MouseRelativeEvent { dx, dy }.to_event());
+        }
+        if dz != 0 {
+            events.push(ScrollEvent { x: 0, y: -dz }.to_event());
+        }
+        events
+    }
+}
+
+/// PS/2鼠标输入处理器
 pub struct MouseInputHandler {
     /// 读取的文件
     file: File,
-    /// 当前数据包序号
-    packet_index: u8,
-    /// 鼠标状态
-    flags: MouseFlags,
-    /// x轴相对移动
-    dx: i16,
-    /// y轴相对移动
-    dy: i16,
-    /// 移动系数/灵敏度
-    scale: u8,
+    /// 数据流解码器
+    decoder: Ps2MouseDecoder,
+    /// 协商的截止时间
+    deadline: Option<Instant>,
 }
 
 impl MouseInputHandler {
-    pub fn new() -> Box<MouseInputHandler> {
-        let file = File::open(MOUSE_DEVICE_PATH).expect("Fail to open mouse device");
+    /// # 函数功能
+    /// 以非阻塞方式打开鼠标设备，可写时开始协商协议
+    ///
+    /// ## 参数
+    /// - path: 设备文件路径
+    ///
+    /// ## 返回值
+    /// 输入处理器
+    pub fn new(path: &str) -> io::Result<Box<MouseInputHandler>> {
+        // 协商协议需要向鼠标写入命令，无法写入时只读打开并使用标准协议
+        let open = |write: bool| {
+            OpenOptions::new()
+                .read(true)
+                .write(write)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(path)
+        };
+        let (file, writable) = match open(true) {
+            Ok(file) => (file, true),
+            Err(_) => (open(false)?, false),
+        };
         // println!("[Init] Mouse_Input_Handler created successfully!");
-        Box::new(MouseInputHandler {
-            flags: MouseFlags::empty(),
-            packet_index: 0,
-            file: file,
-            dx: 0,
-            dy: 0,
-            scale: 1,
-        })
+        let mut handler = Box::new(MouseInputHandler {
+            file,
+            decoder: Ps2MouseDecoder::new(),
+            deadline: None,
+        });
+        if writable {
+            handler.decoder.start_negotiation();
+            handler.deadline = Some(Instant::now() + NEGOTIATION_TIMEOUT);
+            handler.write_commands();
+        }
+        Ok(handler)
+    }
+
+    /// 将解码器产生的命令写入鼠标
+    fn write_commands(&mut self) {
+        let commands = self.decoder.take_commands();
+        if commands.is_empty() {
+            return;
+        }
+        if let Err(err) = self.file.write_all(&commands) {
+            error!("Failed to send commands to mouse: {}", err);
+            self.decoder.abort_negotiation();
+            self.decoder.take_commands();
+        }
     }
 }
 
 impl InputHandler for MouseInputHandler {
     fn get_listening_file(&mut self) -> &File {
-        self.file.by_ref()
+        Read::by_ref(&mut self.file)
     }
 
     fn set_listening_file(&mut self, file: File) {
@@ -78,67 +355,61 @@ impl InputHandler for MouseInputHandler {
 
     fn handle(&mut self, packet: u8) -> Vec<Event> {
         // println!("[Info] Mouse_Input_Handler handle packet {:?}", packet);
-        /// 求补码
-        fn sign_extend(value: u8) -> i16 {
-            ((value as u16) | 0xFF00) as i16
-        }
-
-        let mut events: Vec<Event> = Vec::new();
-        match self.packet_index {
-            0 => {
-                let flags = MouseFlags::from_bits_truncate(packet);
-                if flags.contains(MouseFlags::ALWAYS_ONE) {
-                    self.flags = flags;
-                    events.push(ButtonEvent::new(packet).to_event());
-                }
-            }
-            1 => {
-                // 计算dx
-                if self.flags.contains(MouseFlags::X_OVERFLOW) {
-                    self.dx = 0;
-                } else if self.flags.contains(MouseFlags::X_SIGN) {
-                    self.dx = sign_extend(packet);
-                } else {
-                    self.dx = packet as i16;
-                }
-            }
-            2 => {
-                // 计算dy
-                if self.flags.contains(MouseFlags::Y_OVERFLOW) {
-                    self.dy = 0;
-                } else if self.flags.contains(MouseFlags::Y_SIGN) {
-                    self.dy = sign_extend(packet);
-                } else {
-                    self.dy = packet as i16;
-                }
+        let negotiating = self.decoder.negotiating();
+        let events = self.decoder.feed(packet);
+        self.write_commands();
+        if negotiating && !self.decoder.negotiating() {
+            info!("Mouse uses {:?} protocol", self.decoder.protocol());
+        }
+        events
+    }
 
-                // 传入移动事件
-                events.push(
-                    MouseRelativeEvent {
-                        dx: self.dx as i32 * self.scale as i32,
-                        dy: self.dy as i32 * self.scale as i32,
-                    }
-                    .to_event(),
-                );
+    fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    fn polling(&mut self, ready: bool) -> io::Result<Vec<Event>> {
+        if let Some(deadline) = self.deadline {
+            if !self.decoder.negotiating() {
+                self.deadline = None;
+            } else if Instant::now() > deadline {
+                warn!("Mouse did not answer protocol negotiation");
+                self.deadline = None;
+                self.decoder.abort_negotiation();
+                self.write_commands();
             }
-            _ => unreachable!(),
         }
-        self.packet_index = (self.packet_index + 1) % 3;
-        return events;
+        poll_file(self, ready)
     }
 }
 
-// TODO
+/// 键盘输入处理器，读取PS/2键盘的第一套扫描码
 pub struct KeyboardInputHandler {
     /// 读取的文件
     file: File,
+    /// 扫描码解码器
+    decoder: ScancodeDecoder,
 }
 
 impl KeyboardInputHandler {
-    pub fn new() -> Box<KeyboardInputHandler> {
-        let file = File::open(KBD_DEVICE_PATH).expect("Fail to open mouse device");
+    /// # 函数功能
+    /// 以非阻塞方式打开键盘设备
+    ///
+    /// ## 参数
+    /// - path: 设备文件路径
+    /// - config: 配置信息
+    ///
+    /// ## 返回值
+    /// 输入处理器
+    pub fn new(path: &str, config: &Config) -> io::Result<Box<KeyboardInputHandler>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(path)?;
         // println!("[Init] Keyboard_Input_Handler created successfully!");
-        Box::new(KeyboardInputHandler { file: file })
+        let mut decoder = ScancodeDecoder::new();
+        decoder.set_keymaps(Keymap::load_all(&config.keyboard_layouts));
+        Ok(Box::new(KeyboardInputHandler { file, decoder }))
     }
 }
 
@@ -151,7 +422,150 @@ impl InputHandler for KeyboardInputHandler {
         self.file = file;
     }
 
-    fn handle(&mut self, _packet: u8) -> Vec<Event> {
-        Vec::new()
+    fn handle(&mut self, packet: u8) -> Vec<Event> {
+        self.decoder
+            .feed(packet)
+            .map(|event| event.to_event())
+            .into_iter()
+            .collect()
+    }
+
+    fn reload_config(&mut self, config: &Config) {
+        self.decoder
+            .set_keymaps(Keymap::load_all(&config.keyboard_layouts));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use starry_client::base::event::EventOption;
+
+    use super::*;
+
+    fn feed(decoder: &mut Ps2MouseDecoder, bytes: &[u8]) -> Vec<EventOption> {
+        bytes
+            .iter()
+            .flat_map(|&byte| decoder.feed(byte))
+            .map(Event::to_option)
+            .collect()
+    }
+
+    /// 按照鼠标的应答回放协商过程
+    fn negotiate(decoder: &mut Ps2MouseDecoder, ids: &[u8]) {
+        decoder.start_negotiation();
+        for &id in ids {
+            let acks = decoder.take_commands().len();
+            assert!(feed(decoder, &vec![MOUSE_ACK; acks]).is_empty());
+            assert!(feed(decoder, &[id]).is_empty());
+        }
+        assert_eq!(decoder.take_commands(), vec![CMD_ENABLE_REPORTING]);
+        assert!(feed(decoder, &[MOUSE_ACK]).is_empty());
+        assert!(!decoder.negotiating());
+    }
+
+    #[test]
+    fn negotiates_explorer() {
+        let mut decoder = Ps2MouseDecoder::new();
+        decoder.start_negotiation();
+        assert_eq!(
+            decoder.take_commands(),
+            vec![0xF5, 0xF3, 200, 0xF3, 100, 0xF3, 80, 0xF2]
+        );
+        feed(&mut decoder, &[MOUSE_ACK; 8]);
+        feed(&mut decoder, &[3]);
+        assert_eq!(decoder.protocol(), MouseProtocol::IntelliMouse);
+        assert_eq!(
+            decoder.take_commands(),
+            vec![0xF3, 200, 0xF3, 200, 0xF3, 80, 0xF2]
+        );
+        feed(&mut decoder, &[MOUSE_ACK; 7]);
+        feed(&mut decoder, &[4]);
+        assert_eq!(decoder.protocol(), MouseProtocol::Explorer);
+        assert_eq!(decoder.take_commands(), vec![CMD_ENABLE_REPORTING]);
+        feed(&mut decoder, &[MOUSE_ACK]);
+        assert!(!decoder.negotiating());
+    }
+
+    #[test]
+    fn plain_mouse_keeps_standard_protocol() {
+        let mut decoder = Ps2MouseDecoder::new();
+        negotiate(&mut decoder, &[0]);
+        assert_eq!(decoder.protocol(), MouseProtocol::Standard);
+
+        let events = feed(&mut decoder, &[0b0010_1001, 5, 0xFE]);
+        match events[..] {
+            [EventOption::Button(button), EventOption::MouseRelative(motion)] => {
+                assert!(button.left && !button.right);
+                assert_eq!((motion.dx, motion.dy), (5, -2));
+            }
+            ref other => panic!("unexpected events {:?}", other),
+        }
+    }
+
+    #[test]
+    fn intellimouse_wheel() {
+        let mut decoder = Ps2MouseDecoder::new();
+        negotiate(&mut decoder, &[3, 3]);
+        assert_eq!(decoder.protocol(), MouseProtocol::IntelliMouse);
+
+        // 滚轮向用户方向滚动一格，即向下
+        let events = feed(&mut decoder, &[0b0000_1000, 0, 0, 0x01]);
+        match events[..] {
+            [EventOption::Button(_), EventOption::Scroll(scroll)] => {
+                assert_eq!((scroll.x, scroll.y), (0, -1))
+            }
+            ref other => panic!("unexpected events {:?}", other),
+        }
+        let events = feed(&mut decoder, &[0b0000_1000, 0, 0, 0xFE]);
+        assert!(matches!(
+            events[..],
+            [_, EventOption::Scroll(ScrollEvent { y: 2, .. })]
+        ));
+    }
+
+    #[test]
+    fn explorer_wheel_and_buttons() {
+        let mut decoder = Ps2MouseDecoder::new();
+        negotiate(&mut decoder, &[3, 4]);
+
+        let events = feed(&mut decoder, &[0b0000_1000, 0, 0, 0b0010_1111]);
+        match events[..] {
+            [EventOption::Button(button), EventOption::Scroll(scroll)] => {
+                assert!(button.forward && !button.back);
+                assert_eq!(scroll.y, 1);
+            }
+            ref other => panic!("unexpected events {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unanswered_negotiation_falls_back() {
+        let mut decoder = Ps2MouseDecoder::new();
+        decoder.start_negotiation();
+        decoder.take_commands();
+        decoder.abort_negotiation();
+        assert_eq!(decoder.take_commands(), vec![CMD_ENABLE_REPORTING]);
+        assert!(!decoder.negotiating());
+
+        // 不以应答开头的字节按数据包处理
+        let mut decoder = Ps2MouseDecoder::new();
+        decoder.start_negotiation();
+        let events = feed(&mut decoder, &[0b0000_1010, 1, 1]);
+        assert!(!decoder.negotiating());
+        assert!(matches!(
+            events[0],
+            EventOption::Button(ButtonEvent { right: true, .. })
+        ));
+    }
+
+    #[test]
+    fn resynchronizes_on_invalid_first_byte() {
+        let mut decoder = Ps2MouseDecoder::new();
+        let events = feed(&mut decoder, &[0x00, 0x05, 0b0000_1000, 1, 0]);
+        assert_eq!(events.len(), 2);
+        assert!(matches!(
+            events[1],
+            EventOption::MouseRelative(MouseRelativeEvent { dx: 1, dy: 0 })
+        ));
     }
 }
@@ -0,0 +1,261 @@
+use std::{collections::BTreeMap, fmt, fs};
+
+use log::error;
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer,
+};
+use serde_derive::Deserialize;
+
+use crate::config::ConfigError;
+
+use super::scancode::Modifiers;
+
+/// 内置的键盘布局(名称，文件内容)
+const BUILTIN_KEYMAPS: [(&str, &str); 4] = [
+    ("us", include_str!("../../keymaps/us.toml")),
+    ("de", include_str!("../../keymaps/de.toml")),
+    ("fr", include_str!("../../keymaps/fr.toml")),
+    ("dvorak", include_str!("../../keymaps/dvorak.toml")),
+];
+
+/// 键盘布局：扫描码到各修饰层级字符的映射
+/// 层级依次为普通、Shift、AltGr、Shift+AltGr
+/// 文件中省略的Shift层级与普通层级相同，省略的AltGr层级没有字符，省略的Shift+AltGr层级与AltGr层级相同
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    /// 布局名称
+    pub name: String,
+    /// 扫描码对应的各层级字符
+    keys: BTreeMap<u8, [char; 4]>,
+}
+
+/// 键盘布局文件
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeymapFile {
+    name: String,
+    keys: BTreeMap<Scancode, Levels>,
+}
+
+/// 布局文件中的扫描码，写作十六进制("0x1E")或十进制("30")
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Scancode(u8);
+
+impl<'de> Deserialize<'de> for Scancode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(ScancodeVisitor)
+    }
+}
+
+struct ScancodeVisitor;
+
+impl<'de> Visitor<'de> for ScancodeVisitor {
+    type Value = Scancode;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a scancode such as 0x1E")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Scancode, E> {
+        let result = match value.strip_prefix("0x") {
+            Some(hex) => u8::from_str_radix(hex, 16),
+            None => value.parse(),
+        };
+        result
+            .map(Scancode)
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+}
+
+/// 一个按键各层级的字符
+#[derive(Deserialize)]
+#[serde(try_from = "Vec<String>")]
+struct Levels([char; 4]);
+
+impl TryFrom<Vec<String>> for Levels {
+    type Error = String;
+
+    fn try_from(strings: Vec<String>) -> Result<Self, String> {
+        if strings.is_empty() || strings.len() > 4 {
+            return Err(format!(
+                "expected 1 to 4 characters, found {}",
+                strings.len()
+            ));
+        }
+
+        let mut chars = Vec::new();
+        for string in strings.iter() {
+            let mut iter = string.chars();
+            match (iter.next(), iter.next()) {
+                (Some(c), None) => chars.push(c),
+                _ => return Err(format!("expected a single character, found {:?}", string)),
+            }
+        }
+
+        let normal = chars[0];
+        let shift = chars.get(1).copied().unwrap_or(normal);
+        let altgr = chars.get(2).copied().unwrap_or('\0');
+        let shift_altgr = chars.get(3).copied().unwrap_or(altgr);
+        Ok(Levels([normal, shift, altgr, shift_altgr]))
+    }
+}
+
+impl Keymap {
+    /// # 函数功能
+    /// 解析键盘布局字符串
+    ///
+    /// ## 参数
+    /// - keymap: 布局内容
+    /// - path: 布局文件路径，用于错误信息
+    ///
+    /// ## 返回值
+    /// 解析成功时返回布局，否则返回带行号的错误
+    pub fn from_string(keymap: &str, path: &str) -> Result<Keymap, ConfigError> {
+        let file: KeymapFile =
+            toml::from_str(keymap).map_err(|err| ConfigError::from_toml(err, keymap, path))?;
+        Ok(Keymap {
+            name: file.name,
+            keys: file
+                .keys
+                .into_iter()
+                .map(|(scancode, levels)| (scancode.0, levels.0))
+                .collect(),
+        })
+    }
+
+    /// # 函数功能
+    /// 加载键盘布局
+    ///
+    /// ## 参数
+    /// - spec: 内置布局的名称(us、de、fr、dvorak)或布局文件路径
+    ///
+    /// ## 返回值
+    /// 键盘布局
+    pub fn load(spec: &str) -> Result<Keymap, ConfigError> {
+        if let Some((_, keymap)) = BUILTIN_KEYMAPS.iter().find(|(name, _)| *name == spec) {
+            return Self::from_string(keymap, spec);
+        }
+
+        let keymap = fs::read_to_string(spec).map_err(|err| ConfigError::Io {
+            path: spec.to_string(),
+            err,
+        })?;
+        Self::from_string(&keymap, spec)
+    }
+
+    /// # 函数功能
+    /// 加载配置中的所有键盘布局，加载失败的布局被跳过
+    ///
+    /// ## 参数
+    /// - specs: 布局名称或路径数组
+    ///
+    /// ## 返回值
+    /// 键盘布局数组，全部加载失败时只包含美式布局
+    pub fn load_all(specs: &[String]) -> Vec<Keymap> {
+        let mut keymaps = Vec::new();
+        for spec in specs {
+            match Self::load(spec) {
+                Ok(keymap) => keymaps.push(keymap),
+                Err(err) => error!("Failed to load keyboard layout {}", err),
+            }
+        }
+        if keymaps.is_empty() {
+            keymaps.push(Self::us());
+        }
+        keymaps
+    }
+
+    /// 内置的美式布局
+    pub fn us() -> Keymap {
+        Self::load("us").expect("built-in keymap is invalid")
+    }
+
+    /// # 函数功能
+    /// 求按键在当前修饰键状态下对应的字符
+    /// 大写锁定只作用于Shift层级为对应大写字母的按键
+    ///
+    /// ## 参数
+    /// - scancode: 扫描码
+    /// - modifiers: 修饰键状态
+    ///
+    /// ## 返回值
+    /// 对应的字符，没有对应字符时返回'\0'
+    pub fn character(&self, scancode: u8, modifiers: Modifiers) -> char {
+        let levels = match self.keys.get(&scancode) {
+            Some(levels) => levels,
+            None => return '\0',
+        };
+
+        let caps = modifiers.contains(Modifiers::CAPS_LOCK)
+            && levels[0].is_lowercase()
+            && levels[1].is_uppercase();
+        let shift = modifiers.shift() != caps;
+        let altgr = modifiers.contains(Modifiers::RIGHT_ALT);
+        levels[(altgr as usize) * 2 + shift as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_keymaps_parse() {
+        for (name, _) in BUILTIN_KEYMAPS {
+            assert_eq!(Keymap::load(name).unwrap().name, name);
+        }
+    }
+
+    #[test]
+    fn levels_and_caps_lock() {
+        let de = Keymap::load("de").unwrap();
+        let shift = Modifiers::LEFT_SHIFT;
+        let caps = Modifiers::CAPS_LOCK;
+        let altgr = Modifiers::RIGHT_ALT;
+
+        assert_eq!(de.character(0x15, Modifiers::empty()), 'z');
+        assert_eq!(de.character(0x27, caps), 'Ö');
+        assert_eq!(de.character(0x27, caps | shift), 'ö');
+        assert_eq!(de.character(0x10, altgr), '@');
+        assert_eq!(de.character(0x10, altgr | shift), '@');
+        assert_eq!(de.character(0x11, altgr), '\0');
+        // 大写锁定不影响数字和符号
+        assert_eq!(de.character(0x03, caps), '2');
+        assert_eq!(de.character(0x0C, caps), 'ß');
+
+        let fr = Keymap::load("fr").unwrap();
+        assert_eq!(fr.character(0x03, Modifiers::empty()), 'é');
+        assert_eq!(fr.character(0x03, shift), '2');
+        assert_eq!(fr.character(0x10, Modifiers::empty()), 'a');
+
+        let dvorak = Keymap::load("dvorak").unwrap();
+        assert_eq!(dvorak.character(0x1F, Modifiers::empty()), 'o');
+    }
+
+    #[test]
+    fn invalid_keymap_reports_line() {
+        let keymap = "name = \"x\"\n[keys]\n0x10 = [\"ab\"]\n";
+        match Keymap::from_string(keymap, "x.toml") {
+            Err(ConfigError::Parse { line, message, .. }) => {
+                assert_eq!(line, 3);
+                assert!(message.contains("single character"), "{}", message);
+            }
+            _ => panic!("expected a parse error"),
+        }
+
+        let keymap = "name = \"x\"\n[keys]\n0x10 = [\"a\"]\nzz = [\"b\"]\n";
+        match Keymap::from_string(keymap, "x.toml") {
+            Err(ConfigError::Parse { line, .. }) => assert_eq!(line, 4),
+            _ => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn missing_keymap_is_io_error() {
+        assert!(matches!(
+            Keymap::load("/nonexistent/keymap.toml"),
+            Err(ConfigError::Io { .. })
+        ));
+    }
+}
@@ -1,48 +1,241 @@
-use std::{cell::RefCell, fs::File, io::Read, sync::Arc};
+use std::{
+    cell::RefCell,
+    collections::BTreeSet,
+    fs::File,
+    io::{self, Read},
+    os::unix::io::AsRawFd,
+    rc::{Rc, Weak},
+    time::{Duration, Instant},
+};
 
+use log::{info, warn};
 use starry_client::base::event::Event;
 
+use crate::{config::Config, error::StarryError};
+
+use self::evdev::EvdevInputHandler;
+
 use self::inputs::{KeyboardInputHandler, MouseInputHandler};
 
-use super::window_manager::window_manager;
+use self::pointer::PointerFilter;
+
+use super::{event_loop::Poller, Context};
 
+pub mod evdev;
 pub mod inputs;
+pub mod keymap;
+pub mod pointer;
+pub mod scancode;
 
-static mut INPUT_MANAGER: Option<Arc<InputManager>> = None;
+/// 重新尝试打开缺失的输入设备的时间间隔
+pub const HOTPLUG_INTERVAL: Duration = Duration::from_secs(2);
 
-pub fn input_manager() -> Option<Arc<InputManager>> {
-    unsafe { INPUT_MANAGER.clone() }
+/// 输入设备类型
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DeviceKind {
+    /// PS/2鼠标
+    Mouse,
+    /// PS/2键盘
+    Keyboard,
+    /// evdev设备
+    Evdev,
 }
 
 /// 输入管理器
+/// 无法打开或读取失败的设备被记录下来，由定时器定期重新尝试打开，支持设备热插拔
 #[allow(dead_code)]
 pub struct InputManager {
-    /// 轮询的文件数组
-    handlers: RefCell<Vec<Box<dyn InputHandler>>>,
+    /// 所属的服务器上下文
+    ctx: Weak<Context>,
+    /// 轮询的设备(设备路径，输入处理器)数组
+    handlers: RefCell<Vec<(String, Box<dyn InputHandler>)>>,
+    /// 缺失的设备路径，每个设备只在缺失时报告一次
+    missing: RefCell<BTreeSet<String>>,
+    /// 所有鼠标设备共用的指针过滤器
+    pointer: RefCell<PointerFilter>,
 }
 
 impl InputManager {
-    /// 创建输入管理器
-    pub fn new() {
-        let mut input_handlers = Vec::new();
-        input_handlers.push(MouseInputHandler::new() as Box<dyn InputHandler>);
-        input_handlers.push(KeyboardInputHandler::new() as Box<dyn InputHandler>);
+    /// # 函数功能
+    /// 创建输入管理器，按配置打开输入设备
+    /// 无法打开的设备被跳过，之后由`retry_devices`重新尝试
+    ///
+    /// ## 参数
+    /// - ctx: 所属的服务器上下文
+    /// - config: 配置信息
+    /// - screen: 屏幕大小(宽，高)，用于映射绝对坐标
+    pub fn new(ctx: Weak<Context>, config: &Config, screen: (i32, i32)) -> Self {
+        // println!("[Init] Input_Manager created successfully!");
         let input_manager = InputManager {
-            handlers: RefCell::new(input_handlers),
+            ctx,
+            handlers: RefCell::new(Vec::new()),
+            missing: RefCell::new(BTreeSet::new()),
+            pointer: RefCell::new(PointerFilter::new(config)),
+        };
+        input_manager.open_devices(config, screen);
+        input_manager
+    }
+
+    fn ctx(&self) -> Rc<Context> {
+        Context::upgrade(&self.ctx)
+    }
+
+    /// 配置中的所有输入设备(类型，路径)，evdev设备路径已展开
+    fn configured_devices(config: &Config) -> Vec<(DeviceKind, String)> {
+        let mut devices = Vec::new();
+        if !config.mouse_device.is_empty() {
+            devices.push((DeviceKind::Mouse, config.mouse_device.clone()));
+        }
+        if !config.keyboard_device.is_empty() {
+            devices.push((DeviceKind::Keyboard, config.keyboard_device.clone()));
+        }
+        for pattern in config.evdev_devices.iter() {
+            for path in EvdevInputHandler::expand_path(pattern) {
+                devices.push((DeviceKind::Evdev, path));
+            }
+        }
+        devices
+    }
+
+    /// # 函数功能
+    /// 打开输入设备
+    ///
+    /// ## 参数
+    /// - kind: 设备类型
+    /// - path: 设备文件路径
+    /// - config: 配置信息
+    /// - screen: 屏幕大小(宽，高)
+    ///
+    /// ## 返回值
+    /// 输入处理器
+    fn open_device(
+        kind: DeviceKind,
+        path: &str,
+        config: &Config,
+        screen: (i32, i32),
+    ) -> Result<Box<dyn InputHandler>, StarryError> {
+        let result: io::Result<Box<dyn InputHandler>> = match kind {
+            DeviceKind::Mouse => MouseInputHandler::new(path).map(|handler| handler as _),
+            DeviceKind::Keyboard => {
+                KeyboardInputHandler::new(path, config).map(|handler| handler as _)
+            }
+            DeviceKind::Evdev => {
+                EvdevInputHandler::new(path, config, screen).map(|handler| handler as _)
+            }
         };
+        result.map_err(|err| StarryError::InputDevice {
+            path: path.to_string(),
+            err,
+        })
+    }
 
-        unsafe {
-            INPUT_MANAGER = Some(Arc::new(input_manager));
+    /// 打开配置中尚未打开的输入设备
+    fn open_devices(&self, config: &Config, screen: (i32, i32)) {
+        for (kind, path) in Self::configured_devices(config) {
+            if self.handlers.borrow().iter().any(|(open, _)| *open == path) {
+                continue;
+            }
+            match Self::open_device(kind, &path, config, screen) {
+                Ok(handler) => {
+                    if self.missing.borrow_mut().remove(&path) {
+                        info!("Input device '{}' connected", path);
+                    }
+                    self.handlers.borrow_mut().push((path, handler));
+                }
+                Err(err) => {
+                    if self.missing.borrow_mut().insert(path) {
+                        warn!("{}, continue without it", err);
+                    }
+                }
+            }
         }
+    }
 
-        // println!("[Init] Input_Manager created successfully!");
+    /// 重新尝试打开缺失的输入设备，由定时器定期调用
+    pub fn retry_devices(&self) {
+        let ctx = self.ctx();
+        let config = ctx.server.config.borrow().clone();
+        let screen = (ctx.server.screen_width(), ctx.server.screen_height());
+        self.open_devices(&config, screen);
     }
 
-    /// 驱动所有输入处理器
-    pub fn polling_all(&self) {
+    /// 已打开的输入设备路径
+    pub fn devices(&self) -> Vec<String> {
+        self.handlers
+            .borrow()
+            .iter()
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+
+    /// 将重新加载的配置应用到所有输入处理器
+    pub fn reload_config(&self, config: &Config) {
+        self.pointer.borrow_mut().reload_config(config);
+        for (_, handler) in self.handlers.borrow_mut().iter_mut() {
+            handler.reload_config(config);
+        }
+    }
+
+    /// 对输入处理器产生的事件应用指针速度、加速和左手模式
+    pub fn filter_events(&self, events: Vec<Event>) -> Vec<Event> {
+        let mut pointer = self.pointer.borrow_mut();
+        events
+            .into_iter()
+            .map(|event| pointer.filter(event))
+            .collect()
+    }
+
+    /// 登记所有输入设备的文件描述符
+    pub fn register_fds(&self, poller: &mut Poller) {
+        for (_, handler) in self.handlers.borrow_mut().iter_mut() {
+            poller.add(handler.get_listening_file().as_raw_fd(), false);
+        }
+    }
+
+    /// 输入处理器最近的超时时间
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.handlers
+            .borrow()
+            .iter()
+            .filter_map(|(_, handler)| handler.deadline())
+            .min()
+    }
+
+    /// # 函数功能
+    /// 驱动所有输入处理器，将产生的事件过滤后交给窗口管理器
+    /// 挂断、出错或读到文件末尾的设备(如已被拔出)被关闭，之后由`retry_devices`重新尝试打开
+    ///
+    /// ## 参数
+    /// - poller: 上一次等待的结果，用于判断设备是否就绪或挂断
+    pub fn polling_all(&self, poller: &Poller) {
         // println!("[Info] Input_Manager polling all");
-        for handle in self.handlers.borrow_mut().iter_mut() {
-            handle.polling();
+        let mut events = Vec::new();
+        self.handlers.borrow_mut().retain_mut(|(path, handler)| {
+            let revents = poller.revents(handler.get_listening_file().as_raw_fd());
+            let result = if revents & (libc::POLLHUP | libc::POLLERR | libc::POLLNVAL) != 0 {
+                Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "device hung up or reported an error",
+                ))
+            } else {
+                handler.polling(revents & libc::POLLIN != 0)
+            };
+            match result {
+                Ok(handler_events) => {
+                    events.extend(handler_events);
+                    true
+                }
+                Err(err) => {
+                    warn!("Input device '{}' disconnected: {}", path, err);
+                    self.missing.borrow_mut().insert(path.clone());
+                    false
+                }
+            }
+        });
+        if !events.is_empty() {
+            self.ctx()
+                .window_manager
+                .send_events(self.filter_events(events));
         }
     }
 }
@@ -59,21 +252,54 @@ pub trait InputHandler {
     /// 处理字节数据
     fn handle(&mut self, packet: u8) -> Vec<Event>;
 
+    /// 应用重新加载的配置
+    fn reload_config(&mut self, _config: &Config) {}
+
+    /// 需要在没有输入时被轮询的时间(如等待设备应答超时)，没有时返回None
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    /// # 函数功能
     /// 轮询文件
-    fn polling(&mut self) {
-        let mut buf: [u8; 1024] = [0; 1024];
-        let mut file = self.get_listening_file();
-        let result = file.read(&mut buf);
-
-        if result.is_err() {
-            println!("[Error] Filed to polling file {:?}", file);
-        } else {
-            let count = result.ok().unwrap();
-            // println!("[Info] Input_Handler polling read {:?} bytes", count);
-            for i in 0..count {
-                let events = self.handle(buf[i]);
-                window_manager().unwrap().send_events(events);
-            }
-        }
+    ///
+    /// ## 参数
+    /// - ready: 文件是否被报告为可读
+    ///
+    /// ## 返回值
+    /// 读取的数据产生的事件，读取失败时返回错误
+    fn polling(&mut self, ready: bool) -> io::Result<Vec<Event>> {
+        poll_file(self, ready)
+    }
+}
+
+/// # 函数功能
+/// 读取输入处理器监听的文件，逐字节交给处理器并收集得到的事件
+///
+/// ## 参数
+/// - handler: 输入处理器
+/// - ready: 文件是否被报告为可读
+///
+/// ## 返回值
+/// 得到的事件，非阻塞设备暂无数据时返回空数组；
+/// 被报告为可读却读不到数据时说明已到文件末尾(设备已断开)，返回`UnexpectedEof`错误
+pub fn poll_file<H: InputHandler + ?Sized>(handler: &mut H, ready: bool) -> io::Result<Vec<Event>> {
+    let mut events = Vec::new();
+    let mut buf: [u8; 1024] = [0; 1024];
+    let count = match handler.get_listening_file().read(&mut buf) {
+        Ok(count) => count,
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => 0,
+        Err(err) => return Err(err),
+    };
+    if count == 0 && ready {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "end of file reached",
+        ));
+    }
+    // println!("[Info] Input_Handler polling read {:?} bytes", count);
+    for &byte in &buf[..count] {
+        events.extend(handler.handle(byte));
     }
+    Ok(events)
 }
@@ -0,0 +1,215 @@
+use log::warn;
+use starry_client::base::event::{ButtonEvent, Event, EventOption, MouseRelativeEvent};
+
+use crate::config::{AccelProfile, Config};
+
+/// 指针速度的取值范围
+const SPEED_MIN: f32 = 0.1;
+const SPEED_MAX: f32 = 10.0;
+
+/// 自适应加速：单次移动量不超过该值时不加速
+const ADAPTIVE_THRESHOLD: f32 = 2.0;
+/// 自适应加速：超过阈值后每单位移动量增加的放大系数
+const ADAPTIVE_GAIN: f32 = 0.25;
+/// 自适应加速：最大放大系数
+const ADAPTIVE_MAX: f32 = 4.0;
+
+/// 指针过滤器，对所有鼠标设备的事件应用速度、加速和左手模式
+/// 缩放后不足一个像素的移动量被保留，累积到下一次移动中
+pub struct PointerFilter {
+    /// 指针速度
+    speed: f32,
+    /// 加速方式
+    profile: AccelProfile,
+    /// 是否交换左右键
+    left_handed: bool,
+    /// x、y轴未输出的小数部分
+    remainder: (f32, f32),
+}
+
+impl PointerFilter {
+    /// 根据配置创建过滤器
+    pub fn new(config: &Config) -> Self {
+        let mut filter = PointerFilter {
+            speed: 1.0,
+            profile: AccelProfile::Flat,
+            left_handed: false,
+            remainder: (0.0, 0.0),
+        };
+        filter.reload_config(config);
+        filter
+    }
+
+    /// 应用重新加载的配置，超出范围的指针速度被限制在[0.1, 10]内
+    pub fn reload_config(&mut self, config: &Config) {
+        let speed = config.pointer_speed;
+        self.speed = if speed.is_finite() {
+            speed.clamp(SPEED_MIN, SPEED_MAX)
+        } else {
+            1.0
+        };
+        if self.speed != speed {
+            warn!(
+                "pointer_speed {} is out of range, use {}",
+                speed, self.speed
+            );
+        }
+        self.profile = config.pointer_acceleration;
+        self.left_handed = config.left_handed;
+        self.remainder = (0.0, 0.0);
+    }
+
+    /// # 函数功能
+    /// 过滤一个事件，非鼠标事件原样返回
+    ///
+    /// ## 参数
+    /// - event: 输入设备产生的事件
+    ///
+    /// ## 返回值
+    /// 过滤后的事件
+    pub fn filter(&mut self, event: Event) -> Event {
+        match event.to_option() {
+            EventOption::MouseRelative(motion) => self.accelerate(motion).to_event(),
+            EventOption::Button(button) if self.left_handed => ButtonEvent {
+                left: button.right,
+                right: button.left,
+                ..button
+            }
+            .to_event(),
+            _ => event,
+        }
+    }
+
+    /// 当前加速方式下移动量的放大系数
+    /// 以单次移动的距离估计移动速度
+    fn factor(&self, distance: f32) -> f32 {
+        let accel = match self.profile {
+            AccelProfile::Flat => 1.0,
+            AccelProfile::Adaptive => {
+                (1.0 + (distance - ADAPTIVE_THRESHOLD).max(0.0) * ADAPTIVE_GAIN).min(ADAPTIVE_MAX)
+            }
+        };
+        self.speed * accel
+    }
+
+    /// 缩放相对移动，保留小数部分
+    fn accelerate(&mut self, motion: MouseRelativeEvent) -> MouseRelativeEvent {
+        let (dx, dy) = (motion.dx as f32, motion.dy as f32);
+        let factor = self.factor(dx.hypot(dy));
+
+        // 反向移动时丢弃之前的小数部分，避免指针"回弹"
+        if dx * self.remainder.0 < 0.0 {
+            self.remainder.0 = 0.0;
+        }
+        if dy * self.remainder.1 < 0.0 {
+            self.remainder.1 = 0.0;
+        }
+
+        let x = dx * factor + self.remainder.0;
+        let y = dy * factor + self.remainder.1;
+        self.remainder = (x.fract(), y.fract());
+        MouseRelativeEvent {
+            dx: x.trunc() as i32,
+            dy: y.trunc() as i32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(config: &Config) -> PointerFilter {
+        PointerFilter::new(config)
+    }
+
+    fn motion(filter: &mut PointerFilter, dx: i32, dy: i32) -> (i32, i32) {
+        match filter
+            .filter(MouseRelativeEvent { dx, dy }.to_event())
+            .to_option()
+        {
+            EventOption::MouseRelative(event) => (event.dx, event.dy),
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn slow_motion_accumulates() {
+        let config = Config {
+            pointer_speed: 0.5,
+            ..Config::default()
+        };
+        let mut filter = filter(&config);
+
+        let moves: Vec<(i32, i32)> = (0..4).map(|_| motion(&mut filter, 1, -1)).collect();
+        let total = moves
+            .iter()
+            .fold((0, 0), |sum, m| (sum.0 + m.0, sum.1 + m.1));
+        assert_eq!(moves[0], (0, 0));
+        assert_eq!(total, (2, -2));
+    }
+
+    #[test]
+    fn direction_change_drops_remainder() {
+        let config = Config {
+            pointer_speed: 0.5,
+            ..Config::default()
+        };
+        let mut filter = filter(&config);
+
+        assert_eq!(motion(&mut filter, 1, 0), (0, 0));
+        assert_eq!(motion(&mut filter, -1, 0), (0, 0));
+        assert_eq!(motion(&mut filter, -1, 0), (-1, 0));
+    }
+
+    #[test]
+    fn adaptive_profile_accelerates_fast_motion() {
+        let config = Config {
+            pointer_acceleration: AccelProfile::Adaptive,
+            ..Config::default()
+        };
+        let mut filter = filter(&config);
+
+        assert_eq!(motion(&mut filter, 2, 0), (2, 0));
+        assert_eq!(motion(&mut filter, 10, 0), (30, 0));
+        // 放大系数有上限
+        assert_eq!(motion(&mut filter, 100, 0), (400, 0));
+    }
+
+    #[test]
+    fn speed_is_clamped() {
+        let mut config = Config {
+            pointer_speed: 0.0,
+            ..Config::default()
+        };
+        let mut filter = filter(&config);
+        assert_eq!(motion(&mut filter, 10, 0), (1, 0));
+
+        config.pointer_speed = f32::NAN;
+        filter.reload_config(&config);
+        assert_eq!(motion(&mut filter, 3, 0), (3, 0));
+    }
+
+    #[test]
+    fn left_handed_swaps_buttons() {
+        let config = Config {
+            left_handed: true,
+            ..Config::default()
+        };
+        let mut filter = filter(&config);
+
+        let event = ButtonEvent {
+            left: true,
+            right: false,
+            middle: true,
+            back: false,
+            forward: false,
+        };
+        match filter.filter(event.to_event()).to_option() {
+            EventOption::Button(button) => {
+                assert!(!button.left && button.right && button.middle)
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+    }
+}
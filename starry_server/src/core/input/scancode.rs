@@ -0,0 +1,361 @@
+use std::mem;
+
+use bitflags::bitflags;
+use log::info;
+use starry_client::base::event::KeyEvent;
+
+use super::keymap::Keymap;
+
+/// 扩展键前缀
+const EXTENDED_PREFIX: u8 = 0xE0;
+/// Pause键前缀，其后跟随5个字节
+const PAUSE_PREFIX: u8 = 0xE1;
+/// Pause键序列中前缀之后的字节数
+const PAUSE_LENGTH: u8 = 5;
+/// 断码(松开)标志位
+const BREAK_BIT: u8 = 0x80;
+/// 解码后扩展键扫描码的标志位
+pub const EXTENDED_BIT: u8 = 0x80;
+
+pub const SC_ESCAPE: u8 = 0x01;
+pub const SC_BACKSPACE: u8 = 0x0E;
+pub const SC_TAB: u8 = 0x0F;
+pub const SC_ENTER: u8 = 0x1C;
+pub const SC_LEFT_CTRL: u8 = 0x1D;
+pub const SC_LEFT_SHIFT: u8 = 0x2A;
+pub const SC_RIGHT_SHIFT: u8 = 0x36;
+pub const SC_KEYPAD_STAR: u8 = 0x37;
+pub const SC_LEFT_ALT: u8 = 0x38;
+pub const SC_SPACE: u8 = 0x39;
+pub const SC_CAPS_LOCK: u8 = 0x3A;
+pub const SC_NUM_LOCK: u8 = 0x45;
+pub const SC_KEYPAD_ENTER: u8 = EXTENDED_BIT | SC_ENTER;
+pub const SC_RIGHT_CTRL: u8 = EXTENDED_BIT | SC_LEFT_CTRL;
+pub const SC_KEYPAD_SLASH: u8 = EXTENDED_BIT | 0x35;
+pub const SC_RIGHT_ALT: u8 = EXTENDED_BIT | SC_LEFT_ALT;
+pub const SC_HOME: u8 = EXTENDED_BIT | 0x47;
+pub const SC_UP: u8 = EXTENDED_BIT | 0x48;
+pub const SC_PAGE_UP: u8 = EXTENDED_BIT | 0x49;
+pub const SC_LEFT: u8 = EXTENDED_BIT | 0x4B;
+pub const SC_RIGHT: u8 = EXTENDED_BIT | 0x4D;
+pub const SC_END: u8 = EXTENDED_BIT | 0x4F;
+pub const SC_DOWN: u8 = EXTENDED_BIT | 0x50;
+pub const SC_PAGE_DOWN: u8 = EXTENDED_BIT | 0x51;
+pub const SC_INSERT: u8 = EXTENDED_BIT | 0x52;
+pub const SC_DELETE: u8 = EXTENDED_BIT | 0x53;
+
+bitflags! {
+    /// 修饰键状态
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct Modifiers: u8 {
+        const LEFT_SHIFT = 0b0000_0001;
+        const RIGHT_SHIFT = 0b0000_0010;
+        const LEFT_CTRL = 0b0000_0100;
+        const RIGHT_CTRL = 0b0000_1000;
+        const LEFT_ALT = 0b0001_0000;
+        const RIGHT_ALT = 0b0010_0000;
+        /// 大写锁定(开关状态)
+        const CAPS_LOCK = 0b0100_0000;
+        /// 数字锁定(开关状态)
+        const NUM_LOCK = 0b1000_0000;
+    }
+}
+
+impl Modifiers {
+    /// 是否按住任一Shift
+    pub fn shift(self) -> bool {
+        self.intersects(Modifiers::LEFT_SHIFT | Modifiers::RIGHT_SHIFT)
+    }
+
+    /// 是否按住任一Ctrl
+    pub fn ctrl(self) -> bool {
+        self.intersects(Modifiers::LEFT_CTRL | Modifiers::RIGHT_CTRL)
+    }
+}
+
+/// 小键盘扫描码(0x47~0x53)在数字锁定打开时对应的字符
+const KEYPAD: [char; 13] = [
+    '7', '8', '9', '-', '4', '5', '6', '+', '1', '2', '3', '0', '.',
+];
+
+/// PS/2第一套扫描码解码器
+/// 扩展键(0xE0前缀)解码后的扫描码最高位置1，如右Ctrl为`0x9D`
+/// 按下左Alt+Shift时切换到下一个键盘布局
+pub struct ScancodeDecoder {
+    /// 上一个字节是否为扩展键前缀
+    extended: bool,
+    /// Pause键序列中剩余需要跳过的字节数
+    skip: u8,
+    /// 修饰键状态
+    modifiers: Modifiers,
+    /// 可切换的键盘布局
+    keymaps: Vec<Keymap>,
+    /// 当前使用的键盘布局下标
+    active: usize,
+}
+
+impl Default for ScancodeDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScancodeDecoder {
+    /// 创建使用美式布局的解码器
+    pub fn new() -> Self {
+        ScancodeDecoder {
+            extended: false,
+            skip: 0,
+            modifiers: Modifiers::empty(),
+            keymaps: vec![Keymap::us()],
+            active: 0,
+        }
+    }
+
+    /// 当前的修饰键状态
+    pub fn modifiers(&self) -> Modifiers {
+        self.modifiers
+    }
+
+    /// 当前使用的键盘布局
+    pub fn keymap(&self) -> &Keymap {
+        &self.keymaps[self.active]
+    }
+
+    /// # 函数功能
+    /// 设置可切换的键盘布局，切换到第一个布局
+    ///
+    /// ## 参数
+    /// - keymaps: 键盘布局数组，为空时使用美式布局
+    pub fn set_keymaps(&mut self, keymaps: Vec<Keymap>) {
+        self.keymaps = keymaps;
+        if self.keymaps.is_empty() {
+            self.keymaps.push(Keymap::us());
+        }
+        self.active = 0;
+    }
+
+    /// 切换到下一个键盘布局
+    pub fn next_keymap(&mut self) {
+        self.active = (self.active + 1) % self.keymaps.len();
+        info!("Keyboard layout switched to {}", self.keymap().name);
+    }
+
+    /// # 函数功能
+    /// 解码一个字节
+    ///
+    /// ## 参数
+    /// - byte: 键盘发来的字节
+    ///
+    /// ## 返回值
+    /// 字节构成完整的通码或断码时返回按键事件，否则返回None
+    pub fn feed(&mut self, byte: u8) -> Option<KeyEvent> {
+        if self.skip > 0 {
+            self.skip -= 1;
+            return None;
+        }
+
+        match byte {
+            EXTENDED_PREFIX => {
+                self.extended = true;
+                return None;
+            }
+            PAUSE_PREFIX => {
+                self.skip = PAUSE_LENGTH;
+                return None;
+            }
+            // 键盘的应答、重发请求和错误码
+            0x00 | 0xFA | 0xFE | 0xFF => return None,
+            _ => {}
+        }
+
+        let extended = mem::take(&mut self.extended);
+        let pressed = byte & BREAK_BIT == 0;
+        let code = byte & !BREAK_BIT;
+
+        // 部分键盘在扩展键前后发送的伪Shift
+        if extended && (code == SC_LEFT_SHIFT || code == SC_RIGHT_SHIFT) {
+            return None;
+        }
+
+        let scancode = if extended { code | EXTENDED_BIT } else { code };
+        let previous = self.modifiers;
+        self.update_modifiers(scancode, pressed);
+
+        // 左Alt+Shift切换布局(AltGr+Shift用于输入第四层级字符)
+        let hotkey =
+            |modifiers: Modifiers| modifiers.contains(Modifiers::LEFT_ALT) && modifiers.shift();
+        if pressed && hotkey(self.modifiers) && !hotkey(previous) {
+            self.next_keymap();
+        }
+
+        Some(KeyEvent {
+            character: self.character(scancode),
+            scancode,
+            pressed,
+        })
+    }
+
+    /// 根据按键更新修饰键状态
+    fn update_modifiers(&mut self, scancode: u8, pressed: bool) {
+        let held = match scancode {
+            SC_LEFT_SHIFT => Modifiers::LEFT_SHIFT,
+            SC_RIGHT_SHIFT => Modifiers::RIGHT_SHIFT,
+            SC_LEFT_CTRL => Modifiers::LEFT_CTRL,
+            SC_RIGHT_CTRL => Modifiers::RIGHT_CTRL,
+            SC_LEFT_ALT => Modifiers::LEFT_ALT,
+            SC_RIGHT_ALT => Modifiers::RIGHT_ALT,
+            SC_CAPS_LOCK if pressed => {
+                self.modifiers.toggle(Modifiers::CAPS_LOCK);
+                return;
+            }
+            SC_NUM_LOCK if pressed => {
+                self.modifiers.toggle(Modifiers::NUM_LOCK);
+                return;
+            }
+            _ => return,
+        };
+        self.modifiers.set(held, pressed);
+    }
+
+    /// 求按键对应的字符，与布局无关的按键直接给出，其余按键查询当前布局
+    /// 没有对应字符时返回'\0'
+    fn character(&self, scancode: u8) -> char {
+        match scancode {
+            SC_ESCAPE => '\x1b',
+            SC_BACKSPACE => '\x08',
+            SC_TAB => '\t',
+            SC_ENTER | SC_KEYPAD_ENTER => '\n',
+            SC_SPACE => ' ',
+            SC_KEYPAD_STAR => '*',
+            SC_KEYPAD_SLASH => '/',
+            0x47..=0x53 if self.modifiers.contains(Modifiers::NUM_LOCK) => {
+                KEYPAD[(scancode - 0x47) as usize]
+            }
+            0x4A => '-',
+            0x4E => '+',
+            _ => self.keymap().character(scancode, self.modifiers),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(bytes: &[u8]) -> Vec<(char, u8, bool)> {
+        let mut decoder = ScancodeDecoder::new();
+        bytes
+            .iter()
+            .filter_map(|&byte| decoder.feed(byte))
+            .map(|event| (event.character, event.scancode, event.pressed))
+            .collect()
+    }
+
+    #[test]
+    fn make_and_break_codes() {
+        assert_eq!(
+            decode(&[0x1E, 0x9E, 0x02, 0x82]),
+            vec![
+                ('a', 0x1E, true),
+                ('a', 0x1E, false),
+                ('1', 0x02, true),
+                ('1', 0x02, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn shift_and_caps_lock() {
+        // Shift+a、Shift+1
+        assert_eq!(
+            decode(&[0x2A, 0x1E, 0x02, 0xAA])[1..3],
+            [('A', 0x1E, true), ('!', 0x02, true)]
+        );
+        // 大写锁定只影响字母，与Shift同时生效时抵消
+        let events = decode(&[0x3A, 0xBA, 0x1E, 0x02, 0x36, 0x1E, 0xB6, 0x3A, 0xBA, 0x1E]);
+        let characters: Vec<char> = events
+            .iter()
+            .filter(|event| event.2 && event.0 != '\0')
+            .map(|event| event.0)
+            .collect();
+        assert_eq!(characters, vec!['A', '1', 'a', 'a']);
+    }
+
+    #[test]
+    fn extended_codes() {
+        assert_eq!(
+            decode(&[0xE0, 0x48, 0xE0, 0xC8, 0xE0, 0x1C]),
+            vec![
+                ('\0', SC_UP, true),
+                ('\0', SC_UP, false),
+                ('\n', SC_KEYPAD_ENTER, true)
+            ]
+        );
+
+        // 扩展键不影响后续的普通按键
+        assert_eq!(
+            decode(&[0xE0, 0x1D, 0x1D]),
+            vec![('\0', SC_RIGHT_CTRL, true), ('\0', SC_LEFT_CTRL, true)]
+        );
+    }
+
+    #[test]
+    fn extended_modifiers_are_tracked() {
+        let mut decoder = ScancodeDecoder::new();
+        for byte in [0xE0, 0x1D, 0xE0, 0x38] {
+            decoder.feed(byte);
+        }
+        assert!(decoder.modifiers().ctrl());
+        assert!(decoder.modifiers().contains(Modifiers::RIGHT_ALT));
+
+        for byte in [0xE0, 0x9D] {
+            decoder.feed(byte);
+        }
+        assert!(!decoder.modifiers().ctrl());
+    }
+
+    #[test]
+    fn fake_shift_and_pause_are_ignored() {
+        // Print Screen：E0 2A E0 37，Pause：E1 1D 45 E1 9D C5
+        assert_eq!(
+            decode(&[0xE0, 0x2A, 0xE0, 0x37, 0xE1, 0x1D, 0x45, 0xE1, 0x9D, 0xC5, 0x10]),
+            vec![
+                ('\0', EXTENDED_BIT | SC_KEYPAD_STAR, true),
+                ('q', 0x10, true)
+            ]
+        );
+    }
+
+    #[test]
+    fn alt_shift_switches_layout() {
+        let mut decoder = ScancodeDecoder::new();
+        decoder.set_keymaps(vec![Keymap::us(), Keymap::load("de").unwrap()]);
+
+        assert_eq!(decoder.feed(0x15).unwrap().character, 'y');
+        // 左Alt按住时按下Shift，之后自动重复的Shift不再切换
+        for byte in [0x38, 0x2A, 0x2A, 0xAA, 0xB8] {
+            decoder.feed(byte);
+        }
+        assert_eq!(decoder.keymap().name, "de");
+        assert_eq!(decoder.feed(0x15).unwrap().character, 'z');
+
+        // AltGr+Shift不切换
+        for byte in [0xE0, 0x38, 0x2A, 0xAA, 0xE0, 0xB8] {
+            decoder.feed(byte);
+        }
+        assert_eq!(decoder.keymap().name, "de");
+
+        for byte in [0x2A, 0x38, 0xB8, 0xAA] {
+            decoder.feed(byte);
+        }
+        assert_eq!(decoder.keymap().name, "us");
+    }
+
+    #[test]
+    fn keypad_follows_num_lock() {
+        assert_eq!(decode(&[0x47])[0].0, '\0');
+        assert_eq!(decode(&[0x45, 0xC5, 0x47])[2].0, '7');
+        assert_eq!(decode(&[0x4E])[0].0, '+');
+    }
+}
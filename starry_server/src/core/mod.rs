@@ -1,95 +1,440 @@
-use std::{cell::RefCell, collections::BTreeMap, sync::Arc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+    fs,
+    rc::{Rc, Weak},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use log::{error, info};
+use starry_client::base::{protocol::WindowFlags, screen::ScreenInfo};
 
 use crate::{
-    base::{display::Display, image::Image},
+    base::{display::Display, image::Image, rect::Rect, window::WindowTheme},
     config::Config,
+    error::StarryError,
+    logger,
 };
 
 use self::{
-    compositor::{compositor, Compositor},
-    input::{input_manager, InputManager},
-    window_manager::{window_manager, CursorKind, WindowManager},
+    backend::DisplayBackend,
+    compositor::Compositor,
+    event_loop::{run_timers, Poller, Timers},
+    input::{InputManager, HOTPLUG_INTERVAL},
+    window_manager::{Cursor, CursorKind, WindowManager},
 };
 
+pub mod backend;
 pub mod compositor;
+pub mod event_loop;
 pub mod input;
 pub mod window_manager;
 
-// TODO: 读帧缓冲设备属性
-/// 屏幕宽度
-pub const SCREEN_WIDTH: usize = 1440;
-/// 屏幕高度
-#[allow(dead_code)]
-pub const SCREEN_HEIGHT: usize = 900;
-
 static DESKTOP_BG: &[u8] = include_bytes!("../resource/desktop_bg.png");
 static CURSOR_NORMAL: &[u8] = include_bytes!("../resource/cursor_normal.png");
 
-static mut STARRY_SERVER: Option<Arc<StarryServer>> = None;
+/// 检查配置文件是否修改的时间间隔
+const CONFIG_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// 等待失败后重试前的延迟，避免空转
+const POLL_ERROR_DELAY: Duration = Duration::from_millis(10);
+
+/// 服务器上下文，持有图形系统的所有子系统
+/// 子系统保存上下文的弱引用，通过它访问其他子系统，因此同一进程中可以同时存在多个互不影响的服务器
+pub struct Context {
+    /// 图形系统服务器(配置、显示窗口和样式)
+    pub server: StarryServer,
+    /// 窗口管理器
+    pub window_manager: WindowManager,
+    /// 合成渲染器
+    pub compositor: Compositor,
+    /// 输入管理器
+    pub input_manager: InputManager,
+}
+
+impl Context {
+    /// # 函数功能
+    /// 创建服务器上下文，打开输入设备并监听客户端连接
+    /// 无法打开的输入设备被跳过，不影响启动
+    ///
+    /// ## 参数
+    /// - config: 配置信息
+    /// - config_path: 配置文件路径，用于检测修改并重新加载
+    /// - backend: 显示后端
+    /// - socket_path: 监听客户端连接的套接字路径，为None时不接受连接(用于测试)
+    ///
+    /// ## 返回值
+    /// 服务器上下文，无法创建时返回错误
+    pub fn new(
+        config: Arc<Config>,
+        config_path: Option<String>,
+        backend: Box<dyn DisplayBackend>,
+        socket_path: Option<&str>,
+    ) -> Result<Rc<Context>, StarryError> {
+        let screen_info = backend.screen_info();
+        if screen_info.width == 0 || screen_info.height == 0 {
+            return Err(StarryError::InvalidScreen {
+                width: screen_info.width,
+                height: screen_info.height,
+            });
+        }
+        let screen = (screen_info.width as i32, screen_info.height as i32);
+
+        let listener =
+            match socket_path {
+                Some(path) => Some(WindowManager::bind_listener(path).map_err(|err| {
+                    StarryError::Listener {
+                        path: path.to_string(),
+                        err,
+                    }
+                })?),
+                None => None,
+            };
+
+        Ok(Rc::new_cyclic(|ctx: &Weak<Context>| Context {
+            input_manager: InputManager::new(ctx.clone(), &config, screen),
+            window_manager: WindowManager::new(ctx.clone(), listener, screen),
+            compositor: Compositor::new(ctx.clone(), backend),
+            server: StarryServer::new(ctx.clone(), config, config_path, screen_info),
+        }))
+    }
+
+    /// 获得子系统所属的上下文，上下文被释放后子系统不再被调用
+    fn upgrade(ctx: &Weak<Context>) -> Rc<Context> {
+        ctx.upgrade().expect("server context has been dropped")
+    }
+
+    /// 创建桌面窗口
+    pub fn create_desktop(&self) {
+        // TODO 临时在此创建桌面窗口
+        let desktop_image = self.server.desktop_image(&self.server.config.borrow());
+        let desktop_id = self.window_manager.window_new(
+            0,
+            0,
+            WindowFlags::BACK | WindowFlags::BORDERLESS | WindowFlags::UNCLOSABLE,
+            String::new(),
+            desktop_image,
+            None,
+        );
+        self.server.desktop_id.set(Some(desktop_id));
+    }
+
+    /// # 函数功能
+    /// 执行一轮主循环：读取输入和客户端请求，执行定时器，处理事件，重绘并发送事件
+    ///
+    /// ## 参数
+    /// - poller: 上一次等待的结果
+    pub fn dispatch(&self, poller: &Poller) {
+        self.input_manager.polling_all(poller); // 读取所有设备文件中已有的数据
+        self.window_manager.polling_all_windows(); // 读取所有窗口通信管道中已有的请求
+        run_timers(&self.server.timers); // 执行到期的定时器
+        self.window_manager.handle_all_events(); // 处理所有事件
+        self.compositor.redraw_all(); // 重绘所有更新区域
+        self.window_manager.flush_all_windows(); // 发送事件和回复
+    }
+
+    /// 创建桌面窗口并开启主循环
+    pub fn run(self: &Rc<Self>) {
+        self.create_desktop();
+
+        if self.server.config_path.is_some() {
+            let ctx = Rc::downgrade(self);
+            self.server.add_timer(
+                CONFIG_CHECK_INTERVAL,
+                Some(CONFIG_CHECK_INTERVAL),
+                Box::new(move || {
+                    if let Some(ctx) = ctx.upgrade() {
+                        ctx.server.check_config_update();
+                    }
+                }),
+            );
+        }
+
+        let ctx = Rc::downgrade(self);
+        self.server.add_timer(
+            HOTPLUG_INTERVAL,
+            Some(HOTPLUG_INTERVAL),
+            Box::new(move || {
+                if let Some(ctx) = ctx.upgrade() {
+                    ctx.input_manager.retry_devices();
+                }
+            }),
+        );
+
+        // println!("[Init] Starry_Server start main loop!");
+        let mut poller = Poller::new();
+        loop {
+            self.dispatch(&poller);
 
-pub fn starry_server() -> Option<Arc<StarryServer>> {
-    unsafe { STARRY_SERVER.clone() }
+            // 等待设备或客户端就绪，或下一个定时器到期
+            poller.clear();
+            self.input_manager.register_fds(&mut poller);
+            self.window_manager.register_fds(&mut poller);
+            let deadline = [
+                self.server.timers.borrow().next_deadline(),
+                self.input_manager.next_deadline(),
+            ]
+            .into_iter()
+            .flatten()
+            .min();
+            let timeout =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            if let Err(err) = poller.wait(timeout) {
+                error!("Starry_Server failed to wait for events: {}", err);
+                std::thread::sleep(POLL_ERROR_DELAY);
+            }
+        }
+    }
 }
 
 /// 图形系统服务器
 pub struct StarryServer {
+    /// 所属的服务器上下文
+    ctx: Weak<Context>,
     pub displays: RefCell<Vec<Display>>,
     pub config: RefCell<Arc<Config>>,
-    pub cursors: RefCell<BTreeMap<CursorKind, Image>>,
+    pub cursors: RefCell<BTreeMap<CursorKind, Cursor>>,
+    /// 窗口装饰样式
+    pub theme: RefCell<WindowTheme>,
+    /// 屏幕信息，由显示后端提供
+    pub screen_info: ScreenInfo,
+    /// 配置文件路径，使用默认配置时为None
+    config_path: Option<String>,
+    /// 配置文件最近一次的修改时间
+    config_mtime: Cell<Option<SystemTime>>,
+    /// 主循环的定时器
+    timers: RefCell<Timers>,
+    /// 桌面窗口id
+    desktop_id: Cell<Option<usize>>,
 }
 
 impl StarryServer {
+    /// # 函数功能
     /// 创建图形服务器
-    pub fn new(config: Arc<Config>, displays: Vec<Display>) {
-        let mut cursors = BTreeMap::new();
-        cursors.insert(CursorKind::None, Image::new(0, 0));
-        cursors.insert(
-            CursorKind::Normal,
-            Image::from_path(CURSOR_NORMAL).unwrap_or(Image::new(10, 10)),
-        );
-        // cursors.insert(CursorKind::BottomLeftCorner, Image::from_path_scale(&config.bottom_left_corner, scale).unwrap_or(Image::new(0, 0)));
-        // cursors.insert(CursorKind::BottomRightCorner, Image::from_path_scale(&config.bottom_right_corner, scale).unwrap_or(Image::new(0, 0)));
-        // cursors.insert(CursorKind::BottomSide, Image::from_path_scale(&config.bottom_side, scale).unwrap_or(Image::new(0, 0)));
-        // cursors.insert(CursorKind::LeftSide, Image::from_path_scale(&config.left_side, scale).unwrap_or(Image::new(0, 0)));
-        // cursors.insert(CursorKind::RightSide, Image::from_path_scale(&config.right_side, scale).unwrap_or(Image::new(0, 0)));
+    ///
+    /// ## 参数
+    /// - ctx: 所属的服务器上下文
+    /// - config: 配置信息
+    /// - config_path: 配置文件路径，用于检测修改并重新加载
+    /// - screen_info: 屏幕信息
+    pub fn new(
+        ctx: Weak<Context>,
+        config: Arc<Config>,
+        config_path: Option<String>,
+        screen_info: ScreenInfo,
+    ) -> Self {
+        let cursors = Self::load_cursors(&config);
+        let theme = Self::load_theme(&config);
+        let config_mtime = config_path.as_deref().and_then(Self::modified_time);
+
+        // TODO 考虑多个显示器
+        let displays = vec![Display::new(
+            0,
+            0,
+            screen_info.width as i32,
+            screen_info.height as i32,
+        )];
 
-        let server = StarryServer {
+        // println!("[Init] Starry_Server created successfully!");
+        StarryServer {
+            ctx,
             displays: RefCell::new(displays),
             config: RefCell::new(config),
             cursors: RefCell::new(cursors),
+            theme: RefCell::new(theme),
+            screen_info,
+            config_path,
+            config_mtime: Cell::new(config_mtime),
+            timers: RefCell::new(Timers::new()),
+            desktop_id: Cell::new(None),
+        }
+    }
+
+    fn ctx(&self) -> Rc<Context> {
+        Context::upgrade(&self.ctx)
+    }
+
+    /// 根据配置加载所有鼠标样式
+    /// 普通样式无法加载时使用内置图片，其他样式无法加载时使用普通样式
+    fn load_cursors(config: &Config) -> BTreeMap<CursorKind, Cursor> {
+        let normal = match Self::load_image(&config.normal.path) {
+            Some(image) => Cursor::new(image, config.normal.hotspot.0, config.normal.hotspot.1),
+            None => Cursor::new(
+                Image::from_path(CURSOR_NORMAL).unwrap_or(Image::new(10, 10)),
+                0,
+                0,
+            ),
         };
 
-        unsafe {
-            STARRY_SERVER = Some(Arc::new(server));
+        let mut cursors = BTreeMap::new();
+        cursors.insert(CursorKind::None, Cursor::new(Image::new(0, 0), 0, 0));
+
+        let others = [
+            (CursorKind::BottomLeftCorner, &config.bottom_left_corner),
+            (CursorKind::BottomRightCorner, &config.bottom_right_corner),
+            (CursorKind::BottomSide, &config.bottom_side),
+            (CursorKind::LeftSide, &config.left_side),
+            (CursorKind::RightSide, &config.right_side),
+            (CursorKind::Text, &config.text),
+            (CursorKind::Move, &config.move_),
+            (CursorKind::Busy, &config.busy),
+            (CursorKind::NotAllowed, &config.not_allowed),
+        ];
+        for (kind, cursor_config) in others {
+            let cursor = match Self::load_image(&cursor_config.path) {
+                Some(image) => Cursor::new(image, cursor_config.hotspot.0, cursor_config.hotspot.1),
+                None => normal.clone(),
+            };
+            cursors.insert(kind, cursor);
         }
 
-        // println!("[Init] Starry_Server created successfully!");
+        cursors.insert(CursorKind::Normal, normal);
+        cursors
     }
 
-    /// 开启主循环
-    pub fn run(&self) {
-        WindowManager::new();
-        Compositor::new();
-        InputManager::new();
+    /// 根据配置加载窗口装饰样式
+    fn load_theme(config: &Config) -> WindowTheme {
+        WindowTheme {
+            close: Self::load_image(&config.window_close),
+            close_unfocused: Self::load_image(&config.window_close_unfocused),
+            max: Self::load_image(&config.window_max),
+            max_unfocused: Self::load_image(&config.window_max_unfocused),
+            bar_color: config.bar_color,
+            bar_highlight_color: config.bar_highlight_color,
+            text_color: config.text_color,
+            text_highlight_color: config.text_highlight_color,
+        }
+    }
 
-        // TODO 临时在此创建桌面窗口
-        window_manager().unwrap().window_new(
-            0,
-            0,
-            SCREEN_WIDTH as i32,
-            SCREEN_HEIGHT as i32,
-            "",
-            "".to_string(),
-            DESKTOP_BG,
-        );
+    /// 从文件加载图片，路径为空或加载失败时返回None
+    fn load_image(path: &str) -> Option<Image> {
+        if path.is_empty() {
+            return None;
+        }
 
-        // println!("[Init] Starry_Server start main loop!");
-        loop {
-            input_manager().unwrap().polling_all(); // 轮询所有设备文件
-            window_manager().unwrap().polling_all_windows(); // 轮询所有窗口通信管道
-            window_manager().unwrap().handle_all_events(); // 处理所有事件
-            compositor().unwrap().redraw_all(); // 重绘所有更新区域
-            std::thread::sleep(std::time::Duration::from_millis(1));
+        match fs::read(path) {
+            Ok(data) => {
+                let image = Image::from_path(&data);
+                if image.is_none() {
+                    error!("Failed to decode image '{}'", path);
+                }
+                image
+            }
+            Err(err) => {
+                error!("Failed to read image '{}': {}", path, err);
+                None
+            }
         }
     }
+
+    /// 根据配置生成桌面背景图片
+    fn desktop_image(&self, config: &Config) -> Image {
+        let (width, height) = (self.screen_info.width, self.screen_info.height);
+        let image = if config.desktop_background.is_empty() {
+            Image::from_path_fit(DESKTOP_BG, width, height)
+        } else {
+            fs::read(&config.desktop_background)
+                .ok()
+                .and_then(|data| Image::from_path_fit(&data, width, height))
+                .or_else(|| {
+                    error!(
+                        "Failed to load desktop background '{}'",
+                        config.desktop_background
+                    );
+                    Image::from_path_fit(DESKTOP_BG, width, height)
+                })
+        };
+        image.unwrap_or(Image::new(self.screen_width(), self.screen_height()))
+    }
+
+    /// 获取文件的修改时间
+    fn modified_time(path: &str) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|meta| meta.modified()).ok()
+    }
+
+    /// 检查配置文件，发生修改时重新加载，由定时器定期调用
+    pub fn check_config_update(&self) {
+        if let Some(path) = self.config_path.as_deref() {
+            let mtime = Self::modified_time(path);
+            if mtime.is_some() && mtime != self.config_mtime.get() {
+                self.config_mtime.set(mtime);
+                self.reload_config();
+            }
+        }
+    }
+
+    /// # 函数功能
+    /// 重新加载配置文件，重建鼠标样式和主题并重绘整个屏幕
+    /// 配置文件有错误时保留原有配置
+    /// 显示后端相关的配置需要重启服务器才能生效
+    pub fn reload_config(&self) {
+        let config = match Config::load(self.config_path.as_deref()) {
+            Ok(config) => config,
+            Err(err) => {
+                error!("Failed to reload config {}, keep old config", err);
+                return;
+            }
+        };
+        if let Err(err) = logger::configure(config.log_level.filter(), &config.log_file) {
+            error!("{}", err);
+        }
+        info!("Config reloaded");
+
+        *self.cursors.borrow_mut() = Self::load_cursors(&config);
+        *self.theme.borrow_mut() = Self::load_theme(&config);
+        let ctx = self.ctx();
+        ctx.input_manager.reload_config(&config);
+        let window_manager = &ctx.window_manager;
+        for window in window_manager.windows.borrow_mut().values_mut() {
+            window.invalidate_title();
+        }
+        if let Some(id) = self.desktop_id.get() {
+            let image = self.desktop_image(&config);
+            if let Some(window) = window_manager.windows.borrow_mut().get_mut(&id) {
+                window.image = image;
+            }
+        }
+        *self.config.borrow_mut() = Arc::new(config);
+
+        ctx.compositor.request_redraw(self.screen_rect());
+    }
+
+    /// 屏幕宽度
+    pub fn screen_width(&self) -> i32 {
+        self.screen_info.width as i32
+    }
+
+    /// 屏幕高度
+    pub fn screen_height(&self) -> i32 {
+        self.screen_info.height as i32
+    }
+
+    /// 整个屏幕的矩形区域
+    pub fn screen_rect(&self) -> Rect {
+        Rect::new(0, 0, self.screen_width(), self.screen_height())
+    }
+
+    /// # 函数功能
+    /// 添加主循环的定时器，用于动画、光标闪烁等需要定期执行的任务
+    ///
+    /// ## 参数
+    /// - delay: 第一次到期前的延迟
+    /// - interval: 重复间隔，为None时只触发一次
+    /// - callback: 到期时调用的函数
+    ///
+    /// ## 返回值
+    /// 定时器id
+    pub fn add_timer(
+        &self,
+        delay: Duration,
+        interval: Option<Duration>,
+        callback: Box<dyn FnMut()>,
+    ) -> usize {
+        self.timers.borrow_mut().add(delay, interval, callback)
+    }
+
+    /// 取消定时器
+    pub fn cancel_timer(&self, id: usize) {
+        self.timers.borrow_mut().cancel(id);
+    }
 }
@@ -1,30 +1,33 @@
 use std::{
     cell::{Cell, RefCell},
-    cmp,
+    cmp::{self, Reverse},
     collections::{BTreeMap, VecDeque},
-    sync::Arc,
-};
-
-use starry_client::base::event::{
-    ButtonEvent, Event, EventOption, KeyEvent, MouseRelativeEvent, MouseUpdateEvent,
-    WindowMoveEvent, WindowResizeEvent,
+    fs, io, mem,
+    os::unix::{
+        io::{AsRawFd, OwnedFd},
+        net::UnixListener,
+    },
+    rc::{Rc, Weak},
 };
 
-use crate::{
-    base::{
-        rect::Rect,
-        window::{Window, WindowZOrderMode},
+use log::error;
+use starry_client::base::{
+    event::{
+        ButtonEvent, EnterEvent, Event, EventOption, FocusEvent, KeyEvent, LeaveEvent,
+        MouseRelativeEvent, MouseUpdateEvent, QuitEvent, ScrollEvent, WindowMoveEvent,
+        WindowResizeEvent,
     },
-    core::{SCREEN_HEIGHT, SCREEN_WIDTH},
+    protocol::{Connection, Reply, Request, WindowCursor, WindowFlags},
+    shm::{buffer_len, SharedMemory},
 };
 
-use super::{compositor::compositor, starry_server};
-
-static mut WINDOW_MANAGER: Option<Arc<WindowManager>> = None;
+use crate::base::{
+    image::Image,
+    rect::Rect,
+    window::{ResizeEdge, Window, WindowZOrderMode, TITLE_HEIGHT},
+};
 
-pub fn window_manager() -> Option<Arc<WindowManager>> {
-    unsafe { WINDOW_MANAGER.clone() }
-}
+use super::{event_loop::Poller, Context};
 
 /// 鼠标样式
 #[allow(dead_code)]
@@ -44,21 +47,107 @@ pub enum CursorKind {
     LeftSide,
     /// 右边界
     RightSide,
+    /// 文本输入
+    Text,
+    /// 移动
+    Move,
+    /// 忙碌
+    Busy,
+    /// 禁止操作
+    NotAllowed,
+}
+
+impl From<WindowCursor> for CursorKind {
+    fn from(cursor: WindowCursor) -> Self {
+        match cursor {
+            WindowCursor::Normal => CursorKind::Normal,
+            WindowCursor::Text => CursorKind::Text,
+            WindowCursor::Move => CursorKind::Move,
+            WindowCursor::Busy => CursorKind::Busy,
+            WindowCursor::NotAllowed => CursorKind::NotAllowed,
+        }
+    }
+}
+
+/// 一种鼠标样式
+#[derive(Clone)]
+pub struct Cursor {
+    /// 样式图片
+    pub image: Image,
+    /// 热点在图片中的x坐标
+    pub hotspot_x: i32,
+    /// 热点在图片中的y坐标
+    pub hotspot_y: i32,
+}
+
+impl Cursor {
+    pub fn new(image: Image, hotspot_x: i32, hotspot_y: i32) -> Self {
+        Cursor {
+            image,
+            hotspot_x,
+            hotspot_y,
+        }
+    }
+}
+
+/// 正在进行的鼠标拖拽操作
+#[derive(Clone, Copy, Debug)]
+enum DragState {
+    /// 拖动标题栏移动窗口，记录鼠标相对窗口左上角的偏移
+    Move {
+        id: usize,
+        offset_x: i32,
+        offset_y: i32,
+    },
+    /// 拖动边缘缩放窗口，记录按下时的鼠标位置、窗体矩形和当前预览的窗体矩形
+    Resize {
+        id: usize,
+        edge: ResizeEdge,
+        start_x: i32,
+        start_y: i32,
+        origin: Rect,
+        current: Rect,
+    },
+    /// 在最大化按钮上按下，松开时仍位于按钮上则切换最大化
+    MaxButton { id: usize },
+    /// 在关闭按钮上按下，松开时仍位于按钮上则关闭窗口
+    CloseButton { id: usize },
+}
+
+impl DragState {
+    /// 拖拽操作针对的窗口id
+    fn id(&self) -> usize {
+        match *self {
+            DragState::Move { id, .. }
+            | DragState::Resize { id, .. }
+            | DragState::MaxButton { id }
+            | DragState::CloseButton { id } => id,
+        }
+    }
 }
 
+/// 缩放预览边框的宽度
+pub const RESIZE_OUTLINE: i32 = 2;
+
 /// 窗口管理器
 #[allow(dead_code)]
 pub struct WindowManager {
+    /// 所属的服务器上下文
+    ctx: Weak<Context>,
     /// 下一个窗口的id值
-    next_id: Cell<isize>,
-    /// TODO
-    _hover: RefCell<Option<usize>>,
-    /// 窗口顺序
+    next_id: Cell<usize>,
+    /// 鼠标所在的窗口，接收鼠标移动、按键和滚轮事件
+    hover: Cell<Option<usize>>,
+    /// 在窗口内按下鼠标按键后，松开所有按键之前鼠标事件都发送给该窗口
+    grab: Cell<Option<usize>>,
+    /// 窗口顺序，靠前的窗口位于上层
     pub order: RefCell<VecDeque<usize>>,
-    /// 窗口顺序信息(下标index，模式，窗口id)
+    /// 窗口顺序信息(窗口id，模式，下标index)
     pub zbuffer: RefCell<Vec<(usize, WindowZOrderMode, usize)>>,
     /// 窗口字典
     pub windows: RefCell<BTreeMap<usize, Window>>,
+    /// 聚焦窗口的id
+    focused: Cell<Option<usize>>,
 
     /// 鼠标x坐标
     pub cursor_x: Cell<i32>,
@@ -66,31 +155,69 @@ pub struct WindowManager {
     pub cursor_y: Cell<i32>,
     /// 鼠标状态
     pub cursor_i: Cell<CursorKind>,
+    /// 上一次的鼠标按键状态
+    buttons: Cell<ButtonEvent>,
+    /// 正在进行的拖拽操作
+    drag: Cell<Option<DragState>>,
 
     /// 待处理的事件数组
     events: RefCell<Vec<Event>>,
+
+    /// 监听客户端连接的套接字
+    listener: Option<UnixListener>,
+    /// 尚未创建窗口的客户端连接
+    connections: RefCell<Vec<Connection>>,
 }
 
 impl WindowManager {
-    /// 创建窗口管理器
-    pub fn new() {
-        let window_manager = WindowManager {
+    /// # 函数功能
+    /// 创建窗口管理器，鼠标初始位于屏幕中央
+    ///
+    /// ## 参数
+    /// - ctx: 所属的服务器上下文
+    /// - listener: 监听客户端连接的套接字，为None时不接受连接
+    /// - screen: 屏幕大小(宽，高)
+    pub fn new(ctx: Weak<Context>, listener: Option<UnixListener>, screen: (i32, i32)) -> Self {
+        // println!("[Init] Window_Manager created successfully!");
+        WindowManager {
+            ctx,
             next_id: Cell::new(0),
-            _hover: RefCell::new(None),
+            hover: Cell::new(None),
+            grab: Cell::new(None),
             order: RefCell::new(VecDeque::new()),
             zbuffer: RefCell::new(Vec::new()),
             windows: RefCell::new(BTreeMap::new()),
-            cursor_x: Cell::new(SCREEN_WIDTH as i32 / 2),
-            cursor_y: Cell::new(SCREEN_HEIGHT as i32 / 2),
+            focused: Cell::new(None),
+            cursor_x: Cell::new(screen.0 / 2),
+            cursor_y: Cell::new(screen.1 / 2),
             cursor_i: Cell::new(CursorKind::Normal),
+            buttons: Cell::new(ButtonEvent::new(0)),
+            drag: Cell::new(None),
             events: RefCell::new(Vec::new()),
-        };
-
-        unsafe {
-            WINDOW_MANAGER = Some(Arc::new(window_manager));
+            listener,
+            connections: RefCell::new(Vec::new()),
         }
+    }
 
-        // println!("[Init] Window_Manager created successfully!");
+    fn ctx(&self) -> Rc<Context> {
+        Context::upgrade(&self.ctx)
+    }
+
+    /// # 函数功能
+    /// 创建监听客户端连接的非阻塞套接字
+    ///
+    /// ## 参数
+    /// - path: 套接字路径，上次运行遗留的套接字文件会被删除
+    ///
+    /// ## 返回值
+    /// 监听套接字
+    pub fn bind_listener(path: &str) -> io::Result<UnixListener> {
+        // 清理上次运行遗留的套接字文件
+        let _ = fs::remove_file(path);
+
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Ok(listener)
     }
 
     /// # 函数功能
@@ -99,10 +226,10 @@ impl WindowManager {
     /// ## 参数
     /// - x: 窗口左上角x坐标
     /// - y: 窗口左上角y坐标
-    /// - width: 窗口宽度
-    /// - height: 窗口高度
     /// - flags: 窗口属性
     /// - title: 窗口标题
+    /// - image: 窗体图像，决定窗口的宽度和高度
+    /// - connection_opt: 与客户端的连接
     ///
     /// ## 返回值
     /// 窗口id
@@ -110,45 +237,67 @@ impl WindowManager {
         &self,
         mut x: i32,
         mut y: i32,
-        width: i32,
-        height: i32,
-        _flags: &str,
-        _title: String,
-        image_path: &[u8],
-    ) {
-        let compositor = compositor().unwrap();
+        flags: WindowFlags,
+        title: String,
+        image: Image,
+        connection_opt: Option<Connection>,
+    ) -> usize {
+        let ctx = self.ctx();
+        let compositor = &ctx.compositor;
 
-        let id = self.next_id.get() as usize; // 新窗口的id
-        self.next_id.set(self.next_id.get() + 1);
-
-        if self.next_id.get() < 0 {
-            self.next_id.set(1);
-        }
+        let id = Self::allocate_id(&self.next_id, &self.windows.borrow());
 
         if x < 0 && y < 0 {
-            x = cmp::max(0, (SCREEN_WIDTH as i32 - width) / 2);
-            y = cmp::max(0, (SCREEN_HEIGHT as i32 - height) / 2);
+            let server = &ctx.server;
+            // 为标题栏留出空间
+            let title_height = if flags.contains(WindowFlags::BORDERLESS) {
+                0
+            } else {
+                TITLE_HEIGHT
+            };
+            x = cmp::max(0, (server.screen_width() - image.width()) / 2);
+            y = cmp::max(
+                title_height,
+                (server.screen_height() - image.height() + title_height) / 2,
+            );
         }
 
         // TODO 传入正确的scale
-        // TODO 传入title
-        let window = Window::new(x, y, width, height, 1, image_path);
-
-        // TODO 处理flags
-
-        // TODO 重绘title_rect
-        compositor.request_redraw(window.rect());
+        let mut window = Window::new(x, y, 1, image);
+        window.title = title;
+        window.barderless = flags.contains(WindowFlags::BORDERLESS);
+        window.resizable = flags.contains(WindowFlags::RESIZABLE);
+        window.transparent = flags.contains(WindowFlags::TRANSPARENT);
+        window.unclosable = flags.contains(WindowFlags::UNCLOSABLE);
+        if flags.contains(WindowFlags::FRONT) {
+            window.zorder = WindowZOrderMode::Front;
+        } else if flags.contains(WindowFlags::BACK) {
+            window.zorder = WindowZOrderMode::Back;
+        }
 
-        match window.zorder {
-            WindowZOrderMode::Front | WindowZOrderMode::Normal => {
-                self.order.borrow_mut().push_front(id);
-            }
-            WindowZOrderMode::Back => {
-                self.order.borrow_mut().push_back(id);
+        // 告知客户端窗口id和最终位置
+        if let Some(mut connection) = connection_opt {
+            let reply = Reply::WindowCreated {
+                id: id as u32,
+                x,
+                y,
+            };
+            if let Err(err) = connection.send(&reply) {
+                error!("WindowManager failed to reply window creation: {}", err);
             }
+            window.connection_opt = Some(connection);
         }
 
+        compositor.request_redraw(window.frame_rect());
+
+        // 新窗口位于所在层的最上方，并获得焦点
+        let focusable = Self::focusable(&window);
+        self.order.borrow_mut().push_front(id);
         self.windows.borrow_mut().insert(id, window);
+        self.rezbuffer();
+        if focusable {
+            self.focus_window(Some(id));
+        }
 
         // 确保鼠标正确显示
         let mouse_update_event = MouseUpdateEvent {
@@ -157,6 +306,202 @@ impl WindowManager {
         };
 
         self.handle_mouse_update_event(mouse_update_event);
+
+        id
+    }
+
+    /// # 函数功能
+    /// 分配新窗口的id，id递增分配，回绕后跳过仍在使用的id
+    ///
+    /// ## 参数
+    /// - next_id: 下一个待分配的id
+    /// - windows: 现有窗口
+    ///
+    /// ## 返回值
+    /// 未被任何现有窗口使用的id
+    fn allocate_id(next_id: &Cell<usize>, windows: &BTreeMap<usize, Window>) -> usize {
+        loop {
+            let id = next_id.get();
+            next_id.set(id.wrapping_add(1));
+            if !windows.contains_key(&id) {
+                return id;
+            }
+        }
+    }
+
+    /// # 函数功能
+    /// 关闭窗口：通知客户端后销毁窗口并断开连接
+    ///
+    /// ## 参数
+    /// - id: 窗口id
+    pub fn window_close(&self, id: usize) {
+        if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+            window.send_event(QuitEvent.to_event());
+            window.flush_events();
+            if let Some(connection) = window.connection_opt.as_mut() {
+                // 尽量送达关闭事件，失败时客户端也会因连接断开而得知窗口已关闭
+                let _ = connection.flush();
+            }
+        }
+        self.window_remove(id);
+    }
+
+    /// # 函数功能
+    /// 移除窗口：释放窗体缓冲区和连接，重绘窗口原来占据的区域，并修复焦点和窗口顺序
+    /// 窗口id在移除后才可能被重新分配
+    ///
+    /// ## 参数
+    /// - id: 窗口id
+    pub fn window_remove(&self, id: usize) {
+        let window = match self.windows.borrow_mut().remove(&id) {
+            Some(window) => window,
+            None => return,
+        };
+        let ctx = self.ctx();
+        let compositor = &ctx.compositor;
+        compositor.request_redraw(window.hit_rect());
+        // 释放共享内存映射、标题栏缓存并关闭连接
+        drop(window);
+
+        self.order.borrow_mut().retain(|&order_id| order_id != id);
+        self.rezbuffer();
+
+        // 取消针对该窗口的拖拽操作
+        if let Some(drag) = self.drag.get().filter(|drag| drag.id() == id) {
+            if let DragState::Resize { current, .. } = drag {
+                self.request_preview_redraw(current);
+            }
+            self.drag.set(None);
+        }
+
+        if self.focused.get() == Some(id) {
+            self.focused.set(None);
+            self.focus_window(self.topmost_focusable());
+        }
+
+        if self.grab.get() == Some(id) {
+            self.grab.set(None);
+        }
+        if self.hover.get() == Some(id) {
+            self.hover.set(None);
+        }
+
+        // 鼠标下方的窗口可能已经改变
+        let (x, y) = (self.cursor_x.get(), self.cursor_y.get());
+        self.update_cursor(x, y, self.cursor_kind_at(x, y));
+        self.update_hover(false);
+    }
+
+    /// 获得聚焦窗口的id
+    pub fn focused_window(&self) -> Option<usize> {
+        self.focused.get()
+    }
+
+    /// 窗口能否获得焦点，背景窗口(如桌面)不获得焦点
+    fn focusable(window: &Window) -> bool {
+        window.zorder != WindowZOrderMode::Back
+    }
+
+    /// 最上层的可聚焦窗口
+    fn topmost_focusable(&self) -> Option<usize> {
+        let windows = self.windows.borrow();
+        self.zbuffer
+            .borrow()
+            .iter()
+            .map(|&(id, _, _)| id)
+            .find(|id| windows.get(id).is_some_and(Self::focusable))
+    }
+
+    /// # 函数功能
+    /// 改变聚焦窗口，向失去和获得焦点的窗口发送焦点事件并重绘其标题栏
+    ///
+    /// ## 参数
+    /// - id_opt: 新的聚焦窗口id，为None时没有窗口获得焦点
+    pub fn focus_window(&self, id_opt: Option<usize>) {
+        let id_opt = id_opt.filter(|id| self.windows.borrow().get(id).is_some_and(Self::focusable));
+        let previous = self.focused.replace(id_opt);
+        if previous == id_opt {
+            return;
+        }
+
+        let ctx = self.ctx();
+        let compositor = &ctx.compositor;
+        let mut windows = self.windows.borrow_mut();
+        for (id_opt, focused) in [(previous, false), (id_opt, true)] {
+            if let Some(window) = id_opt.and_then(|id| windows.get_mut(&id)) {
+                compositor.request_redraw(window.title_rect());
+                window.send_event(FocusEvent { focused }.to_event());
+            }
+        }
+    }
+
+    /// # 函数功能
+    /// 将窗口提升到所在层的最上方
+    ///
+    /// ## 参数
+    /// - id: 窗口id
+    pub fn raise_window(&self, id: usize) {
+        self.restack_window(id, true);
+    }
+
+    /// # 函数功能
+    /// 将窗口降低到所在层的最下方
+    ///
+    /// ## 参数
+    /// - id: 窗口id
+    pub fn lower_window(&self, id: usize) {
+        self.restack_window(id, false);
+    }
+
+    /// 将窗口移到窗口顺序的最前(上方)或最后(下方)
+    fn restack_window(&self, id: usize, top: bool) {
+        let frame_rect = match self.windows.borrow().get(&id) {
+            Some(window) => window.frame_rect(),
+            None => return,
+        };
+
+        {
+            let mut order = self.order.borrow_mut();
+            let already = if top {
+                order.front() == Some(&id)
+            } else {
+                order.back() == Some(&id)
+            };
+            if already {
+                return;
+            }
+            order.retain(|&order_id| order_id != id);
+            if top {
+                order.push_front(id);
+            } else {
+                order.push_back(id);
+            }
+        }
+
+        self.rezbuffer();
+        self.ctx().compositor.request_redraw(frame_rect);
+    }
+
+    /// # 函数功能
+    /// 将窗口移到指定的层，移到背景层的聚焦窗口会失去焦点
+    ///
+    /// ## 参数
+    /// - id: 窗口id
+    /// - zorder: 窗口所在的层
+    pub fn set_window_zorder(&self, id: usize, zorder: WindowZOrderMode) {
+        match self.windows.borrow_mut().get_mut(&id) {
+            Some(window) if window.zorder != zorder => {
+                window.zorder = zorder;
+                self.ctx().compositor.request_redraw(window.frame_rect());
+            }
+            _ => return,
+        }
+        self.rezbuffer();
+
+        if zorder == WindowZOrderMode::Back && self.focused.get() == Some(id) {
+            self.focused.set(None);
+            self.focus_window(self.topmost_focusable());
+        }
     }
 
     /// 发送事件
@@ -170,13 +515,317 @@ impl WindowManager {
     }
 
     /// 处理所有事件
+    /// 按到达顺序处理，处理过程中产生的新事件在下一轮处理
     pub fn handle_all_events(&self) {
-        while let Some(event) = self.events.borrow_mut().pop() {
-            self.handle_event(event);
+        loop {
+            let events = mem::take(&mut *self.events.borrow_mut());
+            if events.is_empty() {
+                break;
+            }
+            for event in events {
+                self.handle_event(event);
+            }
+        }
+    }
+
+    /// 轮询所有窗口通信管道
+    pub fn polling_all_windows(&self) {
+        self.accept_connections();
+
+        // 处理尚未创建窗口的连接
+        let connections = mem::take(&mut *self.connections.borrow_mut());
+        for mut connection in connections {
+            match connection
+                .flush()
+                .and_then(|_| connection.receive::<Request>())
+            {
+                Ok(requests) => self.handle_pending_requests(connection, requests),
+                Err(err) => {
+                    if err.kind() != io::ErrorKind::UnexpectedEof {
+                        error!("WindowManager failed to receive request: {}", err);
+                    }
+                }
+            }
+        }
+
+        // 发送窗口事件队列并写出积压的回复，处理已有窗口的请求
+        // 长期不读取数据的客户端会积压过多数据，此时断开连接
+        let ids: Vec<usize> = self.windows.borrow().keys().cloned().collect();
+        for id in ids {
+            let result = match self.windows.borrow_mut().get_mut(&id) {
+                Some(window) => {
+                    window.flush_events();
+                    match window.connection_opt.as_mut() {
+                        Some(connection) => connection
+                            .flush()
+                            .and_then(|_| connection.receive::<Request>()),
+                        None => continue,
+                    }
+                }
+                None => continue,
+            };
+
+            match result {
+                Ok(requests) => {
+                    for request in requests {
+                        self.handle_request(id, request);
+                    }
+                }
+                Err(err) => {
+                    if err.kind() != io::ErrorKind::UnexpectedEof {
+                        error!(
+                            "WindowManager failed to communicate with window {}: {}",
+                            id, err
+                        );
+                    }
+                    self.window_remove(id);
+                }
+            }
+        }
+    }
+
+    /// 发送所有窗口的事件队列，尽可能写出积压的回复
+    /// 写出失败的连接在下一次`polling_all_windows`时断开
+    pub fn flush_all_windows(&self) {
+        for window in self.windows.borrow_mut().values_mut() {
+            window.flush_events();
+            if let Some(connection) = window.connection_opt.as_mut() {
+                let _ = connection.flush();
+            }
+        }
+        for connection in self.connections.borrow_mut().iter_mut() {
+            let _ = connection.flush();
+        }
+    }
+
+    /// 登记监听套接字和所有客户端连接的文件描述符，有积压的发送数据时同时等待可写
+    pub fn register_fds(&self, poller: &mut Poller) {
+        if let Some(listener) = self.listener.as_ref() {
+            poller.add(listener.as_raw_fd(), false);
+        }
+        for connection in self.connections.borrow().iter() {
+            poller.add(connection.stream().as_raw_fd(), connection.has_pending());
+        }
+        for window in self.windows.borrow().values() {
+            if let Some(connection) = window.connection_opt.as_ref() {
+                poller.add(connection.stream().as_raw_fd(), connection.has_pending());
+            }
+        }
+    }
+
+    /// 接受所有等待中的客户端连接
+    fn accept_connections(&self) {
+        let listener = match self.listener.as_ref() {
+            Some(listener) => listener,
+            None => return,
+        };
+
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let mut connection = Connection::new(stream);
+                    match connection.set_nonblocking(true) {
+                        Ok(()) => self.connections.borrow_mut().push(connection),
+                        Err(err) => error!(
+                            "WindowManager failed to set connection nonblocking: {}",
+                            err
+                        ),
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) => {
+                    error!("WindowManager failed to accept connection: {}", err);
+                    break;
+                }
+            }
+        }
+    }
+
+    /// # 函数功能
+    /// 处理尚未创建窗口的连接发来的请求
+    ///
+    /// ## 参数
+    /// - connection: 客户端连接
+    /// - requests: 请求数组
+    fn handle_pending_requests(&self, mut connection: Connection, requests: Vec<Request>) {
+        // 屏幕信息查询和配置重载不依赖窗口，直接处理
+        let requests: Vec<Request> = requests
+            .into_iter()
+            .filter(|request| match request {
+                Request::ScreenInfo => {
+                    self.reply_screen_info(&mut connection);
+                    false
+                }
+                Request::ConfigReload => {
+                    self.ctx().server.reload_config();
+                    false
+                }
+                _ => true,
+            })
+            .collect();
+
+        let mut connection_opt = Some(connection);
+        let mut id_opt = None;
+
+        for request in requests {
+            // 窗口创建后，剩余的请求交给该窗口处理
+            if let Some(id) = id_opt {
+                self.handle_request(id, request);
+                continue;
+            }
+
+            match request {
+                Request::WindowNew {
+                    x,
+                    y,
+                    width,
+                    height,
+                    flags,
+                    title,
+                } => {
+                    let fd_opt = connection_opt.as_mut().and_then(Connection::take_fd);
+                    let image = match Self::open_shared_image(width, height, fd_opt) {
+                        Some(image) => image,
+                        None => return,
+                    };
+                    id_opt =
+                        Some(self.window_new(x, y, flags, title, image, connection_opt.take()));
+                }
+                request => error!(
+                    "WindowManager received {:?} before window creation",
+                    request
+                ),
+            }
+        }
+
+        if let Some(connection) = connection_opt {
+            self.connections.borrow_mut().push(connection);
         }
     }
 
-    pub fn polling_all_windows(&self) {}
+    /// # 函数功能
+    /// 处理窗口的请求
+    ///
+    /// ## 参数
+    /// - id: 窗口id
+    /// - request: 请求
+    fn handle_request(&self, id: usize, request: Request) {
+        let ctx = self.ctx();
+        let compositor = &ctx.compositor;
+
+        match request {
+            Request::WindowNew { .. } => {
+                error!("WindowManager window {} requested a new window", id);
+            }
+            Request::WindowDestroy => self.window_remove(id),
+            Request::ScreenInfo => {
+                if let Some(connection) = self
+                    .windows
+                    .borrow_mut()
+                    .get_mut(&id)
+                    .and_then(|window| window.connection_opt.as_mut())
+                {
+                    self.reply_screen_info(connection);
+                }
+            }
+            Request::ConfigReload => self.ctx().server.reload_config(),
+            Request::WindowSetCursor { cursor } => {
+                if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+                    window.cursor = cursor.into();
+                }
+                // 鼠标可能正位于该窗口内
+                let (x, y) = (self.cursor_x.get(), self.cursor_y.get());
+                self.update_cursor(x, y, self.cursor_kind_at(x, y));
+            }
+            Request::WindowMove { x, y } => self.move_window(id, x, y),
+            Request::WindowRaise => self.raise_window(id),
+            Request::WindowLower => self.lower_window(id),
+            Request::WindowSetLayer { layer } => self.set_window_zorder(id, layer.into()),
+            Request::WindowSetSizeLimits {
+                min_width,
+                min_height,
+                max_width,
+                max_height,
+            } => {
+                if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+                    window.set_size_limits((min_width, min_height), (max_width, max_height));
+                }
+            }
+            Request::WindowResize { width, height } => {
+                let fd_opt = self
+                    .windows
+                    .borrow_mut()
+                    .get_mut(&id)
+                    .and_then(|window| window.connection_opt.as_mut())
+                    .and_then(Connection::take_fd);
+                let image = match Self::open_shared_image(width, height, fd_opt) {
+                    Some(image) => image,
+                    None => return,
+                };
+                if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+                    compositor.request_redraw(window.frame_rect());
+                    window.image = image;
+                    compositor.request_redraw(window.frame_rect());
+                }
+            }
+            Request::WindowSetTitle { title } => {
+                if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+                    window.set_title(title);
+                    compositor.request_redraw(window.title_rect());
+                }
+            }
+            Request::WindowDamage {
+                x,
+                y,
+                width,
+                height,
+            } => {
+                if let Some(window) = self.windows.borrow().get(&id) {
+                    let rect = Rect::new(x, y, width as i32, height as i32);
+                    compositor.request_redraw(window.damage(&rect));
+                }
+            }
+        }
+    }
+
+    /// 向客户端回复屏幕信息
+    fn reply_screen_info(&self, connection: &mut Connection) {
+        let reply = Reply::ScreenInfo(self.ctx().server.screen_info);
+        if let Err(err) = connection.send(&reply) {
+            error!("WindowManager failed to send screen info: {}", err);
+        }
+    }
+
+    /// # 函数功能
+    /// 映射客户端创建的共享内存作为窗体图像
+    ///
+    /// ## 参数
+    /// - width: 图像宽度
+    /// - height: 图像高度
+    /// - fd_opt: 随请求发来的共享内存文件描述符
+    ///
+    /// ## 返回值
+    /// 映射成功时返回图像
+    fn open_shared_image(width: u32, height: u32, fd_opt: Option<OwnedFd>) -> Option<Image> {
+        let fd = match fd_opt {
+            Some(fd) => fd,
+            None => {
+                error!("WindowManager received no shared memory with the request");
+                return None;
+            }
+        };
+
+        match buffer_len(width, height).and_then(|len| SharedMemory::from_fd(fd, len)) {
+            Ok(shm) => Some(Image::from_shared(width as i32, height as i32, shm)),
+            Err(err) => {
+                error!(
+                    "WindowManager failed to map {}x{} shared memory: {}",
+                    width, height, err
+                );
+                None
+            }
+        }
+    }
 
     /// # 函数功能
     /// 处理事件
@@ -188,58 +837,519 @@ impl WindowManager {
         match event_union.to_option() {
             EventOption::MouseRelative(event) => self.handle_mouse_relative_event(event),
             EventOption::Button(event) => self.handle_button_event(event),
+            EventOption::MouseUpdate(event) => {
+                let server = &self.ctx().server;
+                let x = event.x.clamp(0, cmp::max(0, server.screen_width() - 1));
+                let y = event.y.clamp(0, cmp::max(0, server.screen_height() - 1));
+                self.handle_mouse_update_event(MouseUpdateEvent { x, y });
+            }
             EventOption::Key(event) => self.handle_key_event(event),
+            EventOption::Scroll(event) => self.handle_scroll_event(event),
             EventOption::WindowMove(event) => self.handle_window_move_event(event),
             EventOption::WindowResize(event) => self.handle_window_resize_event(event),
             EventOption::Unknown(event) => {
-                println!("[Error] WindowManager handle unkonwn event {:?}", event)
+                error!("WindowManager handle unkonwn event {:?}", event)
             }
-            EventOption::None => {}
+            // 焦点、关闭、进入和离开事件只由服务器发往客户端
+            EventOption::Focus(_)
+            | EventOption::Quit(_)
+            | EventOption::Enter(_)
+            | EventOption::Leave(_)
+            | EventOption::None => {}
         }
     }
 
-    /// 处理鼠标相对移动事件
+    /// 处理鼠标相对移动事件，转换为新的鼠标位置
     fn handle_mouse_relative_event(&self, event: MouseRelativeEvent) {
-        // TODO: 将事件传递给窗口，同时考虑窗口对鼠标位置的影响
-
-        let max_x: i32 = SCREEN_WIDTH as i32;
-        let max_y: i32 = SCREEN_HEIGHT as i32;
-        let cursor_rect = self.cursor_rect();
-
-        //防止鼠标出界
-        let x = cmp::max(
-            0,
-            cmp::min(max_x - cursor_rect.width(), self.cursor_x.get() + event.dx),
-        );
-        let y = cmp::max(
-            0,
-            cmp::min(max_y - cursor_rect.height(), self.cursor_y.get() - event.dy),
-        ); // 原点在左上角，向上为负
+        let ctx = self.ctx();
+        let server = &ctx.server;
+        let max_x: i32 = server.screen_width() - 1;
+        let max_y: i32 = server.screen_height() - 1;
+
+        //防止鼠标(热点)出界
+        let x = cmp::max(0, cmp::min(max_x, self.cursor_x.get() + event.dx));
+        let y = cmp::max(0, cmp::min(max_y, self.cursor_y.get() - event.dy)); // 原点在左上角，向上为负
 
         self.handle_mouse_update_event(MouseUpdateEvent { x, y });
     }
 
     /// 处理鼠标移动事件
     fn handle_mouse_update_event(&self, event: MouseUpdateEvent) {
-        let /*mut*/ new_cursor = CursorKind::Normal;
-
-        // TODO: 判断新的鼠标状态
-        // TODO: 处理拖拽等事件，传递给相应窗口
+        let new_cursor = match self.drag.get() {
+            Some(DragState::Move {
+                id,
+                offset_x,
+                offset_y,
+            }) => {
+                self.move_window(id, event.x - offset_x, event.y - offset_y);
+                CursorKind::Move
+            }
+            Some(DragState::Resize {
+                id,
+                edge,
+                start_x,
+                start_y,
+                origin,
+                current,
+            }) => {
+                let (min, max) = match self.windows.borrow().get(&id) {
+                    Some(window) => (window.min_size, window.max_size),
+                    None => return,
+                };
+                let rect = edge.apply(origin, event.x - start_x, event.y - start_y, min, max);
+                if rect != current {
+                    self.request_preview_redraw(current);
+                    self.request_preview_redraw(rect);
+                    self.drag.set(Some(DragState::Resize {
+                        id,
+                        edge,
+                        start_x,
+                        start_y,
+                        origin,
+                        current: rect,
+                    }));
+                }
+                edge.cursor()
+            }
+            Some(DragState::MaxButton { .. }) | Some(DragState::CloseButton { .. }) => {
+                self.cursor_kind_at(event.x, event.y)
+            }
+            None => {
+                // 焦点跟随鼠标时只在鼠标进入可聚焦窗口时改变焦点，移到桌面上时保持原焦点
+                let follows = self.ctx().server.config.borrow().focus_follows_mouse;
+                if follows {
+                    if let Some(id) = self.window_at(event.x, event.y) {
+                        if self.windows.borrow().get(&id).is_some_and(Self::focusable) {
+                            self.focus_window(Some(id));
+                        }
+                    }
+                }
+                self.cursor_kind_at(event.x, event.y)
+            }
+        };
 
         self.update_cursor(event.x, event.y, new_cursor);
+        self.update_hover(true);
     }
 
-    // TODO
-    fn handle_button_event(&self, _event: ButtonEvent) {}
+    /// # 函数功能
+    /// 查找某一点所在的最上层窗口的窗体(不包括标题栏和边框)
+    ///
+    /// ## 参数
+    /// - x: x坐标
+    /// - y: y坐标
+    ///
+    /// ## 返回值
+    /// 窗口id，该点不在任何窗体内时返回None
+    fn content_window_at(&self, x: i32, y: i32) -> Option<usize> {
+        self.window_at(x, y).filter(|id| {
+            self.windows
+                .borrow()
+                .get(id)
+                .is_some_and(|window| window.rect().contains(x, y))
+        })
+    }
 
-    // TODO
-    fn handle_key_event(&self, _event: KeyEvent) {}
+    /// 接收鼠标事件的窗口：有抓取的窗口时为该窗口，拖拽窗口时没有，否则为鼠标下方的窗体
+    fn pointer_target(&self) -> Option<usize> {
+        if self.drag.get().is_some() {
+            return None;
+        }
+        self.grab
+            .get()
+            .or_else(|| self.content_window_at(self.cursor_x.get(), self.cursor_y.get()))
+    }
 
-    // TODO
-    fn handle_window_move_event(&self, _event: WindowMoveEvent) {}
+    /// # 函数功能
+    /// 更新鼠标所在的窗口
+    /// 鼠标跨越窗口边界时向原窗口发送离开事件，向新窗口发送进入事件，
+    /// 否则在鼠标移动时向所在窗口发送窗口内坐标
+    ///
+    /// ## 参数
+    /// - moved: 鼠标是否移动
+    fn update_hover(&self, moved: bool) {
+        let target = self.pointer_target();
+        let previous = self.hover.replace(target);
+        let mut windows = self.windows.borrow_mut();
+        let (x, y) = (self.cursor_x.get(), self.cursor_y.get());
 
-    // TODO
-    fn handle_window_resize_event(&self, _event: WindowResizeEvent) {}
+        if previous != target {
+            if let Some(window) = previous.and_then(|id| windows.get_mut(&id)) {
+                window.send_event(LeaveEvent.to_event());
+            }
+            if let Some(window) = target.and_then(|id| windows.get_mut(&id)) {
+                let event = EnterEvent {
+                    x: x - window.x,
+                    y: y - window.y,
+                };
+                window.send_event(event.to_event());
+            }
+        } else if moved {
+            if let Some(window) = target.and_then(|id| windows.get_mut(&id)) {
+                let event = MouseUpdateEvent {
+                    x: x - window.x,
+                    y: y - window.y,
+                };
+                window.send_event(event.to_event());
+            }
+        }
+    }
+
+    /// # 函数功能
+    /// 查找某一点所在的最上层窗口(包括标题栏)
+    ///
+    /// ## 参数
+    /// - x: x坐标
+    /// - y: y坐标
+    ///
+    /// ## 返回值
+    /// 窗口id，该点不在任何窗口内时返回None
+    fn window_at(&self, x: i32, y: i32) -> Option<usize> {
+        let windows = self.windows.borrow();
+        self.zbuffer
+            .borrow()
+            .iter()
+            .map(|&(id, _, _)| id)
+            .find(|id| {
+                windows
+                    .get(id)
+                    .is_some_and(|window| window.hit_rect().contains(x, y))
+            })
+    }
+
+    /// # 函数功能
+    /// 移动窗口，重绘移动前后的区域
+    /// 不通知客户端，拖动过程中只在结束时调用`notify_position`发送最终位置
+    ///
+    /// ## 参数
+    /// - id: 窗口id
+    /// - x: 新的x坐标
+    /// - y: 新的y坐标
+    fn move_window(&self, id: usize, x: i32, y: i32) {
+        let ctx = self.ctx();
+        let compositor = &ctx.compositor;
+
+        if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+            if window.x == x && window.y == y {
+                return;
+            }
+
+            compositor.request_redraw(window.frame_rect());
+            window.x = x;
+            window.y = y;
+            compositor.request_redraw(window.frame_rect());
+        }
+    }
+
+    /// # 函数功能
+    /// 正在拖动缩放时，返回预览边框包围的区域(包括标题栏)
+    ///
+    /// ## 返回值
+    /// 预览区域，没有正在进行的缩放时返回None
+    pub fn resize_preview(&self) -> Option<Rect> {
+        match self.drag.get() {
+            Some(DragState::Resize { current, .. }) => Some(Self::preview_rect(current)),
+            _ => None,
+        }
+    }
+
+    /// 窗体矩形对应的预览区域
+    fn preview_rect(rect: Rect) -> Rect {
+        Rect::new(
+            rect.left(),
+            rect.top() - TITLE_HEIGHT,
+            rect.width(),
+            rect.height() + TITLE_HEIGHT,
+        )
+    }
+
+    /// 重绘窗体矩形对应的预览边框
+    fn request_preview_redraw(&self, rect: Rect) {
+        let ctx = self.ctx();
+        let compositor = &ctx.compositor;
+        for edge in Self::preview_rect(rect).outline(RESIZE_OUTLINE) {
+            compositor.request_redraw(edge);
+        }
+    }
+
+    /// # 函数功能
+    /// 结束拖动缩放：移动窗口并将新的大小通知客户端
+    /// 客户端按新的大小重新分配缓冲区后，窗体才会以新的大小显示
+    ///
+    /// ## 参数
+    /// - id: 窗口id
+    /// - rect: 缩放后的窗体矩形
+    fn finish_resize(&self, id: usize, rect: Rect) {
+        self.request_preview_redraw(rect);
+
+        let old_rect = match self.windows.borrow_mut().get_mut(&id) {
+            Some(window) => {
+                // 手动缩放后不再处于最大化状态
+                window.restore_rect = None;
+                window.rect()
+            }
+            None => return,
+        };
+        if rect == old_rect {
+            return;
+        }
+
+        if rect.left() != old_rect.left() || rect.top() != old_rect.top() {
+            self.move_window(id, rect.left(), rect.top());
+            self.notify_position(id);
+        }
+        if rect.width() != old_rect.width() || rect.height() != old_rect.height() {
+            if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+                let event = WindowResizeEvent {
+                    width: rect.width() as u32,
+                    height: rect.height() as u32,
+                };
+                window.send_event(event.to_event());
+            }
+        }
+    }
+
+    /// 将窗口的当前位置通知客户端
+    fn notify_position(&self, id: usize) {
+        if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+            let event = WindowMoveEvent {
+                x: window.x,
+                y: window.y,
+            };
+            window.send_event(event.to_event());
+        }
+    }
+
+    /// # 函数功能
+    /// 根据鼠标下方的内容确定鼠标样式
+    ///
+    /// ## 参数
+    /// - x: 鼠标x坐标
+    /// - y: 鼠标y坐标
+    ///
+    /// ## 返回值
+    /// 鼠标样式
+    fn cursor_kind_at(&self, x: i32, y: i32) -> CursorKind {
+        let windows = self.windows.borrow();
+
+        // zbuffer中靠前的窗口位于上层
+        for &(id, _, _) in self.zbuffer.borrow().iter() {
+            let window = match windows.get(&id) {
+                Some(window) => window,
+                None => continue,
+            };
+
+            if window.rect().contains(x, y) {
+                return window.cursor;
+            }
+            if window.title_rect().contains(x, y) {
+                return CursorKind::Normal;
+            }
+            if let Some(edge) = window.resize_edge(x, y) {
+                return edge.cursor();
+            }
+        }
+
+        CursorKind::Normal
+    }
+
+    /// # 函数功能
+    /// 切换窗口的最大化状态
+    /// 最大化时窗体占满标题栏以下的整个屏幕，还原时回到最大化之前的位置和大小
+    /// 服务器只移动窗口并通知客户端新的大小，由客户端重新分配缓冲区
+    ///
+    /// ## 参数
+    /// - id: 窗口id
+    fn toggle_maximize(&self, id: usize) {
+        let ctx = self.ctx();
+        let server = &ctx.server;
+
+        let target = {
+            let mut windows = self.windows.borrow_mut();
+            let window = match windows.get_mut(&id) {
+                Some(window) if window.resizable => window,
+                _ => return,
+            };
+            match window.restore_rect.take() {
+                Some(rect) => rect,
+                None => {
+                    window.restore_rect = Some(window.rect());
+                    let width = cmp::min(server.screen_width(), window.max_size.0 as i32);
+                    let height = cmp::min(
+                        server.screen_height() - TITLE_HEIGHT,
+                        window.max_size.1 as i32,
+                    );
+                    Rect::new(0, TITLE_HEIGHT, cmp::max(0, width), cmp::max(0, height))
+                }
+            }
+        };
+
+        self.move_window(id, target.left(), target.top());
+        self.notify_position(id);
+        if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+            let event = WindowResizeEvent {
+                width: target.width() as u32,
+                height: target.height() as u32,
+            };
+            window.send_event(event.to_event());
+        }
+    }
+
+    /// 处理鼠标按键事件
+    fn handle_button_event(&self, event: ButtonEvent) {
+        let previous = self.buttons.replace(event);
+        let (x, y) = (self.cursor_x.get(), self.cursor_y.get());
+
+        // 任一按键按下时提升鼠标下方的窗口；点击聚焦模式下同时聚焦该窗口，点击桌面时取消聚焦
+        let pressed = (event.left && !previous.left)
+            || (event.right && !previous.right)
+            || (event.middle && !previous.middle)
+            || (event.back && !previous.back)
+            || (event.forward && !previous.forward);
+        if pressed {
+            let id_opt = self.window_at(x, y);
+            if let Some(id) = id_opt {
+                self.raise_window(id);
+            }
+            if !self.ctx().server.config.borrow().focus_follows_mouse {
+                self.focus_window(id_opt);
+            }
+        }
+
+        if event.left && !previous.left {
+            // 左键按下：在边缘按下时开始缩放，在按钮上按下时记录按钮，在标题栏其他位置按下时开始拖动窗口
+            if let Some(id) = self.window_at(x, y) {
+                let windows = self.windows.borrow();
+                let window = &windows[&id];
+                if let Some(edge) = window.resize_edge(x, y) {
+                    self.drag.set(Some(DragState::Resize {
+                        id,
+                        edge,
+                        start_x: x,
+                        start_y: y,
+                        origin: window.rect(),
+                        current: window.rect(),
+                    }));
+                    self.request_preview_redraw(window.rect());
+                } else if window.max_rect().contains(x, y) {
+                    self.drag.set(Some(DragState::MaxButton { id }));
+                } else if window.close_rect().contains(x, y) {
+                    self.drag.set(Some(DragState::CloseButton { id }));
+                } else if window.title_rect().contains(x, y) {
+                    self.drag.set(Some(DragState::Move {
+                        id,
+                        offset_x: x - window.x,
+                        offset_y: y - window.y,
+                    }));
+                }
+            }
+        } else if !event.left && previous.left {
+            // 左键松开：结束拖动，恢复鼠标下方内容对应的样式
+            match self.drag.take() {
+                Some(DragState::MaxButton { id }) => {
+                    let on_button = self
+                        .windows
+                        .borrow()
+                        .get(&id)
+                        .is_some_and(|window| window.max_rect().contains(x, y));
+                    if on_button {
+                        self.toggle_maximize(id);
+                    }
+                }
+                Some(DragState::CloseButton { id }) => {
+                    let on_button = self
+                        .windows
+                        .borrow()
+                        .get(&id)
+                        .is_some_and(|window| window.close_rect().contains(x, y));
+                    if on_button {
+                        self.window_close(id);
+                    }
+                }
+                Some(DragState::Move { id, .. }) => {
+                    self.notify_position(id);
+                    self.update_cursor(x, y, self.cursor_kind_at(x, y));
+                }
+                Some(DragState::Resize { id, current, .. }) => {
+                    self.finish_resize(id, current);
+                    self.update_cursor(x, y, self.cursor_kind_at(x, y));
+                }
+                None => {}
+            }
+        }
+
+        // 不在拖拽窗口时，将按键事件发送给鼠标所在的窗体
+        if self.drag.get().is_none() {
+            if pressed && self.grab.get().is_none() {
+                self.grab.set(self.content_window_at(x, y));
+            }
+            // 窗口层次可能已经改变，先发送进入事件
+            self.update_hover(false);
+            if let Some(id) = self.grab.get() {
+                if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+                    window.send_event(event.to_event());
+                }
+            }
+        }
+        if !(event.left || event.right || event.middle || event.back || event.forward) {
+            self.grab.set(None);
+        }
+        self.update_hover(false);
+    }
+
+    /// 处理键盘按键事件，只发送给聚焦窗口
+    fn handle_key_event(&self, event: KeyEvent) {
+        let id = match self.focused.get() {
+            Some(id) => id,
+            None => return,
+        };
+        if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+            window.send_event(event.to_event());
+        }
+    }
+
+    /// 处理滚轮事件，发送给鼠标所在的窗体
+    /// 鼠标在标题栏或边框上时不发送
+    fn handle_scroll_event(&self, event: ScrollEvent) {
+        let id = match self.pointer_target() {
+            Some(id) => id,
+            None => return,
+        };
+        if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+            window.send_event(event.to_event());
+        }
+    }
+
+    /// 处理窗口移动事件，移动聚焦窗口并通知客户端
+    fn handle_window_move_event(&self, event: WindowMoveEvent) {
+        let id = match self.focused.get() {
+            Some(id) => id,
+            None => return,
+        };
+        self.move_window(id, event.x, event.y);
+        self.notify_position(id);
+        self.update_hover(false);
+    }
+
+    /// 处理窗口缩放事件，按大小限制缩放聚焦窗口并通知客户端
+    /// 客户端按新的大小重新分配缓冲区后，窗体才会以新的大小显示
+    fn handle_window_resize_event(&self, event: WindowResizeEvent) {
+        let id = match self.focused.get() {
+            Some(id) => id,
+            None => return,
+        };
+        if let Some(window) = self.windows.borrow_mut().get_mut(&id) {
+            if !window.resizable {
+                return;
+            }
+            let event = WindowResizeEvent {
+                width: event.width.clamp(window.min_size.0, window.max_size.0),
+                height: event.height.clamp(window.min_size.1, window.max_size.1),
+            };
+            window.restore_rect = None;
+            window.send_event(event.to_event());
+        }
+    }
 
     /// # 函数功能
     /// 更新鼠标状态
@@ -253,28 +1363,29 @@ impl WindowManager {
 
         if kind != self.cursor_i.get() || x != self.cursor_x.get() || y != self.cursor_y.get() {
             let cursor_rect = self.cursor_rect();
-            compositor().unwrap().request_redraw(cursor_rect);
+            self.ctx().compositor.request_redraw(cursor_rect);
 
             self.cursor_i.set(kind);
             self.cursor_x.set(x);
             self.cursor_y.set(y);
 
             let cursor_rect = self.cursor_rect();
-            compositor().unwrap().request_redraw(cursor_rect);
+            self.ctx().compositor.request_redraw(cursor_rect);
         }
     }
 
     /// # 函数功能
     /// 获得鼠标位置的矩形区域
     pub fn cursor_rect(&self) -> Rect {
-        let server = starry_server().unwrap();
+        let ctx = self.ctx();
+        let server = &ctx.server;
 
-        if let Some(image) = server.cursors.borrow().get(&self.cursor_i.get()) {
+        if let Some(cursor) = server.cursors.borrow().get(&self.cursor_i.get()) {
             return Rect::new(
-                self.cursor_x.get(),
-                self.cursor_y.get(),
-                image.width(),
-                image.height(),
+                self.cursor_x.get() - cursor.hotspot_x,
+                self.cursor_y.get() - cursor.hotspot_y,
+                cursor.image.width(),
+                cursor.image.height(),
             );
         }
 
@@ -282,22 +1393,46 @@ impl WindowManager {
     }
 
     /// 更新zbuffer
+    /// 按层从上到下排列，同一层内保持窗口顺序
     pub fn rezbuffer(&self) {
-        self.zbuffer.borrow_mut().clear();
+        let windows = self.windows.borrow();
+        let mut zbuffer = self.zbuffer.borrow_mut();
+        zbuffer.clear();
 
-        let order = self.order.borrow_mut();
-        for index in 0..order.len() {
-            let id = order[index];
-            let window_z = self
-                .windows
-                .borrow()
-                .get(&index)
-                .expect("窗口不存在!")
-                .zorder
-                .clone();
-            self.zbuffer.borrow_mut().push((id, window_z, index));
+        for (index, &id) in self.order.borrow().iter().enumerate() {
+            if let Some(window) = windows.get(&id) {
+                zbuffer.push((id, window.zorder, index));
+            }
         }
 
-        self.zbuffer.borrow_mut().sort_by(|a, b| b.1.cmp(&a.1));
+        // 稳定排序，同一层的窗口保持在order中的先后顺序
+        zbuffer.sort_by_key(|&(_, zorder, _)| Reverse(zorder));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window() -> Window {
+        Window::new(0, 0, 1, Image::new(1, 1))
+    }
+
+    #[test]
+    fn allocate_id_skips_live_windows() {
+        let mut windows = BTreeMap::new();
+        windows.insert(1, window());
+        windows.insert(2, window());
+
+        let next_id = Cell::new(0);
+        assert_eq!(WindowManager::allocate_id(&next_id, &windows), 0);
+        assert_eq!(WindowManager::allocate_id(&next_id, &windows), 3);
+
+        // 回绕后不会分配仍在使用的id
+        windows.insert(usize::MAX, window());
+        windows.insert(0, window());
+        next_id.set(usize::MAX);
+        assert_eq!(WindowManager::allocate_id(&next_id, &windows), 3);
+        assert_eq!(next_id.get(), 4);
     }
 }
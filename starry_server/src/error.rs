@@ -0,0 +1,63 @@
+use std::{error, fmt, io};
+
+use crate::config::ConfigError;
+
+/// 图形服务器的错误
+#[derive(Debug)]
+pub enum StarryError {
+    /// 配置文件错误
+    Config(ConfigError),
+    /// 无法创建显示后端
+    Backend { spec: String, err: io::Error },
+    /// 显示后端报告的屏幕大小无效
+    InvalidScreen { width: u32, height: u32 },
+    /// 无法监听客户端连接
+    Listener { path: String, err: io::Error },
+    /// 无法打开输入设备
+    InputDevice { path: String, err: io::Error },
+    /// 无法打开日志文件
+    LogFile { path: String, err: io::Error },
+}
+
+impl fmt::Display for StarryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StarryError::Config(err) => write!(f, "invalid config {}", err),
+            StarryError::Backend { spec, err } => {
+                write!(f, "failed to create display backend '{}': {}", spec, err)
+            }
+            StarryError::InvalidScreen { width, height } => {
+                write!(f, "invalid screen size {}x{}", width, height)
+            }
+            StarryError::Listener { path, err } => {
+                write!(f, "failed to bind socket '{}': {}", path, err)
+            }
+            StarryError::InputDevice { path, err } => {
+                write!(f, "failed to open input device '{}': {}", path, err)
+            }
+            StarryError::LogFile { path, err } => {
+                write!(f, "failed to open log file '{}': {}", path, err)
+            }
+        }
+    }
+}
+
+impl error::Error for StarryError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            StarryError::Config(ConfigError::Io { err, .. }) => Some(err),
+            StarryError::Config(ConfigError::Parse { .. }) => None,
+            StarryError::InvalidScreen { .. } => None,
+            StarryError::Backend { err, .. }
+            | StarryError::Listener { err, .. }
+            | StarryError::InputDevice { err, .. }
+            | StarryError::LogFile { err, .. } => Some(err),
+        }
+    }
+}
+
+impl From<ConfigError> for StarryError {
+    fn from(err: ConfigError) -> Self {
+        StarryError::Config(err)
+    }
+}
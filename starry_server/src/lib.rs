@@ -1,6 +1,8 @@
 pub mod base;
 pub mod config;
 pub mod core;
+pub mod error;
+pub mod logger;
 
 extern crate bitflags;
 extern crate resize;
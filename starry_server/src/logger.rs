@@ -0,0 +1,92 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    sync::Mutex,
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::error::StarryError;
+
+/// 服务器的日志记录器，输出到标准错误或日志文件
+struct Logger {
+    /// 日志文件，为None时输出到标准错误
+    file: Mutex<Option<File>>,
+}
+
+static LOGGER: Logger = Logger {
+    file: Mutex::new(None),
+};
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let level = match record.level() {
+            Level::Error => "Error",
+            Level::Warn => "Warning",
+            Level::Info => "Info",
+            Level::Debug => "Debug",
+            Level::Trace => "Trace",
+        };
+        let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+        // 日志无法写出时没有其他地方可以报告，直接丢弃
+        let _ = match file.as_mut() {
+            Some(file) => writeln!(file, "[{}] {}", level, record.args()),
+            None => writeln!(io::stderr(), "[{}] {}", level, record.args()),
+        };
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self
+            .file
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .as_mut()
+        {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// 安装日志记录器，在读取配置之前输出Info及以上级别的日志到标准错误
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
+
+/// # 函数功能
+/// 设置日志级别和输出位置
+///
+/// ## 参数
+/// - level: 输出的最低日志级别
+/// - path: 日志文件路径，日志追加到文件末尾，为空时输出到标准错误
+///
+/// ## 返回值
+/// 无法打开日志文件时返回错误，此时保持原来的输出位置
+pub fn configure(level: LevelFilter, path: &str) -> Result<(), StarryError> {
+    log::set_max_level(level);
+
+    let file = if path.is_empty() {
+        None
+    } else {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map(Some)
+            .map_err(|err| StarryError::LogFile {
+                path: path.to_string(),
+                err,
+            })?
+    };
+    *LOGGER.file.lock().unwrap_or_else(|err| err.into_inner()) = file;
+    Ok(())
+}
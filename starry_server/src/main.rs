@@ -1,26 +1,109 @@
-use std::sync::Arc;
+use std::{env, process, sync::Arc};
 
+use log::{error, warn};
+use starry_client::base::protocol::{request_config_reload, SERVER_SOCKET_PATH};
 use starry_server::{
-    base::display::Display,
     config::Config,
-    core::{starry_server, StarryServer, SCREEN_HEIGHT, SCREEN_WIDTH},
+    core::{
+        backend::{create_backend, DEFAULT_SCREEN_HEIGHT, DEFAULT_SCREEN_WIDTH},
+        Context,
+    },
+    error::StarryError,
+    logger,
 };
 
+/// 命令行参数
+struct Args {
+    /// 配置文件路径
+    config: Option<String>,
+    /// 显示后端
+    backend: Option<String>,
+    /// 屏幕大小，仅在无法从显示设备获取时使用
+    size: Option<(u32, u32)>,
+    /// 通知正在运行的服务器重新加载配置后退出
+    reload: bool,
+}
+
+impl Args {
+    fn parse() -> Args {
+        let mut args = Args {
+            config: None,
+            backend: None,
+            size: None,
+            reload: false,
+        };
+        let mut iter = env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "-c" | "--config" => args.config = iter.next(),
+                "-b" | "--backend" => args.backend = iter.next(),
+                "-s" | "--size" => {
+                    args.size = iter.next().and_then(|size| {
+                        let (width, height) = size.split_once('x')?;
+                        Some((width.parse().ok()?, height.parse().ok()?))
+                    });
+                    if args.size.is_none() {
+                        warn!("Invalid screen size, expected WIDTHxHEIGHT");
+                    }
+                }
+                "-r" | "--reload" => args.reload = true,
+                "-h" | "--help" => {
+                    println!("Usage: starry_server [--config path] [--backend fbdev[:path]|headless|dump[:dir[:ppm|png]]] [--size WIDTHxHEIGHT]");
+                    println!("       starry_server --reload");
+                    process::exit(0);
+                }
+                _ => warn!("Unknown argument '{}'", arg),
+            }
+        }
+        args
+    }
+}
+
 fn main() {
-    // TODO 考虑多个显示器
-    let mut displays: Vec<Display> = Vec::new();
-    displays.push(Display::new(
-        0,
-        0,
-        SCREEN_WIDTH as i32,
-        SCREEN_HEIGHT as i32,
-    ));
-
-    // TODO 暂时不考虑配置文件
-    let config: Arc<Config> = Arc::new(Config::default());
+    logger::init();
+    let args = Args::parse();
+
+    if args.reload {
+        match request_config_reload() {
+            Ok(()) => process::exit(0),
+            Err(err) => {
+                error!("Failed to request config reload: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+
+    if let Err(err) = start(args) {
+        error!("Failed to start Starry_Server: {}", err);
+        process::exit(1);
+    }
+}
+
+/// 读取配置，创建显示后端并开启Starry Server，正常运行时不返回
+fn start(args: Args) -> Result<(), StarryError> {
+    let config_path = Config::find_path(args.config.as_deref());
+    let config = Arc::new(Config::load(config_path.as_deref())?);
+    if let Err(err) = logger::configure(config.log_level.filter(), &config.log_file) {
+        error!("{}", err);
+    }
+
+    // 命令行参数优先于配置文件
+    let backend_spec = args
+        .backend
+        .unwrap_or_else(|| config.display_backend.clone());
+    let (width, height) = args
+        .size
+        .unwrap_or((DEFAULT_SCREEN_WIDTH, DEFAULT_SCREEN_HEIGHT));
+    let backend =
+        create_backend(&backend_spec, width, height, config.display_dither).map_err(|err| {
+            StarryError::Backend {
+                spec: backend_spec.clone(),
+                err,
+            }
+        })?;
 
     //开启Starry Server
-    StarryServer::new(config, displays);
-    let server = starry_server().unwrap();
-    server.run();
+    let ctx = Context::new(config, config_path, backend, Some(SERVER_SOCKET_PATH))?;
+    ctx.run();
+    Ok(())
 }
@@ -0,0 +1,53 @@
+#![allow(dead_code)]
+
+use std::{rc::Rc, sync::Arc};
+
+use starry_client::base::{color::Color, protocol::WindowFlags};
+use starry_server::{
+    base::image::Image,
+    config::Config,
+    core::{
+        backend::{headless::HeadlessBackend, DisplayBackend},
+        Context,
+    },
+};
+
+pub const BACKGROUND: Color = Color::rgb(0, 0, 0);
+pub const RED: Color = Color::rgb(255, 0, 0);
+pub const BLUE: Color = Color::rgb(0, 0, 255);
+
+/// 不打开输入设备的配置
+pub fn config() -> Config {
+    Config {
+        mouse_device: String::new(),
+        keyboard_device: String::new(),
+        evdev_devices: Vec::new(),
+        background_color: BACKGROUND,
+        ..Config::default()
+    }
+}
+
+/// 创建使用指定显示后端、不监听套接字的上下文
+pub fn context_with(backend: Box<dyn DisplayBackend>) -> Rc<Context> {
+    Context::new(Arc::new(config()), None, backend, None).unwrap()
+}
+
+/// 创建使用无显示设备后端的上下文
+pub fn context(width: i32, height: i32) -> Rc<Context> {
+    context_with(Box::new(HeadlessBackend::new(width, height)))
+}
+
+/// 创建无边框的纯色窗口
+pub fn window(ctx: &Context, x: i32, y: i32, width: i32, height: i32, color: Color) -> usize {
+    window_with(ctx, x, y, Image::from_color(width, height, color), false)
+}
+
+/// 创建无边框的窗口，transparent为true时与下方的内容混合
+pub fn window_with(ctx: &Context, x: i32, y: i32, image: Image, transparent: bool) -> usize {
+    let mut flags = WindowFlags::BORDERLESS;
+    if transparent {
+        flags |= WindowFlags::TRANSPARENT;
+    }
+    ctx.window_manager
+        .window_new(x, y, flags, String::new(), image, None)
+}
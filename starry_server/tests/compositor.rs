@@ -0,0 +1,146 @@
+use std::{cell::RefCell, rc::Rc};
+
+use starry_client::base::{color::Color, screen::ScreenInfo};
+use starry_server::{
+    base::{display::Display, image::Image, rect::Rect},
+    core::{
+        backend::{headless::HeadlessBackend, DisplayBackend},
+        Context,
+    },
+};
+
+mod common;
+
+use common::{context_with, window, window_with, BACKGROUND, BLUE, RED};
+
+/// 与测试共享画面的无显示设备后端，记录输出的区域
+struct SharedBackend {
+    headless: Rc<RefCell<HeadlessBackend>>,
+    presented: Rc<RefCell<Vec<Rect>>>,
+}
+
+impl DisplayBackend for SharedBackend {
+    fn name(&self) -> &str {
+        "shared"
+    }
+
+    fn screen_info(&self) -> ScreenInfo {
+        self.headless.borrow().screen_info()
+    }
+
+    fn present(&mut self, display: &mut Display, rect: &Rect) {
+        self.presented.borrow_mut().push(*rect);
+        self.headless.borrow_mut().present(display, rect);
+    }
+
+    fn flush(&mut self) {
+        self.headless.borrow_mut().flush();
+    }
+}
+
+struct Fixture {
+    ctx: Rc<Context>,
+    headless: Rc<RefCell<HeadlessBackend>>,
+    presented: Rc<RefCell<Vec<Rect>>>,
+}
+
+impl Fixture {
+    /// 200x100的屏幕，鼠标位于中央(100, 50)，检查像素时避开该位置
+    fn new() -> Self {
+        let headless = Rc::new(RefCell::new(HeadlessBackend::new(200, 100)));
+        let presented = Rc::new(RefCell::new(Vec::new()));
+        let ctx = context_with(Box::new(SharedBackend {
+            headless: headless.clone(),
+            presented: presented.clone(),
+        }));
+        Fixture {
+            ctx,
+            headless,
+            presented,
+        }
+    }
+
+    /// 重绘并返回输出的区域
+    fn redraw(&self) -> Vec<Rect> {
+        self.presented.borrow_mut().clear();
+        self.ctx.compositor.redraw_all();
+        self.presented.borrow().clone()
+    }
+
+    fn pixel(&self, x: i32, y: i32) -> Color {
+        self.headless.borrow().pixel(x, y)
+    }
+}
+
+#[test]
+fn overlapping_windows_are_drawn_in_one_frame() {
+    let fixture = Fixture::new();
+    window(&fixture.ctx, 10, 10, 40, 40, RED);
+    window(&fixture.ctx, 30, 30, 40, 40, BLUE);
+    fixture.redraw();
+
+    assert_eq!(fixture.headless.borrow().frame_count(), 1);
+    assert_eq!(fixture.pixel(5, 5), BACKGROUND);
+    assert_eq!(fixture.pixel(15, 15), RED);
+    assert_eq!(fixture.pixel(49, 29), RED);
+    assert_eq!(fixture.pixel(40, 40), BLUE);
+    assert_eq!(fixture.pixel(69, 69), BLUE);
+    assert_eq!(fixture.pixel(75, 75), BACKGROUND);
+}
+
+#[test]
+fn only_damaged_rects_are_presented() {
+    let fixture = Fixture::new();
+    window(&fixture.ctx, 0, 0, 200, 100, RED);
+    fixture.redraw();
+
+    fixture
+        .ctx
+        .compositor
+        .request_redraw(Rect::new(0, 0, 10, 10));
+    fixture
+        .ctx
+        .compositor
+        .request_redraw(Rect::new(5, 0, 10, 10));
+    fixture
+        .ctx
+        .compositor
+        .request_redraw(Rect::new(150, 80, 10, 10));
+    let presented = fixture.redraw();
+
+    // 重叠的请求被合并，相距较远的请求分别输出
+    assert_eq!(presented.len(), 2);
+    assert!(presented.contains(&Rect::new(0, 0, 15, 10)));
+    assert!(presented.contains(&Rect::new(150, 80, 10, 10)));
+
+    // 没有请求时不输出
+    assert!(fixture.redraw().is_empty());
+    assert_eq!(fixture.headless.borrow().frame_count(), 2);
+}
+
+#[test]
+fn removed_window_reveals_window_below() {
+    let fixture = Fixture::new();
+    window(&fixture.ctx, 0, 0, 60, 60, RED);
+    let top = window(&fixture.ctx, 20, 20, 60, 60, BLUE);
+    fixture.redraw();
+    assert_eq!(fixture.pixel(40, 40), BLUE);
+
+    fixture.ctx.window_manager.window_remove(top);
+    fixture.redraw();
+    assert_eq!(fixture.pixel(40, 40), RED);
+    assert_eq!(fixture.pixel(70, 70), BACKGROUND);
+}
+
+#[test]
+fn transparent_window_does_not_hide_window_below() {
+    let fixture = Fixture::new();
+    window(&fixture.ctx, 0, 0, 60, 60, RED);
+    let image = Image::from_color(60, 60, Color::rgba(0, 0, 255, 128));
+    window_with(&fixture.ctx, 0, 0, image, true);
+    fixture.redraw();
+
+    let pixel = fixture.pixel(30, 30);
+    assert!(pixel.r() > 0 && pixel.b() > 0, "{:?}", pixel);
+    assert_eq!(pixel.g(), 0);
+}
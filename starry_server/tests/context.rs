@@ -0,0 +1,176 @@
+use std::{env, fs, mem, process, sync::Arc, time::Duration};
+
+use starry_client::base::event::{ButtonEvent, EventOption, MouseUpdateEvent};
+use starry_server::{
+    config::Config,
+    core::{backend::headless::HeadlessBackend, event_loop::Poller, Context},
+    error::StarryError,
+};
+
+mod common;
+
+use common::{context, window, BLUE, RED};
+
+/// 取出窗口队列中的事件
+fn take_events(ctx: &Context, id: usize) -> Vec<EventOption> {
+    let mut windows = ctx.window_manager.windows.borrow_mut();
+    mem::take(&mut windows.get_mut(&id).unwrap().events)
+        .into_iter()
+        .map(|event| event.to_option())
+        .collect()
+}
+
+fn move_to(ctx: &Context, x: i32, y: i32) {
+    ctx.window_manager
+        .send_event(MouseUpdateEvent { x, y }.to_event());
+    ctx.window_manager.handle_all_events();
+}
+
+fn press(ctx: &Context, left: bool) {
+    let mut button = ButtonEvent::new(0);
+    button.left = left;
+    ctx.window_manager.send_event(button.to_event());
+    ctx.window_manager.handle_all_events();
+}
+
+#[test]
+fn contexts_are_isolated() {
+    let a = context(200, 100);
+    let b = context(320, 240);
+
+    let id = window(&a, 0, 0, 10, 10, RED);
+    assert_eq!(a.window_manager.windows.borrow().len(), 1);
+    assert_eq!(a.window_manager.focused_window(), Some(id));
+    assert!(b.window_manager.windows.borrow().is_empty());
+    assert_eq!(b.window_manager.focused_window(), None);
+
+    assert_eq!(a.window_manager.cursor_x.get(), 100);
+    assert_eq!(b.window_manager.cursor_x.get(), 160);
+}
+
+#[test]
+fn empty_screen_is_rejected() {
+    let backend = Box::new(HeadlessBackend::new(0, 0));
+    let result = Context::new(Arc::new(Config::default()), None, backend, None);
+    assert!(matches!(result, Err(StarryError::InvalidScreen { .. })));
+}
+
+#[test]
+fn missing_input_device_is_retried() {
+    let path = env::temp_dir().join(format!("starry_keyboard_{}", process::id()));
+    let path = path.to_str().unwrap().to_string();
+    let _ = fs::remove_file(&path);
+
+    // 设备缺失时仍能启动
+    let config = Config {
+        keyboard_device: path.clone(),
+        ..common::config()
+    };
+    let backend = Box::new(HeadlessBackend::new(200, 100));
+    let ctx = Context::new(Arc::new(config), None, backend, None).unwrap();
+    assert!(ctx.input_manager.devices().is_empty());
+
+    // 设备出现后重试时被打开
+    fs::write(&path, []).unwrap();
+    ctx.input_manager.retry_devices();
+    assert_eq!(ctx.input_manager.devices(), vec![path.clone()]);
+    ctx.input_manager.retry_devices();
+    assert_eq!(ctx.input_manager.devices().len(), 1);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn input_device_at_eof_is_dropped_and_retried() {
+    let path = env::temp_dir().join(format!("starry_keyboard_eof_{}", process::id()));
+    let path = path.to_str().unwrap().to_string();
+    fs::write(&path, []).unwrap();
+
+    let config = Config {
+        keyboard_device: path.clone(),
+        ..common::config()
+    };
+    let backend = Box::new(HeadlessBackend::new(200, 100));
+    let ctx = Context::new(Arc::new(config), None, backend, None).unwrap();
+    assert_eq!(ctx.input_manager.devices(), vec![path.clone()]);
+
+    // 普通文件到达末尾后始终可读，读不到数据时设备被关闭
+    let mut poller = Poller::new();
+    ctx.input_manager.register_fds(&mut poller);
+    assert_eq!(poller.wait(Some(Duration::ZERO)).unwrap(), 1);
+    ctx.input_manager.polling_all(&poller);
+    assert!(ctx.input_manager.devices().is_empty());
+
+    // 之后重试时重新打开
+    ctx.input_manager.retry_devices();
+    assert_eq!(ctx.input_manager.devices(), vec![path.clone()]);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn new_window_is_focused_and_on_top() {
+    let ctx = context(200, 100);
+    let first = window(&ctx, 0, 0, 50, 50, RED);
+    let second = window(&ctx, 20, 20, 50, 50, BLUE);
+
+    assert_eq!(ctx.window_manager.focused_window(), Some(second));
+    assert_eq!(ctx.window_manager.order.borrow().front(), Some(&second));
+
+    ctx.window_manager.raise_window(first);
+    assert_eq!(ctx.window_manager.order.borrow().front(), Some(&first));
+}
+
+#[test]
+fn pointer_enters_and_leaves_windows() {
+    let ctx = context(200, 100);
+    let left = window(&ctx, 0, 0, 50, 50, RED);
+    let right = window(&ctx, 100, 0, 50, 50, BLUE);
+    take_events(&ctx, left);
+    take_events(&ctx, right);
+
+    move_to(&ctx, 10, 10);
+    match take_events(&ctx, left).as_slice() {
+        [EventOption::Enter(event)] => assert_eq!((event.x, event.y), (10, 10)),
+        other => panic!("unexpected events {:?}", other),
+    }
+
+    move_to(&ctx, 110, 5);
+    assert!(matches!(
+        take_events(&ctx, left).as_slice(),
+        [EventOption::Leave(_)]
+    ));
+    match take_events(&ctx, right).as_slice() {
+        [EventOption::Enter(event)] => assert_eq!((event.x, event.y), (10, 5)),
+        other => panic!("unexpected events {:?}", other),
+    }
+}
+
+#[test]
+fn pressed_button_grabs_pointer() {
+    let ctx = context(200, 100);
+    let left = window(&ctx, 0, 0, 50, 50, RED);
+    let right = window(&ctx, 100, 0, 50, 50, BLUE);
+
+    move_to(&ctx, 110, 5);
+    press(&ctx, true);
+    take_events(&ctx, left);
+    take_events(&ctx, right);
+
+    // 按住按键移出窗口，事件仍发送给按下时所在的窗口
+    move_to(&ctx, 10, 10);
+    assert!(take_events(&ctx, left).is_empty());
+    match take_events(&ctx, right).as_slice() {
+        [EventOption::MouseUpdate(event)] => assert_eq!((event.x, event.y), (-90, 10)),
+        other => panic!("unexpected events {:?}", other),
+    }
+
+    // 松开后鼠标所在的窗口收到进入事件
+    press(&ctx, false);
+    assert!(matches!(
+        take_events(&ctx, right).as_slice(),
+        [EventOption::Button(button), EventOption::Leave(_)] if !button.left
+    ));
+    match take_events(&ctx, left).as_slice() {
+        [EventOption::Enter(event)] => assert_eq!((event.x, event.y), (10, 10)),
+        other => panic!("unexpected events {:?}", other),
+    }
+}
@@ -1,14 +1,14 @@
 use starry_client::{
     base::{
         color::Color,
+        event::EventOption,
         renderer::{RenderMode, Renderer},
     },
     window::Window,
 };
 use std::{
     cell::{Cell, RefCell},
-    fs::File,
-    io::Read,
+    mem,
     sync::{Arc, Weak},
     thread,
     time::Duration,
@@ -16,9 +16,7 @@ use std::{
 
 use crate::{traits::focus::Focus, util::widget_set_panel, widgets::Widget};
 
-use super::{event::Event, rect::Rect};
-
-const TTY_DEVICE_PATH: &str = "/dev/char/tty0";
+use super::{event::Event, rect::Rect, vector2::Vector2};
 
 const DURATION_TIME: Duration = Duration::from_millis(25);
 
@@ -93,8 +91,10 @@ pub struct Panel {
     events: RefCell<Vec<Event>>,
     /// 需要重绘画面
     redraw: Cell<bool>,
-    /// tty文件
-    tty_file: RefCell<File>,
+    /// 鼠标在窗口内的位置
+    mouse_point: Cell<Vector2>,
+    /// 鼠标按键状态(左，中，右)
+    mouse_buttons: Cell<(bool, bool, bool)>,
     /// 渲染模式
     renderer_mode: Cell<PanelRendererMode>,
 }
@@ -117,9 +117,8 @@ impl Panel {
             focused_widget: RefCell::new(None),
             events: RefCell::new(Vec::new()),
             redraw: Cell::new(false),
-            tty_file: RefCell::new(
-                File::open(TTY_DEVICE_PATH).expect("[Error] Panel failed to open tty file"),
-            ),
+            mouse_point: Cell::new(Vector2::default()),
+            mouse_buttons: Cell::new((false, false, false)),
             renderer_mode: Cell::new(PanelRendererMode::Normal),
         });
 
@@ -269,13 +268,14 @@ impl Panel {
     }
 
     pub fn tick(&self) {
-        // TODO 通过服务器，先从Window对象接收事件，再进行处理
+        self.polling_window();
         self.handle_events();
     }
 
-    /// 将事件传递给Widget对象
+    /// 将事件按到达顺序传递给Widget对象
     fn handle_events(&self) {
-        while let Some(event) = self.events.borrow_mut().pop() {
+        let events = mem::take(&mut *self.events.borrow_mut());
+        for event in events {
             // 事件是否已被处理
             let caught = Cell::new(false);
 
@@ -290,14 +290,13 @@ impl Panel {
         }
     }
 
-    // TODO 临时函数 用于客户端直接处理用户输入
+    /// 添加待处理的事件
     pub fn push_event(&self, event: Event) {
         self.events.borrow_mut().push(event);
     }
 
     pub fn exec(&self) {
         while self.running.get() {
-            self.polling_tty();
             self.tick();
             self.draw_if_needed();
 
@@ -313,20 +312,68 @@ impl Panel {
         }
     }
 
-    // TODO 临时在客户端做输入读取  后续改为由服务器实现
-    fn polling_tty(&self) {
-        let mut bufffer: [u8; 128] = [0; 128];
-        let count = self
-            .tty_file
-            .borrow_mut()
-            .read(&mut bufffer)
-            .expect("[Error] Panel failed to read tty file");
-        for i in 0..count {
-            self.push_event(Event::KeyPressed {
-                character: Some(bufffer[i] as char),
-            });
+    /// 接收服务器发给窗口的事件，转换为面板事件
+    fn polling_window(&self) {
+        let events = self.window.borrow_mut().events();
+        for event in events {
+            match event.to_option() {
+                EventOption::Key(key_event) => {
+                    let character = Some(key_event.character).filter(|c| *c != '\0');
+                    if key_event.pressed {
+                        self.push_event(Event::KeyPressed { character });
+                    } else {
+                        self.push_event(Event::KeyReleased { character });
+                    }
+                }
+                EventOption::MouseUpdate(mouse_event) => {
+                    self.mouse_point
+                        .set(Vector2::new(mouse_event.x, mouse_event.y));
+                    self.push_mouse_event();
+                }
+                EventOption::Enter(enter_event) => {
+                    self.mouse_point
+                        .set(Vector2::new(enter_event.x, enter_event.y));
+                    self.push_mouse_event();
+                }
+                EventOption::Button(button_event) => {
+                    self.mouse_buttons.set((
+                        button_event.left,
+                        button_event.middle,
+                        button_event.right,
+                    ));
+                    self.push_mouse_event();
+                }
+                EventOption::Scroll(scroll_event) => self.push_event(Event::Scroll {
+                    x: scroll_event.x,
+                    y: scroll_event.y,
+                }),
+                EventOption::WindowResize(resize_event) => {
+                    let mut rect = self.rect.get();
+                    rect.width = resize_event.width;
+                    rect.height = resize_event.height;
+                    self.rect.set(rect);
+                    self.push_event(Event::Resize {
+                        width: resize_event.width,
+                        height: resize_event.height,
+                    });
+                    self.redraw.set(true);
+                }
+                EventOption::Quit(_) => self.close(),
+                _ => {}
+            }
         }
     }
+
+    /// 以当前的鼠标位置和按键状态添加鼠标事件
+    fn push_mouse_event(&self) {
+        let (left_button, middle_button, right_button) = self.mouse_buttons.get();
+        self.push_event(Event::Mouse {
+            point: self.mouse_point.get(),
+            left_button,
+            middle_button,
+            right_button,
+        });
+    }
 }
 
 impl Focus for Panel {
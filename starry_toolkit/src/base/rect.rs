@@ -1,3 +1,5 @@
+use starry_client::base::screen::screen_info;
+
 use super::vector2::Vector2;
 
 /// 表示一个矩形区域
@@ -23,6 +25,12 @@ impl Rect {
         }
     }
 
+    /// 返回整个屏幕的矩形区域，屏幕大小从服务器查询
+    pub fn screen() -> Rect {
+        let info = screen_info().expect("[Error] Failed to query screen info from server");
+        Rect::new(0, 0, info.width, info.height)
+    }
+
     /// 返回矩形左上角的位置点
     pub fn top_left_pos(&self) -> Vector2 {
         Vector2::new(self.x, self.y)
@@ -1,5 +1,4 @@
 use starry_client::base::color::Color;
-use starry_server::core::{SCREEN_HEIGHT, SCREEN_WIDTH};
 use starry_toolkit::base::{
     panel::{Panel, PanelRendererMode},
     rect::Rect,
@@ -10,11 +9,7 @@ use starry_toolkit::{
 };
 
 fn main() {
-    let panel = Panel::new(
-        Rect::new(0, 0, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32),
-        "Title",
-        Color::rgb(255, 255, 255),
-    );
+    let panel = Panel::new(Rect::screen(), "Title", Color::rgb(255, 255, 255));
     // 显示矩形线框
     panel.set_renderer_mode(PanelRendererMode::WithWireframe);
 
@@ -5,7 +5,6 @@ use std::{
 };
 
 use starry_client::base::renderer::Renderer;
-use starry_server::core::{SCREEN_HEIGHT, SCREEN_WIDTH};
 
 use crate::{
     base::{event::Event, panel::Panel, rect::Rect, vector2::Vector2},
@@ -156,7 +155,7 @@ pub trait Widget: Any {
             self.panel().borrow().clone().unwrap().rect()
         } else {
             // 否则以整个屏幕作为参考
-            Rect::new(0, 0, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32)
+            Rect::screen()
         };
 
         let target_rect = align_rect(